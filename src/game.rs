@@ -1,21 +1,32 @@
-use std::{
-    marker::PhantomData,
-    mem,
-    ops::{Add, AddAssign, SubAssign},
-};
+use std::marker::PhantomData;
+pub mod balance;
+pub mod bot;
 mod cities;
+pub mod cities_and_knights;
 mod colors;
+mod desync;
 mod development_card_actions;
 mod development_cards;
 mod dice;
+pub mod editor;
+pub mod game_rules;
+pub mod hot_seat;
 mod larget_army;
 mod longest_road;
+pub mod map;
 mod positions;
+pub mod replay;
 mod resources;
 mod resources_management;
 mod roads;
 mod robber;
+mod rules;
+pub mod save;
+#[cfg(test)]
+mod scenarios;
 pub mod setup_game;
+mod ships;
+pub mod synctest;
 mod towns;
 mod turn_ui;
 use bevy::{ecs::system::SystemParam, prelude::*};
@@ -26,32 +37,50 @@ use bevy_ggrs::{
 };
 use bevy_matchbox::prelude::PeerId;
 use itertools::Itertools;
+use rand_xoshiro::Xoshiro256PlusPlus;
 use serde::{Deserialize, Serialize};
 
 use self::{
+    balance::BoardConstraints,
+    bot::{BotPlugin, Bots},
     cities::City,
+    cities_and_knights::{CitiesAndKnightsPlugin, PlayCitiesAndKnights, ProgressCard, Track},
     colors::{
         CatanColor, CatanColorRef, ColorIterator, CurrentColor, CurrentSetupColor,
         SetupColorIterator,
     },
-    colors::{set_color, set_setup_color},
+    colors::{set_color, set_setup_color, set_special_building_color},
+    desync::{DesyncDetected, DesyncPlugin},
     development_card_actions::{
         MonopolyButton, RoadBuildingState, YearOfPlentyButton, YearOfPlentyState,
     },
     development_cards::DevelopmentCard,
     development_cards::{DevelopmentCards, DevelopmentCardsPile},
-    larget_army::LargestArmyPlugin,
+    editor::EditorPlugin,
+    game_rules::GameRules,
+    hot_seat::HotSeatPlugin,
+    larget_army::{LargestArmyPlugin, LargetArmyRef},
     longest_road::LongestRoadPlugin,
-    longest_road::PlayerLongestRoad,
+    longest_road::{LongestRoadRef, PlayerLongestRoad},
+    map::LoadedMap,
     positions::{BuildingPosition, Position, RoadPosition},
-    resources::DEVELOPMENT_CARD_RESOURCES,
+    replay::{Replay, ReplayPlugin, SaveReplayButton},
     resources::Resources,
     resources_management::ResourceManagmentPlugin,
     resources_management::TradingResources,
     roads::{PlaceRoadButtonState, RoadPlaceButton},
     roads::{Road, RoadUI},
-    robber::{Robber, RobberButton, RobberChooseColorButton, RobberDiscard, RobberResourceSpinner},
+    robber::{
+        PirateButton, Robber, RobberButton, RobberChooseColorButton, RobberDiscard,
+        RobberResourceSpinner,
+    },
+    rules::TradeId,
+    save::{LoadedGame, SaveButton, SavePlugin},
     setup_game::Ports,
+    ships::{
+        DrawnShip, GoldButton, MoveShipButtonState, PirateHighlighter, PlaceShipButtonState, Ship,
+        ShipMoveButton, ShipPlaceButton, ShipUI,
+    },
     towns::{PlaceTownButtonState, TownPlaceButton},
     towns::{Town, TownUI},
     turn_ui::{DieButton, PlayerBanner},
@@ -61,7 +90,6 @@ use crate::{
     game::{
         cities::{CityPlaceButton, CityUI, PlaceCityButtonState},
         positions::FPosition,
-        resources_management::{AcceptTrade, RejectTrade},
         robber::RobberHighlighter,
     },
     utils::{
@@ -82,30 +110,156 @@ pub enum Input {
     #[default]
     None,
     NextColor,
-    // player, place, cost multiplier
+    // the rules charge for the pieces themselves
     // also used for road building
-    AddRoad(RoadPosition, Resources),
-    AddCity(BuildingPosition, Resources),
-    AddTown(BuildingPosition, Resources, bool),
+    AddRoad(RoadPosition),
+    AddCity(BuildingPosition),
+    AddTown(BuildingPosition),
     TakeDevelopmentCard,
+    PlayDevelopmentCard(DevelopmentCard),
 
-    Roll(u8, u8, u8, Option<bool>),
+    // the dice are rolled by every peer from the `SessionRng`
+    Roll,
     // for each year of plenty done twice
     // maybe just send one with two resources
     YearOfPlenty(resources::Resource),
     Monopoly(resources::Resource),
-    // person picked from, and card picked, if there is a discard(cards discarded if needed)
-    Knight(Entity, resources::Resource, Position),
+    // person picked from, the card taken is picked from the `SessionRng`
+    Knight(PlayerHandle, Position),
     // discard
     RobberDiscard(Resources),
-    // need way to cancel trade
-    Trade(TradingResources),         // interactive(TradeResponce)
-    TradeResponce(TradingResources), // interactive(TradeAccept)
-    TradeAccept(TradingResources, Entity),
+    // offered to someone or everyone (`None`)
+    Trade(TradingResources, Option<PlayerHandle>), // interactive(TradeResponce, CounterOffer)
+    TradeResponce(TradeId),                        // interactive(TradeAccept, CounterOffer)
+    CounterOffer(TradeId, TradingResources),
+    CancelTrade(TradeId),
+    TradeAccept(TradeId),
     BankTrade(TradingResources),
     Win,
     // move knight but don't take resources (nothing to take)
     MoveKnight(Position),
+    // seafarers
+    AddShip(RoadPosition),
+    MoveShip(RoadPosition, RoadPosition),
+    // like the knight but on the sea
+    Pirate(PlayerHandle, Position),
+    MovePirate(Position),
+    // one resource from a gold field
+    Gold(resources::Resource),
+    // cities & knights
+    Improve(Track),
+    AddKnight(BuildingPosition),
+    PromoteKnight(BuildingPosition),
+    ActivateKnight(BuildingPosition),
+    ChaseRobber(BuildingPosition),
+    PlayProgressCard(ProgressCard),
+    CommodityMonopoly(resources::Commodity),
+}
+impl Input {
+    /// what this input means to the rules, inputs that are only for the ui (like winning)
+    /// have no action but still have to make sense at this point of the game
+    fn to_action(
+        self,
+        player: PlayerHandle,
+        rules: &rules::GameState,
+        rng: &mut SessionRng,
+    ) -> Result<Option<rules::Action>, rules::RuleError> {
+        let action = match self {
+            Self::None => None,
+            // the rules already decided that someone won, this is just for the ui
+            Self::Win => {
+                if rules.phase != rules::Phase::Finished(player) {
+                    return Err(rules::RuleError::WrongPhase);
+                }
+                None
+            }
+            Self::Trade(trade, to) => Some(rules::Action::OfferTrade(to, trade)),
+            // taking an offer as it is, is just a counter offer with the same trade
+            Self::TradeResponce(id) => {
+                let offer = rules
+                    .trades
+                    .iter()
+                    .find(|offer| offer.id == id)
+                    .ok_or(rules::RuleError::UnknownTrade)?;
+                Some(rules::Action::CounterOffer(id, offer.trade))
+            }
+            Self::CounterOffer(id, trade) => Some(rules::Action::CounterOffer(id, trade)),
+            Self::CancelTrade(id) => Some(rules::Action::CancelTrade(id)),
+            Self::NextColor => Some(rules::Action::EndTurn),
+            Self::AddRoad(road) => Some(rules::Action::PlaceRoad(road)),
+            Self::AddCity(city) => Some(rules::Action::PlaceCity(city)),
+            Self::AddTown(town) => Some(rules::Action::PlaceTown(town)),
+            Self::TakeDevelopmentCard => Some(rules::Action::BuyDevelopmentCard),
+            Self::PlayDevelopmentCard(card) => Some(rules::Action::PlayDevelopmentCard(card)),
+            Self::Roll => {
+                let (d1, d2) = dice::roll_dice(rng);
+                if rules.cities_and_knights.is_some() {
+                    let event = dice::roll_event_die(rng);
+                    Some(rules::Action::RollWithEvent(d1, d2, event))
+                } else {
+                    Some(rules::Action::Roll(d1, d2))
+                }
+            }
+            Self::YearOfPlenty(resource) => Some(rules::Action::YearOfPlenty(resource)),
+            Self::Monopoly(resource) => Some(rules::Action::Monopoly(resource)),
+            Self::Knight(robbed, position) => {
+                let robbed_resources = &rules
+                    .player(robbed)
+                    .ok_or(rules::RuleError::UnknownPlayer)?
+                    .resources;
+                let resource = resources::take_resource(robbed_resources, &mut rng.0)
+                    .ok_or(rules::RuleError::InvalidRobber)?;
+                Some(rules::Action::MoveRobber(
+                    position,
+                    Some((robbed, resource)),
+                ))
+            }
+            Self::MoveKnight(position) => Some(rules::Action::MoveRobber(position, None)),
+            Self::Pirate(robbed, position) => {
+                let robbed_resources = &rules
+                    .player(robbed)
+                    .ok_or(rules::RuleError::UnknownPlayer)?
+                    .resources;
+                let resource = resources::take_resource(robbed_resources, &mut rng.0)
+                    .ok_or(rules::RuleError::InvalidPirate)?;
+                Some(rules::Action::MovePirate(
+                    position,
+                    Some((robbed, resource)),
+                ))
+            }
+            Self::MovePirate(position) => Some(rules::Action::MovePirate(position, None)),
+            Self::AddShip(ship) => Some(rules::Action::PlaceShip(ship)),
+            Self::MoveShip(from, to) => Some(rules::Action::MoveShip(from, to)),
+            Self::Gold(resource) => Some(rules::Action::PickGold(resource)),
+            Self::RobberDiscard(resources) => Some(rules::Action::Discard(resources)),
+            Self::TradeAccept(id) => Some(rules::Action::AcceptTrade(id)),
+            Self::BankTrade(trade) => Some(rules::Action::BankTrade(trade)),
+            Self::Improve(track) => Some(rules::Action::Improve(track)),
+            Self::AddKnight(position) => Some(rules::Action::PlaceKnight(position)),
+            Self::PromoteKnight(position) => Some(rules::Action::PromoteKnight(position)),
+            Self::ActivateKnight(position) => Some(rules::Action::ActivateKnight(position)),
+            Self::ChaseRobber(position) => Some(rules::Action::ChaseRobber(position)),
+            Self::PlayProgressCard(card) => Some(rules::Action::PlayProgressCard(card)),
+            Self::CommodityMonopoly(commodity) => Some(rules::Action::CommodityMonopoly(commodity)),
+        };
+        Ok(action)
+    }
+    /// checks the input against the rules and applies it
+    // dice and steals are drawn from a copy, so a rejected input doesn't move the rng along (it
+    // isn't in `Moves`, so a replay would roll different dice from there on)
+    fn apply(
+        self,
+        player: PlayerHandle,
+        rules: &mut rules::GameState,
+        rng: &mut SessionRng,
+    ) -> Result<rules::Events, rules::RuleError> {
+        let mut drawn = rng.clone();
+        let events = self
+            .to_action(player, rules, &mut drawn)
+            .and_then(|action| action.map_or(Ok(vec![]), |action| rules.apply(player, action)))?;
+        *rng = drawn;
+        Ok(events)
+    }
 }
 pub type GgrsSessionConfig = bevy_ggrs::GgrsConfig<Input, PeerId>;
 pub struct GamePlugin;
@@ -113,14 +267,29 @@ pub struct GamePlugin;
 fn read_local_inputs(
     mut commands: Commands<'_, '_>,
     local_players: Res<'_, LocalPlayers>,
+    local_player: Res<'_, LocalPlayerHandle>,
     mut current_inputs: ResMut<'_, Input>,
+    mut bots: ResMut<'_, Bots>,
+    rules: Option<Res<'_, Rules>>,
 ) {
     commands.insert_resource(LocalInputs::<GgrsSessionConfig>(
         // updating of the input should happen on the fly
+        // bots are local players too, but they come up with their own inputs, and in hot seat only
+        // the player with the ui gets its input
         local_players
             .0
             .iter()
-            .map(|h| (*h, *current_inputs))
+            .map(|h| {
+                let bot = rules
+                    .as_ref()
+                    .and_then(|rules| bots.input(PlayerHandle(*h), rules));
+                let input = bot.unwrap_or(if *h == local_player.0 {
+                    *current_inputs
+                } else {
+                    Input::None
+                });
+                (*h, input)
+            })
             .collect(),
     ));
     *current_inputs = Input::None;
@@ -128,6 +297,15 @@ fn read_local_inputs(
 
 #[derive(Resource, Default, Clone, Copy, Debug, Deref, DerefMut)]
 pub struct SessionSeed(pub u64);
+/// every random thing that happens during the game (dice, robber steals) comes from here, it
+/// continues from the board generation so it is the same for every peer and can be replayed from
+/// the `SessionSeed`
+#[derive(Resource, Clone, Debug, Deref, DerefMut)]
+pub struct SessionRng(pub Xoshiro256PlusPlus);
+/// the actual state of the game, components like `Resources` and `VictoryPoints` are just a view
+/// of this
+#[derive(Resource, Clone, Debug, Deref, DerefMut)]
+pub struct Rules(pub rules::GameState);
 pub fn cleanup(
     nodes: Query<'_, '_, Entity, With<Node>>,
     game_stuff: Query<'_, '_, Entity, With<KatanComponent>>,
@@ -145,6 +323,7 @@ pub fn cleanup(
 pub struct NewGameButton;
 
 fn new_game_interaction(
+    mut commands: Commands<'_, '_>,
     mut state: ResMut<'_, NextState<AppState>>,
     interaction_query: Single<
         '_,
@@ -157,6 +336,8 @@ fn new_game_interaction(
     match *interaction {
         Interaction::Pressed => {
             state.set(AppState::Menu);
+            // otherwise the replay would start again
+            commands.remove_resource::<Replay>();
 
             *color = PRESSED_BUTTON.into();
             button.set_changed();
@@ -171,30 +352,16 @@ fn new_game_interaction(
     }
 }
 
-#[derive(Resource)]
-struct Moves(Vec<(PlayerHandle, Input)>);
+/// every input the rules accepted, in the order they were applied, together with the seed and
+/// player count this is enough to replay the whole game
+#[derive(Resource, Clone, Debug, Default)]
+pub struct Moves(pub Vec<(PlayerHandle, Input)>);
 
 #[derive(SystemParam)]
 pub struct UpdateState<'w, 's> {
-    inputs: Res<'w, PlayerInputs<GgrsSessionConfig>>,
-    players: Query<
-        'w,
-        's,
-        (
-            Entity,
-            &'static PlayerHandle,
-            &'static mut Resources,
-            &'static CatanColor,
-            &'static mut VictoryPoints,
-            &'static mut Ports,
-            &'static mut Left<Road>,
-            &'static mut Left<Town>,
-            &'static mut Left<City>,
-            &'static mut DevelopmentCards,
-        ),
-    >,
-
-    ports: Query<'w, 's, (&'static BuildingPosition, &'static Port)>,
+    inputs: Res<'w, ValidInputs>,
+    rules_events: Res<'w, RulesEvents>,
+    players: PlayerEntities<'w, 's>,
     player_banners: Query<
         'w,
         's,
@@ -206,7 +373,8 @@ pub struct UpdateState<'w, 's> {
     >,
 
     towns: Query<'w, 's, (Entity, &'static BuildingPosition), With<Town>>,
-    bank: ResMut<'w, Resources>,
+    ships: Query<'w, 's, (Entity, &'static RoadPosition), With<Ship>>,
+    drawn_ships: Query<'w, 's, (Entity, &'static DrawnShip)>,
     layout: Res<'w, Layout>,
     commands: Commands<'w, 's>,
     meshes: ResMut<'w, Assets<Mesh>>,
@@ -218,23 +386,16 @@ pub struct UpdateState<'w, 's> {
     setup_color_rotation: ResMut<'w, SetupColorIterator>,
     color_rotation: ResMut<'w, ColorIterator>,
 
-    free_dev_cards: ResMut<'w, DevelopmentCardsPile>,
     local_player: Res<'w, LocalPlayer>,
     app_state: ResMut<'w, NextState<AppState>>,
-
-    board: Query<'w, 's, (&'static Hexagon, &'static Number, &'static Position)>,
-    robber: ResMut<'w, Robber>,
-
-    robber_transform: Single<'w, 's, &'static mut Transform, With<RobberHighlighter>>,
-    moves: ResMut<'w, Moves>,
+    rules: Res<'w, Rules>,
 }
 fn update_from_inputs(
     UpdateState {
         inputs,
+        rules_events,
         players,
-        ports,
         mut player_banners,
-        mut bank,
         layout,
         mut commands,
         mut meshes,
@@ -245,63 +406,69 @@ fn update_from_inputs(
         mut color_r,
         mut setup_color_rotation,
         mut color_rotation,
-        mut free_dev_cards,
         local_player,
         mut app_state,
-        mut robber,
-        board,
-        mut robber_transform,
         towns,
-        mut moves,
+        ships,
+        drawn_ships,
+        rules,
     }: UpdateState<'_, '_>,
 ) {
     let count = inputs.iter().filter(|(i, _)| *i != Input::None).count();
     if count != 0 {
         println!(
             "new {:?} {:?}",
-            players.iter().map(|p| (p.3, inputs[p.1.0])).collect_vec(),
+            (0..inputs.len())
+                .filter_map(|handle| players.get(PlayerHandle(handle)))
+                .map(|player| (player.color, inputs[player.handle.0].0))
+                .collect_vec(),
             game_state.get()
         );
     }
     let scale = 3.;
 
-    moves.0.extend(
-        inputs
-            .iter()
-            .enumerate()
-            .filter(|(_, (i, state))| {
-                matches!(i, Input::None) && state != &InputStatus::Predicted
-                    || state != &InputStatus::Disconnected
-            })
-            .map(|(i, (input, _))| (PlayerHandle(i), *input)),
-    );
-    for (
-        entity,
-        player_handle,
-        mut player_resources,
-        color,
-        mut vps,
-        mut player_ports,
-        mut roads_left,
-        mut towns_left,
-        mut cities_left,
-        mut player_dev_cards,
-    ) in players
-    {
-        let (input, state) = inputs[player_handle.0];
-        if state == InputStatus::Predicted {
+    // numbers (resources, points, pieces left ...) are updated by the rules, here we just spawn
+    // the pieces and ui
+    for (handle, (input, state)) in inputs.iter().enumerate() {
+        if *state == InputStatus::Predicted {
             continue;
         }
-        match input {
-            Input::None => {}
-            Input::MoveKnight(block) => {
-                robber.0 = block;
-
-                let (x, y) = FPosition::from(block).hex_to_pixel();
-                **robber_transform = Transform::from_xyz(x * 77.0, y * 77., 0.0);
-            }
+        let Some(player_ref) = players.get(PlayerHandle(handle)) else {
+            continue;
+        };
+        let CatanColorRef { color, entity, .. } = player_ref;
+        match *input {
+            // handeld by sync_rules
+            Input::None
+            | Input::MoveKnight(_)
+            | Input::Knight(_, _)
+            | Input::TakeDevelopmentCard
+            | Input::PlayDevelopmentCard(_)
+            | Input::YearOfPlenty(_)
+            | Input::Monopoly(_)
+            | Input::RobberDiscard(_)
+            | Input::BankTrade(_)
+            | Input::Pirate(_, _)
+            | Input::MovePirate(_)
+            | Input::Gold(_)
+            // the knights and pillaged cities are drawn from the rules
+            | Input::Improve(_)
+            | Input::AddKnight(_)
+            | Input::PromoteKnight(_)
+            | Input::ActivateKnight(_)
+            | Input::ChaseRobber(_)
+            | Input::PlayProgressCard(_)
+            | Input::CommodityMonopoly(_) => {}
             Input::Win => {
                 app_state.set(AppState::GameOver);
+                // the hidden victory point cards are revealed at the end
+                let won = match rules
+                    .player(PlayerHandle(handle))
+                    .map(|player| player.victory_points.from_development_cards)
+                {
+                    Some(cards @ 1..) => format!("Won ({cards} victory point cards)"),
+                    _ => "Won".to_string(),
+                };
                 commands.spawn((
                     Node {
                         width: Val::Percent(100.0),
@@ -355,7 +522,7 @@ fn update_from_inputs(
                                         }
                                     ),
                                     (
-                                        Text::new("Won".to_string()),
+                                        Text::new(won),
                                         TextColor(BORDER_COLOR_ACTIVE),
                                         TextFont {
                                             font_size: 34.,
@@ -412,20 +579,32 @@ fn update_from_inputs(
                         &mut color_rotation,
                     );
                 } else {
-                    set_color(
-                        &mut color_r,
-                        &mut color_rotation,
-                        &local_player,
-                        &mut mut_game_state,
-                        &mut player_banners,
-                    );
+                    if let rules::Phase::SpecialBuilding(builder) = rules.phase
+                        && let Some(builder) = players.get(builder)
+                    {
+                        set_special_building_color(
+                            &mut color_r,
+                            builder,
+                            &local_player,
+                            &mut mut_game_state,
+                            &mut player_banners,
+                        );
+                    } else {
+                        set_color(
+                            &mut color_r,
+                            &mut color_rotation,
+                            &local_player,
+                            &mut mut_game_state,
+                            &mut player_banners,
+                        );
+                    }
                     commands
                         .entity(layout.trades)
                         .despawn_children()
                         .with_child(Text("trades".to_string()));
                 }
             }
-            Input::AddRoad(road_position, cost) => {
+            Input::AddRoad(road_position) => {
                 println!("new road");
                 // TODO: for current player do it in the road button, so that if this function
                 // (update_inputs) runs after the place (setup) town - so when place town runs all
@@ -433,10 +612,8 @@ fn update_from_inputs(
                 // by towns for ports, development cards are fine b/c you cant use until next turn
                 // anyway - might need to do in other places but this is all that I can think of
                 // right now
-                bank.add_assign(cost);
-                player_resources.sub_assign(cost);
                 let road = commands
-                    .spawn((Road, road_position, *color))
+                    .spawn((Road, road_position, color))
                     .add_rollback()
                     .id();
                 commands.entity(entity).add_child(road);
@@ -444,39 +621,58 @@ fn update_from_inputs(
                     road_position,
                     &mut meshes,
                     &mut materials,
-                    *color,
+                    color,
+                    scale,
+                ));
+            }
+            Input::AddShip(ship_position) => {
+                let ship = commands
+                    .spawn((Ship, ship_position, color))
+                    .add_rollback()
+                    .id();
+                commands.entity(entity).add_child(ship);
+                commands.spawn(ShipUI::bundle(
+                    ship_position,
+                    &mut meshes,
+                    &mut materials,
+                    color,
+                    scale,
+                ));
+            }
+            Input::MoveShip(from, to) => {
+                if let Some((ship, _)) = ships.iter().find(|(_, position)| **position == from) {
+                    commands.entity(ship).insert(to);
+                }
+                if let Some((drawn, _)) = drawn_ships.iter().find(|(_, drawn)| drawn.0 == from) {
+                    commands.entity(drawn).despawn();
+                }
+                commands.spawn(ShipUI::bundle(
+                    to,
+                    &mut meshes,
+                    &mut materials,
+                    color,
                     scale,
                 ));
-
-                roads_left.0 -= 1;
             }
             // make sure entity(of child town) is synced between client
-            Input::AddCity(city_position, cost) => {
+            Input::AddCity(city_position) => {
                 if let Some((entity, _)) = towns
                     .iter()
                     .find(|(_, town_position)| **town_position == city_position)
                 {
                     commands.entity(entity).remove::<Town>().insert(City);
-                    towns_left.0 += 1;
-                    cities_left.0 -= 1;
-                    *player_resources -= cost;
-                    vps.actual += 1;
-
-                    bank.add_assign(cost);
                     commands.spawn(CityUI::bundle(
                         city_position,
                         &mut meshes,
                         &mut materials,
-                        *color,
+                        color,
                         scale,
                     ));
                 }
             }
-            Input::AddTown(town_position, cost, next) => {
-                bank.add_assign(cost);
-                player_resources.sub_assign(cost);
+            Input::AddTown(town_position) => {
                 let town = commands
-                    .spawn((Town, town_position, *color))
+                    .spawn((Town, town_position, color))
                     .add_rollback()
                     .id();
                 commands.entity(entity).add_child(town);
@@ -484,30 +680,14 @@ fn update_from_inputs(
                     town_position,
                     &mut meshes,
                     &mut materials,
-                    *color,
+                    color,
                     scale,
                 ));
-                if let Some((_, port)) = ports
+                // a setup town ends the setup turn, the rules already moved on to the next player
+                if rules_events[handle]
                     .iter()
-                    .find(|(port_position, _)| **port_position == town_position)
+                    .any(|event| matches!(event, rules::Event::NextPlayer(_)))
                 {
-                    *player_ports += *port;
-                }
-
-                vps.actual += 1;
-                towns_left.0 -= 1;
-                // if this player is done all their towns then add the resources from their last
-                // pick
-                if towns_left.0 == 3 {
-                    let initial_resources = board
-                        .iter()
-                        .filter(|hex| town_position.contains(hex.2))
-                        .filter_map(|hex| hex.0.to_resources())
-                        .fold(Resources::empty(), Add::add);
-                    bank.sub_assign(initial_resources);
-                    player_resources.add_assign(initial_resources);
-                }
-                if next {
                     set_setup_color(
                         &mut mut_game_state,
                         &mut setup_color_r,
@@ -519,223 +699,228 @@ fn update_from_inputs(
                     );
                 }
             }
-            Input::TakeDevelopmentCard => {
-                if let Some(card) = free_dev_cards.0.pop() {
-                    let required_resources = DEVELOPMENT_CARD_RESOURCES;
-                    *player_resources -= required_resources;
-                    bank.add_assign(required_resources);
-                    if card == DevelopmentCard::VictoryPoint {
-                        vps.from_development_cards += 1;
-                    }
-                    *player_dev_cards.get_mut(card) += 1;
-                }
-            }
             // handeld by update_from_input_roll
-            Input::Roll(_number, _d1, _d2, _) => (),
-            Input::YearOfPlenty(resource) => {
-                *bank.get_mut(resource) -= 1;
-                *player_resources.get_mut(resource) += 1;
-            }
-            // handeld by update_from_monopoly
-            Input::Monopoly(_resource) => (),
-            // handeld by update_from_knight
-            Input::Knight(_player, _resource, _new_pos) => (),
-
-            Input::RobberDiscard(resources) => {
-                bank.add_assign(resources);
-                player_resources.sub_assign(resources);
-            }
-
-            Input::Trade(trade) => {
-                // we show even if its not possible as after another trade it could be
-                if entity != local_player.0.entity {
-                    commands.entity(layout.trades).with_child((
-                        Node {
-                            display: Display::Grid,
-                            grid_template_columns: vec![
-                                GridTrack::auto(),
-                                GridTrack::auto(),
-                                GridTrack::auto(),
-                            ],
-                            ..Default::default()
-                        },
-                        children![
-                            Text::new(trade.to_string()),
-                            (Button, Text::new("x"), RejectTrade),
-                            (Button, Text::new("Ok"), AcceptTrade { trade })
-                        ],
-                    ));
-                }
-            }
-            Input::TradeResponce(trade) => {
-                let player_ref = CatanColorRef {
-                    color: *color,
-                    entity,
-                    handle: *player_handle,
-                };
-                if *game_state.get() == GameState::Turn {
-                    commands.entity(layout.trades).with_child((
-                        Node {
-                            display: Display::Grid,
-                            grid_template_columns: vec![
-                                GridTrack::auto(),
-                                GridTrack::auto(),
-                                GridTrack::auto(),
-                            ],
-                            ..Default::default()
-                        },
-                        children![
-                            Text::new(trade.to_string()),
-                            (Button, Text::new("x"), RejectTrade, player_ref),
-                            (Button, Text::new("Ok"), AcceptTrade { trade }, player_ref)
-                        ],
-                    ));
-                }
-            }
-            // handeld by update_from_trade_accept
-            Input::TradeAccept(_r, _e) => (),
-            Input::BankTrade(trading_resources) => {
-                bank.sub_assign(trading_resources);
-                player_resources.add_assign(trading_resources);
-            }
+            Input::Roll => (),
+            // trade offers are drawn from the rules by draw_trades
+            Input::Trade(_, _)
+            | Input::TradeResponce(_)
+            | Input::CounterOffer(_, _)
+            | Input::CancelTrade(_)
+            | Input::TradeAccept(_) => (),
         }
     }
 }
-fn update_from_trade_accept(
+/// the inputs of this frame after they went through the rules, rejected inputs are replaced with
+/// `Input::None` so the ui never shows something the rules didn't allow
+#[derive(Resource, Clone, Debug, Default, Deref, DerefMut)]
+pub struct ValidInputs(pub Vec<(Input, InputStatus)>);
+/// an input that was rejected by the rules, every peer checks every input so a modified client
+/// cannot do anything the rules don't allow
+#[derive(Message, Clone, Copy, Debug)]
+pub struct RuleViolation {
+    pub player: PlayerHandle,
+    pub input: Input,
+    pub error: rules::RuleError,
+}
+/// what the rules did with the input of each player (by handle) this frame, so the ui follows the
+/// rules instead of what the input says
+#[derive(Resource, Clone, Debug, Default, Deref, DerefMut)]
+pub struct RulesEvents(pub Vec<rules::Events>);
+// applies every confirmed input to the rules in handle order, so every peer ends up with the same
+// state
+fn update_rules(
     inputs: Res<'_, PlayerInputs<GgrsSessionConfig>>,
-    players: Query<'_, '_, (Entity, &PlayerHandle)>,
-    mut player_resources_q: Query<'_, '_, &mut Resources, With<CatanColor>>,
-    layout: Res<'_, Layout>,
-    mut commands: Commands<'_, '_>,
+    mut valid_inputs: ResMut<'_, ValidInputs>,
+    mut violations: MessageWriter<'_, RuleViolation>,
+    mut rules: ResMut<'_, Rules>,
+    mut rng: ResMut<'_, SessionRng>,
+    mut rules_events: ResMut<'_, RulesEvents>,
+    mut moves: ResMut<'_, Moves>,
 ) {
-    for player in players {
-        if let (Input::TradeAccept(r, trader), InputStatus::Confirmed) = inputs[player.1.0] {
-            if let Ok(mut other_player_resources) = player_resources_q.get_mut(trader) {
-                other_player_resources.sub_assign(r);
+    valid_inputs.0.clear();
+    rules_events.0.clear();
+    for (handle, (input, state)) in inputs.iter().enumerate() {
+        rules_events.push(vec![]);
+        if *state != InputStatus::Confirmed {
+            valid_inputs.push((*input, *state));
+            continue;
+        }
+        let player = PlayerHandle(handle);
+        match input.apply(player, &mut rules, &mut rng) {
+            Ok(events) => {
+                if *input != Input::None {
+                    moves.0.push((player, *input));
+                }
+                valid_inputs.push((*input, *state));
+                rules_events[handle] = events;
             }
-            if let Ok(mut resources) = player_resources_q.get_mut(player.0) {
-                resources.add_assign(r);
+            Err(error) => {
+                violations.write(RuleViolation {
+                    player,
+                    input: *input,
+                    error,
+                });
+                valid_inputs.push((Input::None, *state));
             }
-
-            // TODO: just remove that trade
-            commands
-                .entity(layout.trades)
-                .despawn_children()
-                .with_child(Text("trades".to_string()));
         }
     }
 }
-fn update_from_knight(
-    inputs: Res<'_, PlayerInputs<GgrsSessionConfig>>,
-    players: Query<'_, '_, (Entity, &PlayerHandle)>,
-    mut player_resources_q: Query<'_, '_, &mut Resources, With<CatanColor>>,
+fn log_rule_violations(mut violations: MessageReader<'_, '_, RuleViolation>) {
+    for RuleViolation {
+        player,
+        input,
+        error,
+    } in violations.read()
+    {
+        warn!("rejected {input:?} from {player:?}: {error}");
+    }
+}
+// copies the rules state into the components and resources the ui reads from
+fn sync_rules(
+    rules: Res<'_, Rules>,
+    mut players: Query<
+        '_,
+        '_,
+        (
+            &PlayerHandle,
+            &mut Resources,
+            &mut VictoryPoints,
+            &mut Ports,
+            &mut Left<Road>,
+            &mut Left<Town>,
+            &mut Left<City>,
+            &mut DevelopmentCards,
+            &mut Knights,
+            &mut PlayerLongestRoad,
+            &mut Left<Ship>,
+        ),
+    >,
+    mut bank: ResMut<'_, Resources>,
+    mut free_dev_cards: ResMut<'_, DevelopmentCardsPile>,
     mut robber: ResMut<'_, Robber>,
     mut robber_transform: Single<'_, '_, &mut Transform, With<RobberHighlighter>>,
+    pirate_transform: Option<
+        Single<'_, '_, &mut Transform, (With<PirateHighlighter>, Without<RobberHighlighter>)>,
+    >,
 ) {
-    for player in players {
-        if let (Input::Knight(robbed_player, resource, new_place), InputStatus::Confirmed) =
-            inputs[player.1.0]
-        {
-            if let Ok(mut robbed_resources) = player_resources_q.get_mut(robbed_player) {
-                *robbed_resources.get_mut(resource) -= 1;
-            }
-            if let Ok(mut resources) = player_resources_q.get_mut(player.0) {
-                *resources.get_mut(resource) += 1;
-            }
-            robber.0 = new_place;
-
-            let (x, y) = FPosition::from(new_place).hex_to_pixel();
-            **robber_transform = Transform::from_xyz(x * 77.0, y * 77., 0.0);
-            break;
-        }
+    if !rules.is_changed() {
+        return;
     }
-}
-fn update_from_monopoly(
-    inputs: Res<'_, PlayerInputs<GgrsSessionConfig>>,
-    players: Query<'_, '_, (Entity, &PlayerHandle)>,
-    mut player_resources_q: Query<'_, '_, &mut Resources, With<CatanColor>>,
-) {
-    for player in players {
-        if let (Input::Monopoly(resource), InputStatus::Confirmed) = inputs[player.1.0] {
-            let taken = player_resources_q
-                .iter_mut()
-                .map(|mut r| {
-                    let mut taken = 0;
-                    let original = r.get_mut(resource);
-                    mem::swap(&mut taken, original);
-                    taken
-                })
-                .sum::<u8>();
-
-            if let Ok(mut resources) = player_resources_q.get_mut(player.0) {
-                // we reassign because when we go through the resources we also go through
-                // current color's resources
-                *resources.get_mut(resource) = taken;
-            }
-            break;
+    for (
+        handle,
+        mut resources,
+        mut vps,
+        mut ports,
+        mut roads_left,
+        mut towns_left,
+        mut cities_left,
+        mut development_cards,
+        mut knights,
+        mut longest_road,
+        mut ships_left,
+    ) in &mut players
+    {
+        let Some(player) = rules.player(*handle) else {
+            continue;
+        };
+        resources.set_if_neq(player.resources);
+        vps.set_if_neq(player.victory_points);
+        ports.set_if_neq(player.ports);
+        development_cards.set_if_neq(player.development_cards);
+        knights.set_if_neq(Knights(player.knights));
+        if roads_left.0 != player.roads_left {
+            roads_left.0 = player.roads_left;
+        }
+        if towns_left.0 != player.towns_left {
+            towns_left.0 = player.towns_left;
         }
+        if cities_left.0 != player.cities_left {
+            cities_left.0 = player.cities_left;
+        }
+        if ships_left.0 != player.ships_left {
+            ships_left.0 = player.ships_left;
+        }
+        longest_road.set_if_neq(PlayerLongestRoad(
+            player.longest_road.iter().copied().collect(),
+        ));
+    }
+    bank.set_if_neq(rules.bank);
+    if free_dev_cards.0 != rules.development_cards {
+        free_dev_cards.0.clone_from(&rules.development_cards);
+    }
+    if robber.set_if_neq(Robber(rules.robber)) {
+        let (x, y) = FPosition::from(rules.robber).hex_to_pixel();
+        **robber_transform = Transform::from_xyz(x * 77.0, y * 77., 0.0);
+    }
+    if let (Some(pirate), Some(mut pirate_transform)) = (rules.pirate, pirate_transform) {
+        let (x, y) = FPosition::from(pirate).hex_to_pixel();
+        pirate_transform.set_if_neq(Transform::from_xyz(x * 77.0, y * 77., 0.0));
     }
 }
 fn update_from_inputs_roll(
-    inputs: Res<'_, PlayerInputs<GgrsSessionConfig>>,
+    inputs: Res<'_, ValidInputs>,
     players: Query<'_, '_, (Entity, &PlayerHandle)>,
 
-    player_resources_q: Query<'_, '_, &mut Resources, With<CatanColor>>,
-
     mut die_q: Query<'_, '_, (&mut Text, &mut Transform), With<DieButton>>,
 
-    board: Query<'_, '_, (&Hexagon, &Number, &Position)>,
-    towns: Query<'_, '_, (&ChildOf, &Town, &BuildingPosition), With<CatanColor>>,
-    cities: Query<'_, '_, (&ChildOf, &City, &BuildingPosition), With<CatanColor>>,
-
-    resources: ResMut<'_, Resources>,
-    robber: Res<'_, Robber>,
+    rules: Res<'_, Rules>,
     local_player: Res<'_, LocalPlayer>,
     mut state: ResMut<'_, NextState<GameState>>,
 ) {
     for (entity, player) in players {
-        if let (Input::Roll(roll, d1, d2, is_robber), InputStatus::Confirmed) = inputs[player.0] {
-            dice::update_dice(&mut die_q, d1, d2);
-            match is_robber {
-                Some(true) if local_player.0.entity == entity => {
+        if let (Input::Roll, InputStatus::Confirmed) = inputs[player.0] {
+            if let Some((d1, d2)) = rules.last_roll {
+                dice::update_dice(&mut die_q, d1, d2);
+            }
+            let roller = local_player.0.entity == entity;
+            // resources are handed out by the rules
+            match rules.phase {
+                rules::Phase::Discard(_) if roller => {
                     state.set(GameState::RobberDiscardResources);
                 }
-                Some(true) => {
+                rules::Phase::Discard(_) => {
                     state.set(GameState::RobberDiscardResourcesInActive);
                 }
-                Some(false) => {}
-                None => {
-                    dice::distribute_resources(
-                        roll,
-                        board,
-                        towns,
-                        cities,
-                        player_resources_q,
-                        resources,
-                        robber,
-                    );
+                rules::Phase::PlaceRobber { .. } if roller => {
+                    state.set(GameState::PlaceRobber);
+                }
+                rules::Phase::Turn if roller => {
+                    state.set(GameState::Turn);
                 }
+                _ => {}
             }
 
             break;
         }
     }
 }
-const FPS: usize = 60;
+pub const FPS: usize = 60;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_sub_state::<GameState>()
             .add_sub_state::<YearOfPlentyState>()
             .add_sub_state::<RoadBuildingState>()
-            .add_systems(ReadInputs, read_local_inputs)
+            .add_systems(
+                ReadInputs,
+                read_local_inputs.run_if(not(resource_exists::<Replay>)),
+            )
             .add_plugins((
                 ResourceManagmentPlugin,
                 LargestArmyPlugin,
                 LongestRoadPlugin,
+                DesyncPlugin,
+                SavePlugin,
+                ReplayPlugin,
+                BotPlugin,
+                HotSeatPlugin,
+                EditorPlugin,
+                CitiesAndKnightsPlugin,
             ))
             .insert_resource(Input::None)
+            .init_resource::<ValidInputs>()
+            .add_message::<RuleViolation>()
             .insert_resource(RollbackFrameRate(FPS))
-            .insert_resource(Moves(vec![]))
+            .init_resource::<Moves>()
             .rollback_component_with_copy::<towns::Town>()
             .rollback_component_with_copy::<Left<towns::Town>>()
             .rollback_component_with_copy::<Left<cities::City>>()
@@ -743,11 +928,18 @@ impl Plugin for GamePlugin {
             .rollback_component_with_copy::<Ports>()
             .rollback_component_with_copy::<VictoryPoints>()
             .rollback_component_with_clone::<PlayerLongestRoad>()
+            .rollback_component_with_copy::<LongestRoadRef>()
+            .rollback_component_with_copy::<LargetArmyRef>()
             .rollback_component_with_copy::<Knights>()
             .rollback_component_with_copy::<Left<roads::Road>>()
+            .rollback_component_with_copy::<Left<Ship>>()
             .rollback_component_with_clone::<Mesh2d>()
             .rollback_component_with_copy::<CatanColorRef>()
             .rollback_resource_with_copy::<Robber>()
+            .rollback_resource_with_clone::<Rules>()
+            .rollback_resource_with_clone::<DevelopmentCardsPile>()
+            .rollback_resource_with_clone::<SessionRng>()
+            .rollback_resource_with_clone::<Moves>()
             .rollback_component_with_clone::<MeshMaterial2d<ColorMaterial>>()
             .rollback_component_with_clone::<Node>()
             .rollback_component_with_copy::<Transform>()
@@ -756,12 +948,17 @@ impl Plugin for GamePlugin {
             .rollback_component_with_copy::<RoadPosition>()
             .rollback_component_with_copy::<cities::City>()
             .rollback_component_with_copy::<roads::Road>()
+            .rollback_component_with_copy::<Ship>()
             .add_systems(
                 Update,
-                handle_ggrs_events.run_if(in_state(AppState::InGame)),
+                (
+                    handle_ggrs_events.run_if(in_state(AppState::InGame)),
+                    log_rule_violations,
+                ),
             )
-            .insert_resource(BoardSize(3))
+            .init_resource::<BoardSize>()
             .init_resource::<Robber>()
+            .init_resource::<RulesEvents>()
             .init_resource::<RobberDiscard>()
             .insert_resource(Resources::new_game())
             // TODO: is there way to init resource
@@ -789,8 +986,11 @@ impl Plugin for GamePlugin {
                 PostUpdate,
                 (
                     development_cards::show_dev_cards.run_if(in_state(GameState::Roll)),
-                    development_card_actions::development_card_action_interaction
-                        .run_if(in_state(GameState::Turn).or(in_state(GameState::Roll))),
+                    development_card_actions::development_card_action_interaction.run_if(
+                        in_state(GameState::Turn)
+                            .or(in_state(GameState::Roll))
+                            .and(not(special_building)),
+                    ),
                 ),
             )
             .add_systems(OnEnter(GameState::SetupRoad), roads::place_setup_road)
@@ -822,12 +1022,27 @@ impl Plugin for GamePlugin {
             )
             .add_systems(
                 OnExit(RoadBuildingState::Road1),
-                cleanup_button::<RoadPlaceButton>,
+                (
+                    cleanup_button::<RoadPlaceButton>,
+                    cleanup_button::<ShipPlaceButton>,
+                ),
             )
             .add_systems(
                 OnExit(RoadBuildingState::Road2),
-                cleanup_button::<RoadPlaceButton>,
+                (
+                    cleanup_button::<RoadPlaceButton>,
+                    cleanup_button::<ShipPlaceButton>,
+                ),
+            )
+            .add_systems(
+                OnExit(GameState::PlaceShip),
+                cleanup_button::<ShipPlaceButton>,
             )
+            .add_systems(
+                OnExit(GameState::MoveShip),
+                cleanup_button::<ShipMoveButton>,
+            )
+            .add_systems(OnExit(GameState::PickGold), cleanup_button::<GoldButton>)
             .add_systems(
                 OnExit(GameState::PlaceTown),
                 cleanup_button::<TownPlaceButton>,
@@ -838,7 +1053,10 @@ impl Plugin for GamePlugin {
             )
             .add_systems(
                 OnExit(GameState::PlaceRobber),
-                cleanup_button::<RobberButton>,
+                (
+                    cleanup_button::<RobberButton>,
+                    cleanup_button::<PirateButton>,
+                ),
             )
             .add_systems(
                 OnExit(GameState::RobberPickColor),
@@ -877,11 +1095,38 @@ impl Plugin for GamePlugin {
             )
             .add_systems(
                 OnEnter(RoadBuildingState::Road1),
-                roads::place_normal_road::<0>,
+                (roads::place_normal_road::<0>, ships::place_normal_ship::<0>),
             )
             .add_systems(
                 OnEnter(RoadBuildingState::Road2),
-                roads::place_normal_road::<0>,
+                (roads::place_normal_road::<0>, ships::place_normal_ship::<0>),
+            )
+            .add_systems(OnEnter(GameState::PlaceShip), ships::place_normal_ship::<1>)
+            .add_systems(OnEnter(GameState::MoveShip), ships::show_ship_moves)
+            .add_systems(OnEnter(GameState::PickGold), ships::gold_setup)
+            .add_systems(
+                Update,
+                (
+                    common_ui::button_system_with_generic::<
+                        ShipPlaceButton,
+                        PlaceShipButtonState<'_>,
+                    >
+                        .run_if(
+                            in_state(GameState::PlaceShip).or(in_state(GameState::RoadBuilding)),
+                        ),
+                    common_ui::button_system_with_generic::<
+                        ShipMoveButton,
+                        MoveShipButtonState<'_, '_>,
+                    >
+                        .run_if(in_state(GameState::MoveShip)),
+                    ships::gold_interaction.run_if(in_state(GameState::PickGold)),
+                    ships::follow_gold.run_if(
+                        in_state(AppState::InGame).and(resource_exists_and_changed::<Rules>),
+                    ),
+                    turn_ui::turn_ui_ship_interaction.run_if(in_state(GameState::Turn)),
+                    turn_ui::turn_ui_move_ship_interaction.run_if(in_state(GameState::Turn)),
+                    robber::place_pirate_interaction.run_if(in_state(GameState::PlaceRobber)),
+                ),
             )
             .add_systems(OnEnter(GameState::PlaceTown), towns::place_normal_town)
             .add_systems(OnEnter(GameState::PlaceCity), cities::place_normal_city)
@@ -948,7 +1193,15 @@ impl Plugin for GamePlugin {
             .add_systems(
                 Update,
                 (
-                    check_for_winner.run_if(in_state(GameState::Turn)),
+                    // with the special building phase you can get to 10 points on someone elses
+                    // turn, but you only win once its your turn again
+                    // (in hot seat the winner might not have the device yet)
+                    check_for_winner.run_if(
+                        in_state(AppState::InGame).and(
+                            resource_exists_and_changed::<Rules>
+                                .or(resource_exists_and_changed::<LocalPlayer>),
+                        ),
+                    ),
                     new_game_interaction.run_if(in_state(AppState::GameOver)),
                 ),
             )
@@ -966,13 +1219,11 @@ impl Plugin for GamePlugin {
             .add_systems(
                 GgrsSchedule,
                 (
-                    update_from_inputs,
-                    update_from_inputs_roll,
-                    update_from_monopoly,
-                    update_from_knight,
-                    update_from_trade_accept,
+                    update_rules,
+                    (update_from_inputs, update_from_inputs_roll).ambiguous_with_all(),
+                    sync_rules,
                 )
-                    .ambiguous_with_all(),
+                    .chain(),
             )
             .add_systems(
                 Update,
@@ -1033,20 +1284,57 @@ pub enum GameState {
     RobberPickColor,
     // picking which place to put robber on
     PlaceRobber,
+    // seafarers
+    PlaceShip,
+    MoveShip,
+    PickGold,
+    // cities & knights
+    PlaceKnight,
+    PromoteKnight,
+    ActivateKnight,
+    ChaseRobber,
+    CommodityMonopoly,
 }
 
 // for players input with ggrs
 #[derive(Component, PartialEq, Eq, Debug, Clone, Copy, Hash, Deserialize, Serialize)]
 #[require(KatanComponent)]
 pub struct PlayerHandle(pub usize);
-#[derive(Component, PartialEq, Debug, Clone, Copy)]
+/// inputs refer to players by handle, entities are local to each peer (and depend on spawn order)
+/// so this is how you get back to the player entity
+#[derive(SystemParam)]
+pub struct PlayerEntities<'w, 's> {
+    players: Query<'w, 's, (Entity, &'static PlayerHandle, &'static CatanColor)>,
+}
+impl PlayerEntities<'_, '_> {
+    pub fn get(&self, handle: PlayerHandle) -> Option<CatanColorRef> {
+        self.players
+            .iter()
+            .find(|(_, player_handle, _)| **player_handle == handle)
+            .map(|(entity, handle, color)| CatanColorRef {
+                color: *color,
+                entity,
+                handle: *handle,
+            })
+    }
+}
+#[derive(Component, PartialEq, Debug, Clone, Copy, Deserialize, Serialize)]
 #[require(KatanComponent)]
 enum Number {
     Number(u8),
     None,
 }
+impl Number {
+    /// how likely it is for the number to be rolled (out of 36)
+    pub const fn pips(self) -> u8 {
+        match self {
+            Self::Number(number) => 6 - number.abs_diff(7),
+            Self::None => 0,
+        }
+    }
+}
 
-#[derive(Debug, Component, Clone, Copy)]
+#[derive(Debug, Component, Clone, Copy, Deserialize, Serialize)]
 #[require(KatanComponent)]
 // our hexagons are pointy
 enum Hexagon {
@@ -1059,6 +1347,8 @@ enum Hexagon {
     Water,
     Port,
     Empty,
+    // seafarers, produces whichever resource the player picks
+    Gold,
 }
 impl From<u8> for Hexagon {
     fn from(value: u8) -> Self {
@@ -1072,6 +1362,7 @@ impl From<u8> for Hexagon {
             6 => Self::Water,
             7 => Self::Port,
             8 => Self::Empty,
+            9 => Self::Gold,
             _ => Self::Empty,
         }
     }
@@ -1088,8 +1379,22 @@ impl Hexagon {
             Self::Water => Color::srgb_u8(0, 0, 255),
             Self::Port => Color::srgb_u8(0, 0, 255),
             Self::Empty => Color::BLACK.with_alpha(-1.),
+            Self::Gold => Color::srgb_u8(212, 175, 55),
+        }
+    }
+    /// cities & knights, what a city makes here instead of a second resource
+    pub const fn commodity(self) -> Option<resources::Commodity> {
+        match self {
+            Self::Wood => Some(resources::Commodity::Paper),
+            Self::Sheep => Some(resources::Commodity::Cloth),
+            Self::Ore => Some(resources::Commodity::Coin),
+            _ => None,
         }
     }
+    /// hexes that get a number
+    pub const fn produces(self) -> bool {
+        self.to_resources().is_some() || matches!(self, Self::Gold)
+    }
     pub const fn to_resources(self) -> Option<Resources> {
         match self {
             Self::Wood => Some(Resources {
@@ -1131,10 +1436,11 @@ impl Hexagon {
             Self::Water => None,
             Self::Port => None,
             Self::Empty => None,
+            Self::Gold => None,
         }
     }
 }
-#[derive(Debug, Component, Clone, Copy)]
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[require(KatanComponent)]
 enum Port {
     TwoForOne(resources::Resource),
@@ -1149,8 +1455,23 @@ impl Port {
         .darker(0.02)
     }
 }
-#[derive(Resource, Clone, Copy)]
-struct BoardSize(u8);
+/// how many rings of land the board has, picked in the lobby (or taken from the save)
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardSize(pub u8);
+impl BoardSize {
+    pub const MIN: u8 = 2;
+    // the coordinates are `i8`s, but even this is way bigger then you would want to play on
+    pub const MAX: u8 = 6;
+    /// the board you get without picking one, 5-6 players get the bigger extension board
+    pub const fn for_players(player_count: usize) -> Self {
+        if player_count > 4 { Self(4) } else { Self(3) }
+    }
+}
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self(3)
+    }
+}
 
 fn cleanup_button<T: Component>(
     mut commands: Commands<'_, '_>,
@@ -1187,7 +1508,7 @@ pub struct PlaceButton;
 // TODO: maybe we should impose an order on postions for stuff like roads so that comparing them is
 // easeier (i.e. first postion is smallest ....)
 
-#[derive(Component, PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(Component, PartialEq, Eq, Debug, Copy, Clone, Hash)]
 #[require(KatanComponent)]
 struct Left<T>(pub u8, PhantomData<T>);
 
@@ -1197,42 +1518,84 @@ struct Left<T>(pub u8, PhantomData<T>);
 #[require(KatanComponent)]
 struct Building;
 
-#[derive(Component, PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[derive(Component, PartialEq, Eq, Default, Clone, Copy, Debug, Hash, Deserialize, Serialize)]
 #[require(KatanComponent)]
 pub struct VictoryPoints {
     pub actual: u8,
     pub from_development_cards: u8,
 }
-#[derive(Component, PartialEq, Eq, Default, Clone, Copy, Debug)]
+#[derive(Component, PartialEq, Eq, Default, Clone, Copy, Debug, Hash)]
 #[require(KatanComponent)]
 pub struct Knights(pub u8);
 #[derive(Resource, PartialEq, Eq, Default, Clone, Copy, Debug)]
 pub struct PlayerCount(pub u8);
+impl PlayerCount {
+    pub const MIN: u8 = 1;
+    // there are only six colors
+    pub const MAX: u8 = 6;
+}
 fn game_setup(
     mut next_state: ResMut<'_, NextState<GameState>>,
     mut commands: Commands<'_, '_>,
     meshes: ResMut<'_, Assets<Mesh>>,
     materials: ResMut<'_, Assets<ColorMaterial>>,
     player_count: Res<'_, PlayerCount>,
+    board_size: Res<'_, BoardSize>,
+    constraints: Option<Res<'_, BoardConstraints>>,
+    cities_and_knights: Option<Res<'_, PlayCitiesAndKnights>>,
+    game_rules: Option<Res<'_, GameRules>>,
     seed: Res<'_, SessionSeed>,
+    loaded: Option<Res<'_, LoadedGame>>,
+    map: Option<Res<'_, LoadedMap>>,
 
     local_player: Res<'_, LocalPlayerHandle>,
 ) {
     let layout = layout(&mut commands);
     commands.insert_resource(layout);
-    let catan_colors = setup_game::setup(
-        &mut commands,
-        meshes,
-        materials,
-        *player_count.into_inner(),
-        seed.0,
-        *local_player.into_inner(),
-    );
+    let catan_colors = if let Some(loaded) = &loaded {
+        setup_game::load(
+            &mut commands,
+            meshes,
+            materials,
+            &loaded.0,
+            *local_player.into_inner(),
+        )
+    } else {
+        setup_game::setup(
+            &mut commands,
+            meshes,
+            materials,
+            *player_count.into_inner(),
+            *board_size.into_inner(),
+            constraints.as_deref(),
+            cities_and_knights.is_some(),
+            game_rules.as_deref().copied().unwrap_or_default(),
+            map.as_ref().map(|map| &map.0),
+            seed.0,
+            *local_player.into_inner(),
+        )
+    };
 
-    commands.insert_resource(ColorIterator(catan_colors.clone().cycle()));
-    commands.insert_resource(SetupColorIterator(
-        catan_colors.clone().chain(catan_colors.rev()),
-    ));
+    let mut color_rotation = catan_colors.clone().cycle();
+    let mut setup_color_rotation = catan_colors.clone().chain(catan_colors.rev());
+    // start of the game picks the next color, so for a loaded game skip everyone before the
+    // current player
+    if let Some(loaded) = &loaded {
+        match loaded.0.rules.phase {
+            rules::Phase::Setup { turn, .. } => {
+                setup_color_rotation.by_ref().take(turn).for_each(drop);
+            }
+            _ => {
+                setup_color_rotation.by_ref().for_each(drop);
+                color_rotation
+                    .by_ref()
+                    .take(loaded.0.rules.current.0)
+                    .for_each(drop);
+            }
+        }
+    }
+    commands.insert_resource(ColorIterator(color_rotation));
+    commands.insert_resource(SetupColorIterator(setup_color_rotation));
 
     next_state.set(GameState::Start);
 }
@@ -1252,6 +1615,58 @@ pub fn next_player(
     }
 }
 
+/// the ui state of a player at this point of the game, for when we can't get there by following
+/// the inputs (like loading a game or passing the device in hot seat)
+pub fn set_state_from_rules(
+    rules: &rules::GameState,
+    local: PlayerHandle,
+    game_state: &mut NextState<GameState>,
+    road_building_state: &mut NextState<RoadBuildingState>,
+    year_of_plenty_state: &mut NextState<YearOfPlentyState>,
+) {
+    let active = rules.active_player() == Some(local);
+    game_state.set(match &rules.phase {
+        rules::Phase::Setup { road_placed, .. } if active => {
+            if *road_placed {
+                GameState::SetupTown
+            } else {
+                GameState::SetupRoad
+            }
+        }
+        rules::Phase::Setup { .. } => GameState::NotActiveSetup,
+        rules::Phase::Roll if active => GameState::Roll,
+        rules::Phase::Discard(_) if active => GameState::RobberDiscardResources,
+        rules::Phase::Discard(_) => GameState::RobberDiscardResourcesInActive,
+        rules::Phase::Gold(pending) if pending.iter().any(|(handle, _)| *handle == local) => {
+            GameState::PickGold
+        }
+        rules::Phase::PlaceRobber { .. } if active => GameState::PlaceRobber,
+        // the builder uses the normal turn ui, but only building is allowed
+        rules::Phase::Turn | rules::Phase::SpecialBuilding(_) if active => GameState::Turn,
+        rules::Phase::RoadBuilding(left) if active => {
+            if *left == 1 {
+                road_building_state.set(RoadBuildingState::Road2);
+            }
+            GameState::RoadBuilding
+        }
+        rules::Phase::YearOfPlenty(left) if active => {
+            if *left == 1 {
+                year_of_plenty_state.set(YearOfPlentyState::Resource2);
+            }
+            GameState::YearOfPlenty
+        }
+        rules::Phase::Monopoly if active => GameState::Monopoly,
+        rules::Phase::CommodityMonopoly if active => GameState::CommodityMonopoly,
+        _ => GameState::NotActive,
+    });
+}
+
+/// run condition for the parts of the turn ui that aren't allowed in the special building phase
+/// (trading and playing development cards)
+pub fn special_building(rules: Option<Res<'_, Rules>>) -> bool {
+    rules.is_some_and(|rules| matches!(rules.phase, rules::Phase::SpecialBuilding(_)))
+}
+
 #[derive(Resource, Debug, Clone, Copy)]
 pub struct Layout {
     pub player_banner: Entity,
@@ -1283,7 +1698,21 @@ fn layout(commands: &mut Commands<'_, '_>) -> Layout {
                 ..default()
             },
             BorderColor::all(Color::BLACK),
-            children![Text("settings".to_string()),],
+            children![
+                Text("settings".to_string()),
+                (
+                    Button,
+                    SaveButton,
+                    Text::new("save"),
+                    BackgroundColor(NORMAL_BUTTON)
+                ),
+                (
+                    Button,
+                    SaveReplayButton,
+                    Text::new("save replay"),
+                    BackgroundColor(NORMAL_BUTTON)
+                ),
+            ],
         ))
         .id();
     let development_cards_layout = commands
@@ -1409,7 +1838,10 @@ fn layout(commands: &mut Commands<'_, '_>) -> Layout {
         setting_pull_out: settings_pull_out_layout,
     }
 }
-fn handle_ggrs_events(mut session: ResMut<'_, Session<GgrsSessionConfig>>) {
+fn handle_ggrs_events(
+    mut session: ResMut<'_, Session<GgrsSessionConfig>>,
+    mut desyncs: MessageWriter<'_, DesyncDetected>,
+) {
     if let Session::P2P(s) = session.as_mut() {
         for event in s.events() {
             match event {
@@ -1420,11 +1852,12 @@ fn handle_ggrs_events(mut session: ResMut<'_, Session<GgrsSessionConfig>>) {
                     local_checksum,
                     remote_checksum,
                     frame,
-                    ..
+                    addr,
                 } => {
                     error!(
                         "Desync on frame {frame}. Local checksum: {local_checksum:X}, remote checksum: {remote_checksum:X}"
                     );
+                    desyncs.write(DesyncDetected { frame, peer: addr });
                 }
                 _ => info!("GGRS event: {event:?}"),
             }
@@ -1432,14 +1865,13 @@ fn handle_ggrs_events(mut session: ResMut<'_, Session<GgrsSessionConfig>>) {
     }
 }
 
+// the rules decide who won, the winner just tells everyone
 fn check_for_winner(
     current_color: Res<'_, LocalPlayer>,
     mut current_inputs: ResMut<'_, Input>,
-    players: Query<'_, '_, &VictoryPoints, Changed<VictoryPoints>>,
+    rules: Res<'_, Rules>,
 ) {
-    if let Ok(vps) = players.get(current_color.0.entity)
-        && vps.actual + vps.from_development_cards >= 10
-    {
+    if rules.phase == rules::Phase::Finished(current_color.0.handle) {
         *current_inputs = Input::Win;
     }
 }
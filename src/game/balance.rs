@@ -0,0 +1,436 @@
+//! fair boards
+//! the shuffled board is fixed up a swap at a time until it breaks none of the constraints, every
+//! swap (and every reshuffle when that gets stuck) is picked with the seeded rng so the same seed
+//! still gives the same board
+use std::fmt;
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use rand::{Rng, seq::SliceRandom};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Hexagon, Number,
+    positions::{BuildingPosition, Position},
+};
+
+// swaps in a row that don't make the board any better before we start over from a new shuffle
+const STUCK: usize = 5_000;
+// new shuffles before the constraints are loosened a step
+const RESTARTS: usize = 10;
+// by then the constraints don't ask for much more then keeping the 6s and 8s apart, if even that
+// can't be done we play on whatever we have
+const LOOSEN: usize = 10;
+
+/// what a fair board looks like, without this resource boards are only shuffled (with the 6s and 8s
+/// kept apart)
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BoardConstraints {
+    /// most hexes of the same resource that can be connected to each other
+    pub max_cluster: usize,
+    /// least pips an intersection surrounded by land touches
+    pub min_pips: u8,
+    /// most pips an intersection surrounded by land touches
+    pub max_pips: u8,
+    /// keep 2s and 12s from being next to each other (6s and 8s are always kept apart)
+    pub separate_extremes: bool,
+    /// how many pips each resource can be off from its share of all the pips
+    pub max_resource_deviation: u8,
+}
+impl Default for BoardConstraints {
+    fn default() -> Self {
+        Self {
+            max_cluster: 2,
+            min_pips: 3,
+            max_pips: 12,
+            separate_extremes: true,
+            max_resource_deviation: 2,
+        }
+    }
+}
+impl BoardConstraints {
+    /// a step easier to meet, for when no board can be found that meets these (the default
+    /// constraints never need this on the board sizes the lobby allows)
+    const fn loosened(self) -> Self {
+        Self {
+            max_cluster: self.max_cluster + 1,
+            min_pips: self.min_pips.saturating_sub(1),
+            max_pips: self.max_pips.saturating_add(1),
+            separate_extremes: false,
+            max_resource_deviation: self.max_resource_deviation.saturating_add(1),
+        }
+    }
+}
+
+/// how badly a board breaks each constraint, 0 everywhere is a board that passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Violations {
+    /// 6s and 8s next to each other
+    pub reds: usize,
+    /// 2s and 12s next to each other
+    pub extremes: usize,
+    /// hexes over the biggest cluster size
+    pub clusters: usize,
+    /// pips intersections are under the minimum or over the maximum
+    pub pips: usize,
+    /// pips resources are off from their share by more than is allowed
+    pub spread: usize,
+}
+impl Violations {
+    pub const fn total(&self) -> usize {
+        self.reds + self.extremes + self.clusters + self.pips + self.spread
+    }
+}
+
+// which land hexes are next to each other and which meet at an intersection, swapping only changes
+// what's on the hexes so this is only worked out once
+struct Layout {
+    neighbors: Vec<Vec<usize>>,
+    corners: Vec<[usize; 3]>,
+}
+impl Layout {
+    fn new(land: &[(Position, Hexagon, Number)]) -> Self {
+        let index = |position: Position| land.iter().position(|(p, _, _)| *p == position);
+        let neighbors = land
+            .iter()
+            .map(|(position, _, _)| {
+                Position::DIRECTION_VECTORS
+                    .iter()
+                    .filter_map(|direction| index(*position + *direction))
+                    .collect()
+            })
+            .collect();
+        // intersections on the coast always touch less, so only ones surrounded by land count
+        let corners = land
+            .iter()
+            .flat_map(|(position, _, _)| position.building_positions_around())
+            .unique()
+            .filter_map(|BuildingPosition::All(p1, p2, p3)| {
+                Some([index(p1)?, index(p2)?, index(p3)?])
+            })
+            .collect();
+        Self { neighbors, corners }
+    }
+    fn pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbors
+            .iter()
+            .enumerate()
+            .flat_map(|(i, neighbors)| neighbors.iter().map(move |j| (i, *j)))
+            .filter(|(i, j)| i < j)
+    }
+    fn corner_pips<'a>(
+        &'a self,
+        land: &'a [(Position, Hexagon, Number)],
+    ) -> impl Iterator<Item = u8> + 'a {
+        self.corners
+            .iter()
+            .map(|corner| corner.iter().map(|i| land[*i].2.pips()).sum())
+    }
+    fn violations(
+        &self,
+        land: &[(Position, Hexagon, Number)],
+        constraints: &BoardConstraints,
+    ) -> Violations {
+        let both = |i: usize, j: usize, numbers: &[u8]| {
+            [land[i].2, land[j].2]
+                .iter()
+                .all(|number| matches!(number, Number::Number(n) if numbers.contains(n)))
+        };
+        let reds = self.pairs().filter(|(i, j)| both(*i, *j, &[6, 8])).count();
+        let extremes = if constraints.separate_extremes {
+            self.pairs().filter(|(i, j)| both(*i, *j, &[2, 12])).count()
+        } else {
+            0
+        };
+        let pips = self
+            .corner_pips(land)
+            .map(|pips| {
+                usize::from(constraints.min_pips.saturating_sub(pips))
+                    + usize::from(pips.saturating_sub(constraints.max_pips))
+            })
+            .sum();
+        let (hexes, total) = resource_totals(land);
+        let spread = resource_pips(land)
+            .map(|(_, count, pips)| {
+                // compared times the number of hexes so we can stay in integers
+                let off = (pips * hexes).abs_diff(count * total);
+                off.saturating_sub(usize::from(constraints.max_resource_deviation) * hexes)
+                    .div_ceil(hexes.max(1))
+            })
+            .sum();
+        Violations {
+            reds,
+            extremes,
+            clusters: self.clusters(land, constraints.max_cluster),
+            pips,
+            spread,
+        }
+    }
+    fn clusters(&self, land: &[(Position, Hexagon, Number)], max_cluster: usize) -> usize {
+        let mut seen = vec![false; land.len()];
+        let mut over = 0;
+        for start in 0..land.len() {
+            if seen[start] || land[start].1.to_resources().is_none() {
+                continue;
+            }
+            seen[start] = true;
+            let kind = land[start].1 as u8;
+            let mut stack = vec![start];
+            let mut size = 0_usize;
+            while let Some(i) = stack.pop() {
+                size += 1;
+                for j in &self.neighbors[i] {
+                    if !seen[*j] && land[*j].1 as u8 == kind {
+                        seen[*j] = true;
+                        stack.push(*j);
+                    }
+                }
+            }
+            over += size.saturating_sub(max_cluster);
+        }
+        over
+    }
+}
+
+// how many resource hexes and pips each resource has
+fn resource_pips(
+    land: &[(Position, Hexagon, Number)],
+) -> impl Iterator<Item = (Hexagon, usize, usize)> + '_ {
+    (0..5).map(Hexagon::from).map(move |kind| {
+        land.iter()
+            .filter(|(_, hex, _)| *hex as u8 == kind as u8)
+            .fold((kind, 0, 0), |(kind, count, pips), (_, _, number)| {
+                (kind, count + 1, pips + usize::from(number.pips()))
+            })
+    })
+}
+fn resource_totals(land: &[(Position, Hexagon, Number)]) -> (usize, usize) {
+    resource_pips(land).fold((0, 0), |(hexes, total), (_, count, pips)| {
+        (hexes + count, total + pips)
+    })
+}
+
+// swapping twice puts everything back, so this is also how a swap is undone
+fn swap(land: &mut [(Position, Hexagon, Number)], i: usize, j: usize, kind: u8) {
+    let resources = land[i].1.to_resources().is_some() && land[j].1.to_resources().is_some();
+    match kind {
+        // move the whole hex, number and all (this is the only way deserts move)
+        0 => {
+            let hex = (land[i].1, land[i].2);
+            (land[i].1, land[i].2) = (land[j].1, land[j].2);
+            (land[j].1, land[j].2) = hex;
+        }
+        1 if resources => {
+            let number = land[i].2;
+            land[i].2 = land[j].2;
+            land[j].2 = number;
+        }
+        2 if resources => {
+            let hex = land[i].1;
+            land[i].1 = land[j].1;
+            land[j].1 = hex;
+        }
+        _ => {}
+    }
+}
+
+/// swaps hexes and numbers around until the board passes the constraints, a swap is kept if it
+/// doesn't make the board worse, when that gets stuck the board is shuffled again and if that keeps
+/// happening the constraints are loosened a step at a time
+pub fn balance(
+    land: &mut [(Position, Hexagon, Number)],
+    constraints: &BoardConstraints,
+    rng: &mut Xoshiro256PlusPlus,
+) {
+    if land.len() < 2 {
+        return;
+    }
+    let layout = Layout::new(land);
+    let mut constraints = *constraints;
+    for loosened in 0..=LOOSEN {
+        if loosened > 0 {
+            constraints = constraints.loosened();
+            warn!(
+                "could not find a board that fits the constraints, loosening them to {constraints:?}"
+            );
+        }
+        for _ in 0..RESTARTS {
+            if climb(&layout, land, &constraints, rng) {
+                return;
+            }
+            shuffle(land, rng);
+        }
+    }
+    warn!("could not find a board that fits even the loosest constraints");
+}
+
+// keeps swaps that don't make the board worse, until it passes or hasn't gotten better in a while
+fn climb(
+    layout: &Layout,
+    land: &mut [(Position, Hexagon, Number)],
+    constraints: &BoardConstraints,
+    rng: &mut Xoshiro256PlusPlus,
+) -> bool {
+    let mut violations = layout.violations(land, constraints).total();
+    let mut stuck = 0;
+    while violations > 0 {
+        if stuck == STUCK {
+            return false;
+        }
+        let i = rng.random_range(0..land.len());
+        let j = rng.random_range(0..land.len());
+        let kind = rng.random_range(0..3);
+        swap(land, i, j, kind);
+        let swapped = layout.violations(land, constraints).total();
+        stuck = if swapped < violations { 0 } else { stuck + 1 };
+        if swapped <= violations {
+            violations = swapped;
+        } else {
+            swap(land, i, j, kind);
+        }
+    }
+    true
+}
+
+fn shuffle(land: &mut [(Position, Hexagon, Number)], rng: &mut Xoshiro256PlusPlus) {
+    let mut hexes = land
+        .iter()
+        .map(|(_, hex, number)| (*hex, *number))
+        .collect_vec();
+    hexes.shuffle(rng);
+    for ((_, hex, number), shuffled) in land.iter_mut().zip(hexes) {
+        (*hex, *number) = shuffled;
+    }
+}
+
+/// how fair a board is, logged when a game starts and shown in the map editor
+#[derive(Debug, Clone)]
+pub struct Fairness {
+    pub violations: Violations,
+    /// least and most pips of the intersections surrounded by land
+    pub pips: (u8, u8),
+    /// pips of each resource and what it would have with a fair share of all the pips
+    pub resources: Vec<(Hexagon, usize, f32)>,
+    /// out of 100, only a board where every intersection touches the same pips and every resource
+    /// gets its fair share gets all of them, every broken constraint takes 10 off
+    pub score: u8,
+}
+impl fmt::Display for Fairness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fairness {}/100, intersections touch {}-{} pips",
+            self.score, self.pips.0, self.pips.1
+        )?;
+        for (kind, pips, share) in &self.resources {
+            write!(f, ", {kind:?} {pips} pips ({share:.1} fair)")?;
+        }
+        if self.violations.total() > 0 {
+            write!(
+                f,
+                ", breaks the constraints {} times",
+                self.violations.total()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// scores the land of a board, works for any board (custom maps too) not just generated ones
+pub fn fairness(land: &[(Position, Hexagon, Number)], constraints: &BoardConstraints) -> Fairness {
+    let layout = Layout::new(land);
+    let violations = layout.violations(land, constraints);
+    let corners = layout.corner_pips(land).collect_vec();
+    let mean =
+        corners.iter().map(|pips| f32::from(*pips)).sum::<f32>() / corners.len().max(1) as f32;
+    let deviation = (corners
+        .iter()
+        .map(|pips| (f32::from(*pips) - mean).powi(2))
+        .sum::<f32>()
+        / corners.len().max(1) as f32)
+        .sqrt();
+    let (hexes, total) = resource_totals(land);
+    let resources = resource_pips(land)
+        .filter(|(_, count, _)| *count > 0)
+        .map(|(kind, count, pips)| (kind, pips, (count * total) as f32 / hexes as f32))
+        .collect_vec();
+    let spread = resources
+        .iter()
+        .map(|(_, pips, share)| (*pips as f32 - share).abs())
+        .sum::<f32>();
+    let score = 100. - 10. * violations.total() as f32 - 5. * deviation - 2. * spread;
+    Fairness {
+        violations,
+        pips: (
+            corners.iter().copied().min().unwrap_or_default(),
+            corners.iter().copied().max().unwrap_or_default(),
+        ),
+        resources,
+        score: score.clamp(0., 100.).round() as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::game::{BoardSize, positions, setup_game::shuffle_land};
+
+    fn board(land: &[Position], seed: u64) -> Vec<(Position, Hexagon, Number)> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+        let mut board = shuffle_land(land, &mut rng);
+        balance(&mut board, &BoardConstraints::default(), &mut rng);
+        board
+    }
+
+    fn balances(land: &[Position], seeds: std::ops::Range<u64>) {
+        for seed in seeds {
+            let violations = fairness(&board(land, seed), &BoardConstraints::default()).violations;
+            assert_eq!(violations.total(), 0, "seed {seed}: {violations:?}");
+        }
+    }
+
+    #[test]
+    fn every_seed_balances_the_usual_board() {
+        balances(&positions::generate_postions(3).collect_vec(), 0..200);
+    }
+
+    #[test]
+    fn every_seed_balances_the_extension_board() {
+        balances(
+            &positions::generate_stretched_postions(4).collect_vec(),
+            0..200,
+        );
+    }
+
+    // the bigger boards take a while, so only a few seeds each
+    #[test]
+    fn every_board_size_balances() {
+        for size in BoardSize::MIN..=BoardSize::MAX {
+            let size = size as i8;
+            balances(&positions::generate_postions(size).collect_vec(), 0..10);
+            balances(
+                &positions::generate_stretched_postions(size).collect_vec(),
+                0..10,
+            );
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_board() {
+        let land = positions::generate_postions(3).collect_vec();
+        // `Hexagon` can't be compared
+        let hexes = |seed| {
+            board(&land, seed)
+                .into_iter()
+                .map(|(position, hex, number)| (position, hex as u8, number))
+                .collect_vec()
+        };
+        for seed in 0..10 {
+            assert_eq!(hexes(seed), hexes(seed), "seed {seed}");
+        }
+        assert_ne!(hexes(0), hexes(1));
+    }
+}
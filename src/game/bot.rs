@@ -0,0 +1,360 @@
+//! computer players
+//! a bot is just another local ggrs player whose inputs come from the rules instead of the ui, so
+//! the other peers can't tell it apart from a human
+//! the rules already know every legal action, so a bot just scores them and picks the best one
+use bevy::prelude::*;
+
+use super::{
+    Input, Number, PlayerHandle,
+    cities_and_knights::ProgressCard,
+    development_cards::DevelopmentCard,
+    positions::{BuildingPosition, Position, RoadPosition},
+    resources::{CITY_RESOURCES, DEVELOPMENT_CARD_RESOURCES, Resource, Resources, TOWN_RESOURCES},
+    resources_management::TradingResources,
+    rules::{self, Action, GameState, Phase, Piece, TradeId, TradeOffer},
+};
+use crate::utils::{CheckedAdd, CheckedSub};
+
+// frames between a bots inputs, so the rules (and the ui of the other players) can catch up and so
+// you can see what the bot is doing
+const BOT_DELAY: u32 = 30;
+
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub handle: PlayerHandle,
+    // frames until the bot does something again
+    wait: u32,
+    // trade offers before this one the bot already looked at
+    answered: TradeId,
+}
+impl Bot {
+    pub const fn new(handle: PlayerHandle) -> Self {
+        Self {
+            handle,
+            wait: BOT_DELAY,
+            answered: TradeId(0),
+        }
+    }
+    fn input(&mut self, rules: &GameState) -> Input {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return Input::None;
+        }
+        let offer = rules
+            .trade_offers(self.handle)
+            .find(|offer| offer.id >= self.answered)
+            .copied();
+        let input = if let Some(offer) = offer {
+            self.answered = TradeId(offer.id.0 + 1);
+            respond(rules, self.handle, &offer)
+        } else {
+            decide(rules, self.handle)
+        };
+        if input != Input::None {
+            self.wait = BOT_DELAY;
+        }
+        input
+    }
+}
+
+/// the bots this peer runs, for the other peers they are just remote players
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Bots {
+    bots: Vec<Bot>,
+}
+impl Bots {
+    pub fn new(handles: impl IntoIterator<Item = PlayerHandle>) -> Self {
+        Self {
+            bots: handles.into_iter().map(Bot::new).collect(),
+        }
+    }
+    pub fn contains(&self, handle: PlayerHandle) -> bool {
+        self.bots.iter().any(|bot| bot.handle == handle)
+    }
+    /// the next input of a bot, or `None` if the handle isn't one of our bots
+    pub fn input(&mut self, handle: PlayerHandle, rules: &GameState) -> Option<Input> {
+        self.bots
+            .iter_mut()
+            .find(|bot| bot.handle == handle)
+            .map(|bot| bot.input(rules))
+    }
+}
+
+pub struct BotPlugin;
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bots>();
+    }
+}
+
+// a bot takes any trade where it gets at least as many cards as it gives
+fn respond(rules: &GameState, me: PlayerHandle, offer: &TradeOffer) -> Input {
+    let Some(this) = rules.player(me) else {
+        return Input::None;
+    };
+    if rules.phase != Phase::Turn
+        || rules.current == me
+        || offer.from != rules.current
+        || this.resources.checked_sub(offer.trade).is_none()
+    {
+        return Input::None;
+    }
+    // the offer is from the point of view of the current player
+    let (giving, taking) = offer.trade.given_and_taken();
+    let gets: i8 = giving.iter().map(|(_, count)| -count).sum();
+    let gives: i8 = taking.iter().map(|(_, count)| count).sum();
+    if gets >= gives {
+        Input::TradeResponce(offer.id)
+    } else {
+        Input::None
+    }
+}
+
+fn decide(rules: &GameState, me: PlayerHandle) -> Input {
+    // the rules decide who won, but the game only ends once the winner says so
+    if rules.phase == Phase::Finished(me) {
+        return Input::Win;
+    }
+    rules
+        .legal_actions(me)
+        .into_iter()
+        .max_by_key(|action| score(rules, me, action))
+        .map_or(Input::None, to_input)
+}
+
+fn to_input(action: Action) -> Input {
+    match action {
+        Action::PlaceRoad(road) => Input::AddRoad(road),
+        Action::PlaceTown(town) => Input::AddTown(town),
+        Action::PlaceCity(city) => Input::AddCity(city),
+        Action::BuyDevelopmentCard => Input::TakeDevelopmentCard,
+        Action::PlayDevelopmentCard(card) => Input::PlayDevelopmentCard(card),
+        // the dice are rolled from the session rng
+        Action::Roll(_, _) | Action::RollWithEvent(_, _, _) => Input::Roll,
+        Action::Discard(resources) => Input::RobberDiscard(resources),
+        // what gets taken is also picked from the session rng
+        Action::MoveRobber(position, Some((victim, _))) => Input::Knight(victim, position),
+        Action::MoveRobber(position, None) => Input::MoveKnight(position),
+        Action::Monopoly(resource) => Input::Monopoly(resource),
+        Action::YearOfPlenty(resource) => Input::YearOfPlenty(resource),
+        Action::BankTrade(trade) => Input::BankTrade(trade),
+        // bots don't offer trades
+        Action::Trade(_, _)
+        | Action::OfferTrade(_, _)
+        | Action::CounterOffer(_, _)
+        | Action::CancelTrade(_)
+        | Action::AcceptTrade(_) => Input::None,
+        Action::EndTurn => Input::NextColor,
+        Action::PlaceShip(ship) => Input::AddShip(ship),
+        Action::MoveShip(from, to) => Input::MoveShip(from, to),
+        Action::MovePirate(position, Some((victim, _))) => Input::Pirate(victim, position),
+        Action::MovePirate(position, None) => Input::MovePirate(position),
+        Action::PickGold(resource) => Input::Gold(resource),
+        Action::Improve(track) => Input::Improve(track),
+        Action::PlaceKnight(position) => Input::AddKnight(position),
+        Action::PromoteKnight(position) => Input::PromoteKnight(position),
+        Action::ActivateKnight(position) => Input::ActivateKnight(position),
+        Action::ChaseRobber(position) => Input::ChaseRobber(position),
+        Action::PlayProgressCard(card) => Input::PlayProgressCard(card),
+        Action::CommodityMonopoly(commodity) => Input::CommodityMonopoly(commodity),
+    }
+}
+
+// higher is better, ending the turn (and rolling) is 0 so anything below that is never picked if
+// the bot can end its turn instead
+fn score(rules: &GameState, me: PlayerHandle, action: &Action) -> i32 {
+    let Some(this) = rules.player(me) else {
+        return 0;
+    };
+    let setup = matches!(rules.phase, Phase::Setup { .. });
+    match action {
+        Action::PlaceCity(city) => 100 + building_score(rules, city),
+        Action::PlaceTown(town) if setup => building_score(rules, town),
+        Action::PlaceTown(town) => 90 + building_score(rules, town),
+        Action::PlaceRoad(road) if setup || matches!(rules.phase, Phase::RoadBuilding(_)) => {
+            road_score(rules, *road)
+        }
+        // save up for the town instead
+        Action::PlaceRoad(_) if this.towns_left > 0 && !rules.town_placements(me).is_empty() => -1,
+        Action::PlaceRoad(road) => 20 + road_score(rules, *road),
+        // ships are scored like roads, but a road is a bit more useful (it can't be moved away)
+        Action::PlaceShip(ship) if setup || matches!(rules.phase, Phase::RoadBuilding(_)) => {
+            road_score(rules, *ship)
+        }
+        Action::PlaceShip(_) if this.towns_left > 0 && !rules.town_placements(me).is_empty() => -1,
+        Action::PlaceShip(ship) => 15 + road_score(rules, *ship),
+        // the pirate doesn't block any production, so its only worth it for what can be stolen
+        Action::MovePirate(_, steal) => steal.map_or(-1, |(victim, _)| {
+            i32::from(rules.players[victim.0].resources.count()) - 1
+        }),
+        Action::BuyDevelopmentCard => 40,
+        Action::PlayDevelopmentCard(DevelopmentCard::Knight)
+        | Action::ChaseRobber(_)
+        | Action::PlayProgressCard(ProgressCard::Bishop)
+            if robbed(rules, me) =>
+        {
+            50
+        }
+        // don't waste the knight before rolling
+        Action::PlayDevelopmentCard(DevelopmentCard::Knight) if rules.phase == Phase::Roll => -1,
+        Action::PlayDevelopmentCard(DevelopmentCard::Knight) => 10,
+        Action::PlayDevelopmentCard(DevelopmentCard::RoadBuilding)
+        | Action::PlayProgressCard(ProgressCard::RoadBuilding)
+            if this.roads_left == 0 || rules.road_placements(me).is_empty() =>
+        {
+            -1
+        }
+        Action::PlayDevelopmentCard(_) | Action::PlayProgressCard(_) => 15,
+        Action::Roll(_, _) | Action::RollWithEvent(_, _, _) | Action::EndTurn => 0,
+        // improvements are what gets progress cards (and the mighty knights), knights are there
+        // so the cities don't get pillaged
+        Action::Improve(_) => 60,
+        Action::ActivateKnight(_) => 35,
+        Action::PlaceKnight(_) => 30,
+        Action::PromoteKnight(_) => 25,
+        Action::CommodityMonopoly(commodity) => rules
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(handle, _)| *handle != me.0)
+            .map(|(_, player)| i32::from(player.commodities.get(*commodity).min(1)))
+            .sum(),
+        Action::MoveRobber(position, steal) => {
+            robber_score(rules, me, *position)
+                + steal.map_or(0, |(victim, _)| {
+                    i32::from(rules.players[victim.0].resources.count())
+                })
+        }
+        Action::Monopoly(resource) => rules
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(handle, _)| *handle != me.0)
+            .map(|(_, player)| i32::from(player.resources.get(*resource)))
+            .sum(),
+        Action::YearOfPlenty(resource) | Action::PickGold(resource) => {
+            missing(this.resources, *resource)
+        }
+        Action::BankTrade(trade) => bank_trade_score(rules, me, *trade),
+        // spread out what is left, so the bot has a bit of everything
+        Action::Discard(discard) => -Resource::ALL
+            .into_iter()
+            .map(|resource| {
+                let left = i32::from(this.resources.get(resource) - discard.get(resource));
+                left * left
+            })
+            .sum::<i32>(),
+        // the bot doesn't plan far enough ahead for moving ships to be worth it, and only chases
+        // the robber off its own hexes
+        Action::Trade(_, _)
+        | Action::OfferTrade(_, _)
+        | Action::CounterOffer(_, _)
+        | Action::CancelTrade(_)
+        | Action::AcceptTrade(_)
+        | Action::MoveShip(_, _)
+        | Action::ChaseRobber(_) => -1,
+    }
+}
+
+fn pips(number: Number) -> i32 {
+    i32::from(number.pips())
+}
+fn hex_pips(rules: &GameState, position: Position) -> i32 {
+    rules
+        .board
+        .hexes
+        .iter()
+        .find(|(hex, _, _)| *hex == position)
+        .map_or(0, |(_, _, number)| pips(*number))
+}
+
+// how much a building there would produce, with a bit extra for every hex that produces anything
+fn building_score(rules: &GameState, building: &BuildingPosition) -> i32 {
+    rules
+        .board
+        .hexes
+        .iter()
+        .filter(|(position, hex, _)| building.contains(position) && hex.produces())
+        .map(|(_, _, number)| pips(*number) + 1)
+        .sum()
+}
+
+// roads are worth the best town they could lead to
+fn road_score(rules: &GameState, road: RoadPosition) -> i32 {
+    let buildings = rules
+        .buildings
+        .iter()
+        .map(|(_, building, _)| *building)
+        .collect::<Vec<_>>();
+    rules::buildings_on_road(rules.board.size, road)
+        .filter(|building| rules::no_touching_buildings(building, &buildings, rules.board.size))
+        .map(|building| building_score(rules, &building))
+        .max()
+        .unwrap_or(0)
+}
+
+fn robbed(rules: &GameState, me: PlayerHandle) -> bool {
+    rules
+        .buildings
+        .iter()
+        .any(|(owner, building, _)| *owner == me && building.contains(&rules.robber))
+}
+
+// block the hex that produces the most for the other players (and the least for us)
+fn robber_score(rules: &GameState, me: PlayerHandle, position: Position) -> i32 {
+    let pips = hex_pips(rules, position);
+    rules
+        .buildings
+        .iter()
+        .filter(|(_, building, _)| building.contains(&position))
+        .map(|(owner, _, piece)| {
+            let production = match piece {
+                Piece::Town => pips,
+                Piece::City => 2 * pips,
+            };
+            if *owner == me {
+                -10 * production
+            } else {
+                10 * production
+            }
+        })
+        .sum()
+}
+
+// how many of this resource we still need for a city and a town
+fn missing(resources: Resources, resource: Resource) -> i32 {
+    i32::from(CITY_RESOURCES.get(resource) + TOWN_RESOURCES.get(resource))
+        - i32::from(resources.get(resource))
+}
+
+// a bank trade is only worth it if it lets us build something we couldn't before
+fn bank_trade_score(rules: &GameState, me: PlayerHandle, trade: TradingResources) -> i32 {
+    let Some(this) = rules.player(me) else {
+        return -1;
+    };
+    let Some(after) = this.resources.checked_add(trade) else {
+        return -1;
+    };
+    let goals = [
+        (
+            CITY_RESOURCES,
+            this.cities_left > 0 && !rules.city_placements(me).is_empty(),
+        ),
+        (
+            TOWN_RESOURCES,
+            this.towns_left > 0 && !rules.town_placements(me).is_empty(),
+        ),
+        (
+            DEVELOPMENT_CARD_RESOURCES,
+            !rules.development_cards.is_empty(),
+        ),
+    ];
+    if goals
+        .into_iter()
+        .any(|(cost, possible)| possible && !this.resources.contains(cost) && after.contains(cost))
+    {
+        30
+    } else {
+        -1
+    }
+}
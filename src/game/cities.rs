@@ -11,14 +11,14 @@ use super::{
     Building, GameState, Input, KatanComponent, Left,
     colors::{CatanColor, CurrentColor},
     positions::BuildingPosition,
-    resources::{CITY_RESOURCES, Resources},
+    resources::Resources,
     towns::Town,
 };
 
 #[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent, PlaceButton)]
-pub struct CityPlaceButton(Resources, BuildingPosition);
-#[derive(Debug, Component, Clone, Copy)]
+pub struct CityPlaceButton(BuildingPosition);
+#[derive(Debug, Component, Clone, Copy, Hash)]
 #[require(KatanComponent)]
 #[require(Building)]
 pub struct City;
@@ -38,6 +38,7 @@ impl UI for CityUI {
         let mesh1 = meshes.add(Rectangle::new(scale * 4.3, scale * 4.3));
         (
             KatanComponent,
+            DrawnCity(city_position),
             Mesh2d(mesh1),
             MeshMaterial2d(materials.add(color.to_bevy_color())),
             Transform::from_xyz(x * scale * 25.6, y * scale * 25.6, 0.0),
@@ -54,19 +55,23 @@ impl UI for CityUI {
         }
     }
 }
+/// the mesh of a city, so it can be taken away again when the barbarians pillage it
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct DrawnCity(pub BuildingPosition);
 #[derive(SystemParam)]
 pub struct PlaceCityButtonState<'w> {
     game_state_mut: ResMut<'w, NextState<GameState>>,
     input: ResMut<'w, Input>,
 }
 impl ButtonInteraction<CityPlaceButton> for PlaceCityButtonState<'_> {
-    fn interact(&mut self, CityPlaceButton(cost, position): &CityPlaceButton) {
+    fn interact(&mut self, CityPlaceButton(position): &CityPlaceButton) {
         let PlaceCityButtonState {
             game_state_mut,
             input,
         } = self;
 
-        **input = Input::AddCity(*position, *cost);
+        **input = Input::AddCity(*position);
         game_state_mut.set(GameState::Turn);
     }
 }
@@ -110,7 +115,7 @@ pub fn place_normal_city(
                         height: Val::VMin(2.0),
                         ..default()
                     },
-                    CityPlaceButton(CITY_RESOURCES, p),
+                    CityPlaceButton(p),
                     BorderRadius::MAX,
                     BackgroundColor(NORMAL_BUTTON),
                 )),
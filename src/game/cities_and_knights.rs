@@ -0,0 +1,722 @@
+//! cities & knights, commodities, city improvements, progress cards, knights and the barbarians
+//! the rules for all of it are in `rules`, this is what they keep track of and the ui for it, which
+//! is only shown when the game was started with cities & knights
+//! not in here (yet): metropolises and the improvement abilities (like the aqueduct), trading
+//! commodities, commodities counting towards the 7 card limit, knights blocking roads and
+//! starting with a city
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ui_anchor::{AnchorPoint, AnchorUiConfig, AnchoredUiNodes};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+
+use super::{
+    GameState, Input, KatanComponent, Layout, LocalPlayer, PlaceButton, PlayerHandle, Rules,
+    cities::{City, DrawnCity},
+    colors::CurrentColor,
+    common_ui::{self, ButtonInteraction},
+    positions::BuildingPosition,
+    resources::Commodity,
+    rules,
+    towns::Town,
+};
+
+/// while this exists new games are played with cities & knights, picked in the lobby
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlayCitiesAndKnights;
+
+/// the three kinds of city improvements, each one is paid for with its own commodity
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Hash)]
+pub enum Track {
+    Science,
+    Trade,
+    Politics,
+}
+impl Track {
+    pub const ALL: [Self; 3] = [Self::Science, Self::Trade, Self::Politics];
+    pub const fn commodity(self) -> Commodity {
+        match self {
+            Self::Science => Commodity::Paper,
+            Self::Trade => Commodity::Cloth,
+            Self::Politics => Commodity::Coin,
+        }
+    }
+}
+
+pub const MAX_IMPROVEMENT: u8 = 5;
+// mighty knights need a fortress (the third politics improvement)
+pub const MIGHTY_KNIGHT_IMPROVEMENT: u8 = 3;
+/// how far each track is improved, from 0 to `MAX_IMPROVEMENT`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, Hash)]
+pub struct Improvements {
+    pub science: u8,
+    pub trade: u8,
+    pub politics: u8,
+}
+impl Improvements {
+    pub const fn get(&self, track: Track) -> u8 {
+        match track {
+            Track::Science => self.science,
+            Track::Trade => self.trade,
+            Track::Politics => self.politics,
+        }
+    }
+    pub const fn get_mut(&mut self, track: Track) -> &mut u8 {
+        match track {
+            Track::Science => &mut self.science,
+            Track::Trade => &mut self.trade,
+            Track::Politics => &mut self.politics,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Hash)]
+pub enum ProgressCard {
+    // science
+    // two wheat for every field next to one of your buildings
+    Irrigation,
+    // two ore for every mountain next to one of your buildings
+    Mining,
+    RoadBuilding,
+    Printer,
+    // trade
+    // up to two of a resource from every other player
+    ResourceMonopoly,
+    // one of a commodity from every other player
+    TradeMonopoly,
+    // politics
+    // moves the robber like a knight
+    Bishop,
+    // activates all of your knights for free
+    Warlord,
+    Constitution,
+}
+impl ProgressCard {
+    pub const fn track(self) -> Track {
+        match self {
+            Self::Irrigation | Self::Mining | Self::RoadBuilding | Self::Printer => Track::Science,
+            Self::ResourceMonopoly | Self::TradeMonopoly => Track::Trade,
+            Self::Bishop | Self::Warlord | Self::Constitution => Track::Politics,
+        }
+    }
+    /// the printer and the constitution are a victory point as soon as they are drawn, so they
+    /// never end up in a hand
+    pub const fn victory_point(self) -> bool {
+        matches!(self, Self::Printer | Self::Constitution)
+    }
+}
+/// the progress card pile before shuffling, only the cards we have (the real game has 18 of each
+/// track)
+pub fn progress_cards() -> Vec<ProgressCard> {
+    [
+        (ProgressCard::Irrigation, 2),
+        (ProgressCard::Mining, 2),
+        (ProgressCard::RoadBuilding, 2),
+        (ProgressCard::Printer, 1),
+        (ProgressCard::ResourceMonopoly, 4),
+        (ProgressCard::TradeMonopoly, 2),
+        (ProgressCard::Bishop, 2),
+        (ProgressCard::Warlord, 2),
+        (ProgressCard::Constitution, 1),
+    ]
+    .into_iter()
+    .flat_map(|(card, count)| std::iter::repeat_n(card, count))
+    .collect()
+}
+// how many progress cards (that aren't victory points) a player can hold
+pub const PROGRESS_CARD_LIMIT: usize = 4;
+
+/// the third die, either the barbarians get closer or the players with enough improvements in
+/// the track on the gate get a progress card
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Hash)]
+pub enum EventDie {
+    Ship,
+    Gate(Track),
+}
+impl EventDie {
+    /// every different result
+    pub const ALL: [Self; 4] = [
+        Self::Ship,
+        Self::Gate(Track::Science),
+        Self::Gate(Track::Trade),
+        Self::Gate(Track::Politics),
+    ];
+    // half of the die is the barbarian ship
+    pub const FACES: [Self; 6] = [
+        Self::Ship,
+        Self::Ship,
+        Self::Ship,
+        Self::Gate(Track::Science),
+        Self::Gate(Track::Trade),
+        Self::Gate(Track::Politics),
+    ];
+}
+
+// how many ships the barbarians sail before they attack
+pub const BARBARIAN_DISTANCE: u8 = 7;
+// basic, strong and mighty
+pub const KNIGHT_LEVELS: u8 = 3;
+pub const KNIGHTS_PER_LEVEL: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Knight {
+    pub owner: PlayerHandle,
+    pub position: BuildingPosition,
+    pub level: u8,
+    /// only active knights defend against the barbarians or chase away the robber
+    pub active: bool,
+}
+
+/// everything cities & knights adds to the game that isn't part of a player
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CitiesAndKnights {
+    /// how far the barbarians have sailed, they attack once they get to `BARBARIAN_DISTANCE`
+    pub barbarians: u8,
+    /// the robber stays where it is until the barbarians attacked for the first time
+    pub attacked: bool,
+    pub knights: Vec<Knight>,
+    // top of the pile is the end
+    pub progress_cards: Vec<ProgressCard>,
+    pub last_event: Option<EventDie>,
+}
+impl CitiesAndKnights {
+    pub const fn new(progress_cards: Vec<ProgressCard>) -> Self {
+        Self {
+            barbarians: 0,
+            attacked: false,
+            knights: vec![],
+            progress_cards,
+            last_event: None,
+        }
+    }
+}
+
+pub struct CitiesAndKnightsPlugin;
+impl Plugin for CitiesAndKnightsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::Start),
+            show_panel.run_if(playing_cities_and_knights),
+        )
+        .add_systems(
+            Update,
+            (update_panel, draw_knights, sync_pillaged)
+                .run_if(playing_cities_and_knights)
+                .run_if(resource_changed::<Rules>.or(resource_changed::<LocalPlayer>)),
+        )
+        .add_systems(
+            Update,
+            common_ui::button_system_with_generic::<
+                CitiesAndKnightsButton,
+                CitiesAndKnightsButtonState<'_>,
+            >
+                .run_if(in_state(GameState::Turn)),
+        )
+        .add_systems(OnEnter(GameState::PlaceKnight), show_knight_placements)
+        .add_systems(OnEnter(GameState::PromoteKnight), show_knight_promotions)
+        .add_systems(OnEnter(GameState::ActivateKnight), show_knight_activations)
+        .add_systems(OnEnter(GameState::ChaseRobber), show_robber_chases)
+        .add_systems(
+            Update,
+            common_ui::button_system_with_generic::<KnightButton, KnightButtonState<'_>>.run_if(
+                in_state(GameState::PlaceKnight)
+                    .or(in_state(GameState::PromoteKnight))
+                    .or(in_state(GameState::ActivateKnight))
+                    .or(in_state(GameState::ChaseRobber)),
+            ),
+        )
+        .add_systems(
+            OnExit(GameState::PlaceKnight),
+            super::cleanup_button::<KnightButton>,
+        )
+        .add_systems(
+            OnExit(GameState::PromoteKnight),
+            super::cleanup_button::<KnightButton>,
+        )
+        .add_systems(
+            OnExit(GameState::ActivateKnight),
+            super::cleanup_button::<KnightButton>,
+        )
+        .add_systems(
+            OnExit(GameState::ChaseRobber),
+            super::cleanup_button::<KnightButton>,
+        )
+        .add_systems(
+            OnEnter(GameState::CommodityMonopoly),
+            commodity_monopoly_setup,
+        )
+        .add_systems(
+            Update,
+            commodity_monopoly_interaction.run_if(in_state(GameState::CommodityMonopoly)),
+        )
+        .add_systems(
+            OnExit(GameState::CommodityMonopoly),
+            super::cleanup_button::<CommodityMonopolyButton>,
+        );
+    }
+}
+
+/// run condition for everything that only exists in a cities & knights game
+pub fn playing_cities_and_knights(rules: Option<Res<'_, Rules>>) -> bool {
+    rules.is_some_and(|rules| rules.cities_and_knights.is_some())
+}
+
+// knight placing, promoting, activating and chasing the robber away all pick an intersection
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent, PlaceButton)]
+pub struct KnightButton(Input);
+#[derive(SystemParam)]
+pub struct KnightButtonState<'w> {
+    game_state_mut: ResMut<'w, NextState<GameState>>,
+    input: ResMut<'w, Input>,
+}
+impl ButtonInteraction<KnightButton> for KnightButtonState<'_> {
+    fn interact(&mut self, KnightButton(input): &KnightButton) {
+        *self.input = *input;
+        // the robber still has to be moved after being chased away
+        self.game_state_mut
+            .set(if matches!(input, Input::ChaseRobber(_)) {
+                GameState::PlaceRobber
+            } else {
+                GameState::Turn
+            });
+    }
+}
+
+fn spawn_knight_buttons(
+    commands: &mut Commands<'_, '_>,
+    game_state: &mut NextState<GameState>,
+    positions: Vec<BuildingPosition>,
+    input: fn(BuildingPosition) -> Input,
+) {
+    let multiplier = 3.0;
+    if positions.is_empty() {
+        game_state.set(GameState::Turn);
+    }
+    for position in positions {
+        let (x, y) = position.positon_to_pixel_coordinates();
+        commands.spawn((
+            Transform::from_xyz(x * multiplier * 25.6, y * multiplier * 25.6, 0.0),
+            AnchoredUiNodes::spawn_one((
+                AnchorUiConfig {
+                    anchorpoint: AnchorPoint::middle(),
+                    offset: None,
+                    ..Default::default()
+                },
+                Button,
+                Node {
+                    width: Val::VMin(2.0),
+                    height: Val::VMin(2.0),
+                    ..default()
+                },
+                KnightButton(input(position)),
+                BorderRadius::MAX,
+                BackgroundColor(NORMAL_BUTTON),
+            )),
+        ));
+    }
+}
+fn show_knight_placements(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    spawn_knight_buttons(
+        &mut commands,
+        &mut game_state,
+        rules.knight_placements(color_r.0.handle),
+        Input::AddKnight,
+    );
+}
+fn show_knight_promotions(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    spawn_knight_buttons(
+        &mut commands,
+        &mut game_state,
+        rules.knight_promotions(color_r.0.handle),
+        Input::PromoteKnight,
+    );
+}
+fn show_knight_activations(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    spawn_knight_buttons(
+        &mut commands,
+        &mut game_state,
+        rules.knight_activations(color_r.0.handle),
+        Input::ActivateKnight,
+    );
+}
+fn show_robber_chases(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    spawn_knight_buttons(
+        &mut commands,
+        &mut game_state,
+        rules.robber_chases(color_r.0.handle),
+        Input::ChaseRobber,
+    );
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+pub struct CommodityMonopolyButton(Commodity);
+fn commodity_monopoly_setup(mut commands: Commands<'_, '_>) {
+    Commodity::ALL.iter().enumerate().for_each(|(i, c)| {
+        commands.spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            children![(
+                Button,
+                Node {
+                    position_type: PositionType::Relative,
+                    width: Val::Px(15.0),
+                    height: Val::Px(15.0),
+
+                    bottom: Val::Px(35.),
+                    left: Val::Px((i * 30) as f32),
+                    ..default()
+                },
+                CommodityMonopolyButton(*c),
+                BorderRadius::MAX,
+                BackgroundColor(c.color()),
+            )],
+        ));
+    });
+}
+fn commodity_monopoly_interaction(
+    mut interaction_query: Query<
+        '_,
+        '_,
+        (
+            &Interaction,
+            &mut Button,
+            &mut BackgroundColor,
+            &CommodityMonopolyButton,
+        ),
+        (Changed<Interaction>,),
+    >,
+
+    mut state: ResMut<'_, NextState<GameState>>,
+    mut input: ResMut<'_, Input>,
+) {
+    for (interaction, mut button, mut color, kind) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *input = Input::CommodityMonopoly(kind.0);
+                *color = PRESSED_BUTTON.into();
+                button.set_changed();
+                state.set(GameState::Turn);
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                button.set_changed();
+            }
+            Interaction::None => {
+                *color = kind.0.color().into();
+            }
+        }
+    }
+}
+
+// the barbarians, commodities, improvements and knights of the local player
+#[derive(Component, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+pub struct CitiesAndKnightsText;
+#[derive(Component, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+pub struct ProgressCardList;
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[require(KatanComponent)]
+pub enum CitiesAndKnightsButton {
+    Knight,
+    Promote,
+    Activate,
+    Chase,
+    Improve(Track),
+    Progress(ProgressCard),
+}
+fn text_button(text: String, button: CitiesAndKnightsButton) -> impl Bundle {
+    (
+        Node {
+            height: Val::Px(25.0),
+            padding: UiRect::horizontal(Val::Px(3.)),
+            ..default()
+        },
+        Button,
+        Text::new(text),
+        BackgroundColor(NORMAL_BUTTON),
+        button,
+    )
+}
+fn show_panel(mut commands: Commands<'_, '_>, layout: Res<'_, Layout>) {
+    commands.entity(layout.board).with_child((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(1.),
+            top: Val::Percent(1.),
+            display: Display::Grid,
+            row_gap: Val::Px(3.),
+            ..default()
+        },
+        children![
+            (
+                Text::new(""),
+                TextFont::from_font_size(13.),
+                TextColor(Color::BLACK),
+                CitiesAndKnightsText,
+            ),
+            (
+                Node {
+                    display: Display::Grid,
+                    grid_auto_flow: GridAutoFlow::Column,
+                    column_gap: Val::Px(3.),
+                    ..default()
+                },
+                children![
+                    text_button("knight".to_string(), CitiesAndKnightsButton::Knight),
+                    text_button("promote".to_string(), CitiesAndKnightsButton::Promote),
+                    text_button("activate".to_string(), CitiesAndKnightsButton::Activate),
+                    text_button("chase robber".to_string(), CitiesAndKnightsButton::Chase),
+                ],
+            ),
+            (
+                Node {
+                    display: Display::Grid,
+                    grid_auto_flow: GridAutoFlow::Column,
+                    column_gap: Val::Px(3.),
+                    ..default()
+                },
+                children![
+                    text_button(
+                        "improve science".to_string(),
+                        CitiesAndKnightsButton::Improve(Track::Science)
+                    ),
+                    text_button(
+                        "improve trade".to_string(),
+                        CitiesAndKnightsButton::Improve(Track::Trade)
+                    ),
+                    text_button(
+                        "improve politics".to_string(),
+                        CitiesAndKnightsButton::Improve(Track::Politics)
+                    ),
+                ],
+            ),
+            (
+                Node {
+                    display: Display::Grid,
+                    grid_auto_flow: GridAutoFlow::Column,
+                    column_gap: Val::Px(3.),
+                    ..default()
+                },
+                ProgressCardList,
+            ),
+        ],
+    ));
+}
+fn update_panel(
+    mut commands: Commands<'_, '_>,
+    rules: Res<'_, Rules>,
+    local_player: Res<'_, LocalPlayer>,
+    mut text: Query<'_, '_, &mut Text, With<CitiesAndKnightsText>>,
+    list: Query<'_, '_, Entity, With<ProgressCardList>>,
+) {
+    let (Some(cities_and_knights), Some(player)) = (
+        rules.cities_and_knights.as_ref(),
+        rules.player(local_player.0.handle),
+    ) else {
+        return;
+    };
+    let handle = local_player.0.handle;
+    let knights = cities_and_knights
+        .knights
+        .iter()
+        .filter(|knight| knight.owner == handle)
+        .collect_vec();
+    let defence: u8 = knights
+        .iter()
+        .filter(|knight| knight.active)
+        .map(|knight| knight.level)
+        .sum();
+    let strength = rules
+        .buildings
+        .iter()
+        .filter(|(_, _, piece)| *piece == rules::Piece::City)
+        .count();
+    let event = match cities_and_knights.last_event {
+        None => "-".to_string(),
+        Some(EventDie::Ship) => "ship".to_string(),
+        Some(EventDie::Gate(track)) => format!("{track:?} gate").to_lowercase(),
+    };
+    for mut text in &mut text {
+        text.0 = format!(
+            "barbarians: {}/{BARBARIAN_DISTANCE} (strength {strength}), event: {event}\npaper: \
+             {}, cloth: {}, coin: {}\nscience: {}, trade: {}, politics: {}\nknights: {} ({} \
+             active), defence: {defence}",
+            cities_and_knights.barbarians,
+            player.commodities.paper,
+            player.commodities.cloth,
+            player.commodities.coin,
+            player.improvements.science,
+            player.improvements.trade,
+            player.improvements.politics,
+            knights.len(),
+            knights.iter().filter(|knight| knight.active).count(),
+        );
+    }
+    for list in list {
+        commands
+            .entity(list)
+            .despawn_related::<Children>()
+            .with_children(|parent| {
+                for card in &player.progress_cards {
+                    parent.spawn(text_button(
+                        format!("{card:?}"),
+                        CitiesAndKnightsButton::Progress(*card),
+                    ));
+                }
+            });
+    }
+}
+#[derive(SystemParam)]
+pub struct CitiesAndKnightsButtonState<'w> {
+    rules: Res<'w, Rules>,
+    local_player: Res<'w, LocalPlayer>,
+    game_state_mut: ResMut<'w, NextState<GameState>>,
+    input: ResMut<'w, Input>,
+}
+impl ButtonInteraction<CitiesAndKnightsButton> for CitiesAndKnightsButtonState<'_> {
+    fn interact(&mut self, button: &CitiesAndKnightsButton) {
+        match *button {
+            CitiesAndKnightsButton::Knight => self.game_state_mut.set(GameState::PlaceKnight),
+            CitiesAndKnightsButton::Promote => self.game_state_mut.set(GameState::PromoteKnight),
+            CitiesAndKnightsButton::Activate => {
+                self.game_state_mut.set(GameState::ActivateKnight);
+            }
+            CitiesAndKnightsButton::Chase => self.game_state_mut.set(GameState::ChaseRobber),
+            CitiesAndKnightsButton::Improve(track) => *self.input = Input::Improve(track),
+            CitiesAndKnightsButton::Progress(card) => {
+                // the card is taken away once the input is applied by the rules
+                *self.input = Input::PlayProgressCard(card);
+                match card {
+                    ProgressCard::RoadBuilding => self.game_state_mut.set(GameState::RoadBuilding),
+                    ProgressCard::ResourceMonopoly => self.game_state_mut.set(GameState::Monopoly),
+                    ProgressCard::TradeMonopoly => {
+                        self.game_state_mut.set(GameState::CommodityMonopoly);
+                    }
+                    ProgressCard::Bishop => self.game_state_mut.set(GameState::PlaceRobber),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // only what the rules would allow right now
+    fn verify(&mut self, button: &CitiesAndKnightsButton) -> bool {
+        let handle = self.local_player.0.handle;
+        match *button {
+            CitiesAndKnightsButton::Knight => !self.rules.knight_placements(handle).is_empty(),
+            CitiesAndKnightsButton::Promote => !self.rules.knight_promotions(handle).is_empty(),
+            CitiesAndKnightsButton::Activate => !self.rules.knight_activations(handle).is_empty(),
+            CitiesAndKnightsButton::Chase => {
+                self.rules.phase == rules::Phase::Turn
+                    && !self.rules.robber_chases(handle).is_empty()
+            }
+            CitiesAndKnightsButton::Improve(track) => {
+                self.rules.improvements(handle).contains(&track)
+            }
+            CitiesAndKnightsButton::Progress(card) => {
+                self.rules.phase == rules::Phase::Turn && self.rules.can_play_progress_card(card)
+            }
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+pub struct DrawnKnight;
+// knights are redrawn from the rules whenever they change, inactive knights are see through and
+// the number is the knights level
+fn draw_knights(
+    mut commands: Commands<'_, '_>,
+    rules: Res<'_, Rules>,
+    drawn: Query<'_, '_, Entity, With<DrawnKnight>>,
+    mut last: Local<'_, Vec<Knight>>,
+    mut meshes: ResMut<'_, Assets<Mesh>>,
+    mut materials: ResMut<'_, Assets<ColorMaterial>>,
+) {
+    let Some(cities_and_knights) = &rules.cities_and_knights else {
+        return;
+    };
+    if *last == cities_and_knights.knights {
+        return;
+    }
+    last.clone_from(&cities_and_knights.knights);
+    for entity in drawn {
+        commands.entity(entity).despawn();
+    }
+    let scale = 3.;
+    for knight in &cities_and_knights.knights {
+        let Some(owner) = rules.player(knight.owner) else {
+            continue;
+        };
+        let color = owner.color.to_bevy_color();
+        let color = if knight.active {
+            color
+        } else {
+            color.with_alpha(0.4)
+        };
+        let (x, y) = knight.position.positon_to_pixel_coordinates();
+        commands.spawn((
+            DrawnKnight,
+            Mesh2d(meshes.add(Circle::new(scale * 2.3))),
+            MeshMaterial2d(materials.add(color)),
+            Transform::from_xyz(x * scale * 25.6, y * scale * 25.6, 1.0),
+            children![(
+                Text2d::new(knight.level.to_string()),
+                TextFont::from_font_size(10.),
+                TextColor(Color::BLACK),
+                Transform::from_xyz(0., 0., 1.0),
+            )],
+        ));
+    }
+}
+
+// cities the barbarians pillaged are towns again, the towns mesh is still under the cities one
+fn sync_pillaged(
+    mut commands: Commands<'_, '_>,
+    rules: Res<'_, Rules>,
+    cities: Query<'_, '_, (Entity, &BuildingPosition), With<City>>,
+    drawn: Query<'_, '_, (Entity, &DrawnCity)>,
+) {
+    for (entity, position) in cities {
+        if !rules
+            .buildings
+            .iter()
+            .any(|(_, town, piece)| town == position && *piece == rules::Piece::Town)
+        {
+            continue;
+        }
+        commands.entity(entity).remove::<City>().insert(Town);
+        for (drawn_entity, _) in drawn.iter().filter(|(_, drawn)| drawn.0 == *position) {
+            commands.entity(drawn_entity).despawn();
+        }
+    }
+}
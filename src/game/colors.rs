@@ -9,6 +9,7 @@ use bevy::{
     color::{self, palettes::css},
     prelude::*,
 };
+use serde::{Deserialize, Serialize};
 
 use super::{GameState, KatanComponent, LocalPlayer, PlayerHandle, turn_ui::PlayerBanner};
 
@@ -38,13 +39,18 @@ impl From<CurrentSetupColor> for CatanColor {
         value.0.color
     }
 }
-#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Debug, Component, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+)]
 #[require(KatanComponent)]
 pub enum CatanColor {
     Red,
     Green,
     Blue,
     White,
+    // only for 5-6 players
+    Orange,
+    Brown,
 }
 #[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
 #[require(KatanComponent)]
@@ -66,6 +72,8 @@ impl CatanColor {
             Self::Green => color::palettes::basic::GREEN.into(),
             Self::Blue => color::palettes::basic::BLUE.into(),
             Self::White => color::palettes::basic::WHITE.into(),
+            Self::Orange => css::ORANGE.into(),
+            Self::Brown => css::SADDLE_BROWN.into(),
         }
     }
 }
@@ -82,6 +90,43 @@ pub fn set_color(
 
     game_state: &mut ResMut<'_, NextState<GameState>>,
     player_banners: &mut Query<'_, '_, (&mut BackgroundColor, &mut Outline, &PlayerBanner)>,
+) {
+    let next = color_rotation.0.next().unwrap();
+    move_highlight(color_r, next, player_banners);
+    super::next_player(
+        game_state,
+        local_player,
+        color_r.0,
+        GameState::Roll,
+        GameState::NotActive,
+    );
+}
+
+// with 5-6 players everyone else gets to build after a turn, the builder takes over the current
+// color so the placement ui works for them, but the turn order stays the same
+pub fn set_special_building_color(
+    color_r: &mut ResMut<'_, CurrentColor>,
+    builder: CatanColorRef,
+
+    local_player: &Res<'_, LocalPlayer>,
+
+    game_state: &mut ResMut<'_, NextState<GameState>>,
+    player_banners: &mut Query<'_, '_, (&mut BackgroundColor, &mut Outline, &PlayerBanner)>,
+) {
+    move_highlight(color_r, builder, player_banners);
+    super::next_player(
+        game_state,
+        local_player,
+        color_r.0,
+        GameState::Turn,
+        GameState::NotActive,
+    );
+}
+
+fn move_highlight(
+    color_r: &mut ResMut<'_, CurrentColor>,
+    next: CatanColorRef,
+    player_banners: &mut Query<'_, '_, (&mut BackgroundColor, &mut Outline, &PlayerBanner)>,
 ) {
     if let Some((mut background, mut border, _)) = player_banners
         .iter_mut()
@@ -90,7 +135,7 @@ pub fn set_color(
         *background = BackgroundColor(background.0.with_alpha(0.5));
         border.color = Color::NONE;
     }
-    **color_r = CurrentColor(color_rotation.0.next().unwrap());
+    **color_r = CurrentColor(next);
     if let Some((mut background, mut border, _)) = player_banners
         .iter_mut()
         .find(|(_, _, banner)| banner.0 == color_r.0)
@@ -99,13 +144,6 @@ pub fn set_color(
         border.color = css::CADET_BLUE.into();
         *background = BackgroundColor(background.0.with_alpha(1.0));
     }
-    super::next_player(
-        game_state,
-        local_player,
-        color_r.0,
-        GameState::Roll,
-        GameState::NotActive,
-    );
 }
 
 pub fn set_setup_color(
@@ -0,0 +1,441 @@
+//! desync detection, ggrs only tells us that the checksums of a frame are different so we also keep
+//! a checksum per part of the game state and send them to the other peer when that happens
+//! in a sync test session the same checksums catch a frame that comes out different when it is
+//! simulated again after a rollback
+use std::{
+    collections::VecDeque,
+    hash::{Hash, Hasher},
+};
+
+use bevy::prelude::*;
+use bevy_ggrs::{GgrsSchedule, GgrsTime, LocalPlayers, RollbackApp, RollbackFrameCount, Session};
+use bevy_matchbox::prelude::*;
+use itertools::Itertools;
+
+use super::{
+    GgrsSessionConfig, KatanComponent, Knights, Left, PlayerHandle, Rules, SessionRng,
+    VictoryPoints,
+    cities::City,
+    development_cards::{DevelopmentCards, DevelopmentCardsPile},
+    larget_army::{LargetArmyRef, update_larget_army},
+    longest_road::{LongestRoadRef, update_longest_road_ref},
+    resources::Resources,
+    roads::Road,
+    robber::Robber,
+    ships::Ship,
+    towns::Town,
+};
+use crate::{
+    AppState,
+    lobby::RELIABLE_CHANNEL,
+    utils::{BORDER_COLOR_ACTIVE, NORMAL_BUTTON, StableHasher, TEXT_COLOR, stable_hash},
+};
+
+// how many frames of checksums we keep around, ggrs only reports a desync once the frame is
+// confirmed by both peers so this has to cover the network delay
+const HISTORY: usize = 600;
+
+const PARTS: [&str; 15] = [
+    "resources",
+    "victory points",
+    "roads left",
+    "towns left",
+    "cities left",
+    "development cards",
+    "robber",
+    "bank",
+    "ships left",
+    "knights",
+    "development card pile",
+    "longest road",
+    "largest army",
+    "rules",
+    "rng",
+];
+type Checksums = [u64; PARTS.len()];
+
+#[derive(Message, Clone, Copy, Debug)]
+pub struct DesyncDetected {
+    pub frame: i32,
+    pub peer: PeerId,
+}
+
+/// a frame of a sync test session that came out different after a rollback, so something isn't
+/// rolled back (or isn't deterministic)
+#[derive(Message, Clone, Debug)]
+pub struct RollbackMismatch {
+    pub frame: i32,
+    pub diverged: String,
+}
+
+#[derive(Resource, Default, Debug)]
+struct ChecksumHistory(VecDeque<(i32, Checksums)>);
+
+#[derive(Resource, Debug)]
+struct DesyncReport {
+    frame: i32,
+    local: Option<Checksums>,
+    remote: Option<Checksums>,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+struct DesyncText;
+
+pub struct DesyncPlugin;
+impl Plugin for DesyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<DesyncDetected>()
+            .add_message::<RollbackMismatch>()
+            .checksum_component_with_hash::<Resources>()
+            .checksum_component_with_hash::<VictoryPoints>()
+            .checksum_component_with_hash::<Left<Road>>()
+            .checksum_component_with_hash::<Left<Town>>()
+            .checksum_component_with_hash::<Left<City>>()
+            .checksum_component_with_hash::<Left<Ship>>()
+            .checksum_component_with_hash::<DevelopmentCards>()
+            .checksum_component_with_hash::<Knights>()
+            .checksum_component_with_hash::<LongestRoadRef>()
+            .checksum_component_with_hash::<LargetArmyRef>()
+            .checksum_resource_with_hash::<DevelopmentCardsPile>()
+            .checksum_resource_with_hash::<Robber>()
+            // the bank
+            .checksum_resource_with_hash::<Resources>()
+            // what the game actually runs on, the rest is a view of it
+            .checksum_resource::<Rules>(|rules| stable_hash(&rules.0))
+            .checksum_resource::<SessionRng>(|rng| stable_hash(&rng.0))
+            .add_systems(OnEnter(AppState::InGame), reset)
+            .add_systems(
+                GgrsSchedule,
+                record_checksums
+                    .after(update_longest_road_ref)
+                    .after(update_larget_army),
+            )
+            .add_systems(
+                Update,
+                (
+                    // replays don't have a socket (or any other peer)
+                    report_desync
+                        .run_if(in_state(AppState::InGame).and(resource_exists::<MatchboxSocket>)),
+                    // in the lobby this channel is used for claiming handles
+                    receive_checksums.run_if(
+                        resource_exists::<MatchboxSocket>.and(not(in_state(AppState::Menu))),
+                    ),
+                    update_desync_text.run_if(resource_exists_and_changed::<DesyncReport>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn reset(mut commands: Commands<'_, '_>) {
+    commands.insert_resource(ChecksumHistory::default());
+    commands.remove_resource::<DesyncReport>();
+}
+
+// runs at the end of every rollback frame, so after a rollback the frames that were resimulated
+// get overwritten
+fn record_checksums(
+    frame: Res<'_, RollbackFrameCount>,
+    mut history: ResMut<'_, ChecksumHistory>,
+    players: Query<
+        '_,
+        '_,
+        (
+            &PlayerHandle,
+            &Resources,
+            &VictoryPoints,
+            &Left<Road>,
+            &Left<Town>,
+            &Left<City>,
+            &DevelopmentCards,
+            &Left<Ship>,
+            &Knights,
+            Has<LongestRoadRef>,
+            Has<LargetArmyRef>,
+        ),
+    >,
+    robber: Res<'_, Robber>,
+    bank: Res<'_, Resources>,
+    development_card_pile: Res<'_, DevelopmentCardsPile>,
+    rules: Res<'_, Rules>,
+    rng: Res<'_, SessionRng>,
+    session: Option<Res<'_, Session<GgrsSessionConfig>>>,
+    mut mismatches: MessageWriter<'_, RollbackMismatch>,
+) {
+    let frame = **frame;
+    let mut hashers: [StableHasher; PARTS.len()] = Default::default();
+    for (
+        _,
+        resources,
+        victory_points,
+        roads,
+        towns,
+        cities,
+        development_cards,
+        ships,
+        knights,
+        longest_road,
+        largest_army,
+    ) in players.iter().sorted_by_key(|player| player.0.0)
+    {
+        resources.hash(&mut hashers[0]);
+        victory_points.hash(&mut hashers[1]);
+        roads.hash(&mut hashers[2]);
+        towns.hash(&mut hashers[3]);
+        cities.hash(&mut hashers[4]);
+        development_cards.hash(&mut hashers[5]);
+        ships.hash(&mut hashers[8]);
+        knights.hash(&mut hashers[9]);
+        longest_road.hash(&mut hashers[11]);
+        largest_army.hash(&mut hashers[12]);
+    }
+    robber.hash(&mut hashers[6]);
+    bank.hash(&mut hashers[7]);
+    development_card_pile.hash(&mut hashers[10]);
+    let mut checksums = hashers.map(|hasher| hasher.finish());
+    // the rules and the rng aren't `Hash`, the rules only keep ordered sets so they serialize the
+    // same on every peer
+    checksums[13] = stable_hash(&rules.0);
+    checksums[14] = stable_hash(&rng.0);
+
+    // a sync test session simulates every frame again with the same inputs, so it has to come out
+    // the same (in other sessions the inputs of the first try might have been predicted wrong)
+    if matches!(session.as_deref(), Some(Session::SyncTest(_)))
+        && let Some(first) = checksums_at(&history, frame)
+        && first != checksums
+    {
+        mismatches.write(RollbackMismatch {
+            frame,
+            diverged: diverged(&first, &checksums),
+        });
+    }
+    while history.0.back().is_some_and(|(f, _)| *f >= frame) {
+        history.0.pop_back();
+    }
+    history.0.push_back((frame, checksums));
+    if history.0.len() > HISTORY {
+        history.0.pop_front();
+    }
+}
+
+fn checksums_at(history: &ChecksumHistory, frame: i32) -> Option<Checksums> {
+    history
+        .0
+        .iter()
+        .find(|(f, _)| *f == frame)
+        .map(|(_, checksums)| *checksums)
+}
+
+fn diverged(local: &Checksums, remote: &Checksums) -> String {
+    PARTS
+        .iter()
+        .zip(local.iter().zip(remote.iter()))
+        .filter(|(_, (local, remote))| local != remote)
+        .map(|(part, _)| *part)
+        .join(", ")
+}
+
+fn encode(frame: i32, checksums: &Checksums) -> Box<[u8]> {
+    frame
+        .to_le_bytes()
+        .into_iter()
+        .chain(checksums.iter().flat_map(|checksum| checksum.to_le_bytes()))
+        .collect()
+}
+
+fn decode(packet: &[u8]) -> Option<(i32, Checksums)> {
+    if packet.len() != 4 + 8 * PARTS.len() {
+        return None;
+    }
+    let (frame, checksums) = packet.split_at(4);
+    let frame = i32::from_le_bytes(frame.try_into().ok()?);
+    let mut result = [0; PARTS.len()];
+    for (checksum, bytes) in result.iter_mut().zip(checksums.chunks_exact(8)) {
+        *checksum = u64::from_le_bytes(bytes.try_into().ok()?);
+    }
+    Some((frame, result))
+}
+
+// only the first desync is reported, after that the game is over anyway
+fn start_report(
+    commands: &mut Commands<'_, '_>,
+    socket: &mut MatchboxSocket,
+    history: &ChecksumHistory,
+    app_state: &mut NextState<AppState>,
+    frame: i32,
+    peer: PeerId,
+) -> DesyncReport {
+    let local = checksums_at(history, frame);
+    if let Some(local) = &local
+        && let Ok(channel) = socket.get_channel_mut(RELIABLE_CHANNEL)
+    {
+        channel.send(encode(frame, local), peer);
+    }
+
+    // same as winning, end ggrs session and keep the board so the player can see what happend
+    app_state.set(AppState::GameOver);
+    commands.remove_resource::<LocalPlayers>();
+    commands.remove_resource::<Session<GgrsSessionConfig>>();
+    // https://github.com/gschup/bevy_ggrs/issues/93
+    commands.insert_resource(Time::new_with(GgrsTime));
+    spawn_desync_panel(commands);
+
+    DesyncReport {
+        frame,
+        local,
+        remote: None,
+    }
+}
+
+fn report_desync(
+    mut commands: Commands<'_, '_>,
+    mut desyncs: MessageReader<'_, '_, DesyncDetected>,
+    mut socket: ResMut<'_, MatchboxSocket>,
+    history: Res<'_, ChecksumHistory>,
+    report: Option<Res<'_, DesyncReport>>,
+    mut app_state: ResMut<'_, NextState<AppState>>,
+) {
+    if report.is_some() {
+        desyncs.clear();
+        return;
+    }
+    if let Some(DesyncDetected { frame, peer }) = desyncs.read().next() {
+        let report = start_report(
+            &mut commands,
+            &mut socket,
+            &history,
+            &mut app_state,
+            *frame,
+            *peer,
+        );
+        commands.insert_resource(report);
+    }
+    desyncs.clear();
+}
+
+// the other peer might notice the desync before us, in that case we still reply with our
+// checksums
+fn receive_checksums(
+    mut commands: Commands<'_, '_>,
+    mut socket: ResMut<'_, MatchboxSocket>,
+    history: Option<Res<'_, ChecksumHistory>>,
+    mut report: Option<ResMut<'_, DesyncReport>>,
+    mut app_state: ResMut<'_, NextState<AppState>>,
+) {
+    let Ok(channel) = socket.get_channel_mut(RELIABLE_CHANNEL) else {
+        return;
+    };
+    let packets = channel.receive();
+    let Some(history) = history else {
+        return;
+    };
+    for (peer, packet) in packets {
+        let Some((frame, remote)) = decode(&packet) else {
+            warn!("got malformed checksums from {peer:?}");
+            continue;
+        };
+        if let Some(report) = &mut report {
+            if report.frame == frame {
+                report.remote = Some(remote);
+            }
+        } else {
+            let mut new_report = start_report(
+                &mut commands,
+                &mut socket,
+                &history,
+                &mut app_state,
+                frame,
+                peer,
+            );
+            new_report.remote = Some(remote);
+            commands.insert_resource(new_report);
+            // we only care about the first report
+            return;
+        }
+    }
+}
+
+fn spawn_desync_panel(commands: &mut Commands<'_, '_>) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_content: AlignContent::Center,
+            ..Default::default()
+        },
+        DespawnOnExit(AppState::GameOver),
+        children![(
+            Node {
+                display: Display::Grid,
+                margin: UiRect::all(Val::Auto),
+                border: UiRect::all(Val::Px(5.0)),
+                row_gap: Val::Percent(2.),
+                justify_content: JustifyContent::Center,
+                padding: UiRect::all(Val::Percent(5.0)),
+                align_content: AlignContent::Center,
+                grid_template_rows: vec![GridTrack::max_content(), GridTrack::max_content()],
+                ..Default::default()
+            },
+            BorderColor::all(BORDER_COLOR_ACTIVE),
+            BackgroundColor(NORMAL_BUTTON.with_alpha(0.9)),
+            children![
+                (
+                    Text::new("Desync"),
+                    TextColor(BORDER_COLOR_ACTIVE),
+                    TextFont {
+                        font_size: 34.,
+                        ..default()
+                    }
+                ),
+                (
+                    DesyncText,
+                    Text::default(),
+                    TextColor(TEXT_COLOR),
+                    TextFont {
+                        font_size: 20.,
+                        ..default()
+                    }
+                ),
+            ]
+        )],
+    ));
+}
+
+fn update_desync_text(
+    report: Res<'_, DesyncReport>,
+    mut text: Query<'_, '_, &mut Text, With<DesyncText>>,
+) {
+    let DesyncReport {
+        frame,
+        local,
+        remote,
+    } = *report;
+    let details = match (local, remote) {
+        (None, _) => "no local checksums for this frame".to_string(),
+        (Some(_), None) => "waiting for checksums of the other player".to_string(),
+        (Some(local), Some(remote)) => {
+            let diverged = diverged(&local, &remote);
+            let parts = PARTS
+                .iter()
+                .zip(local.iter().zip(remote.iter()))
+                .map(|(part, (local, remote))| {
+                    format!(
+                        "{part}: {local:016X} {remote:016X}{}",
+                        if local == remote { "" } else { " <- diverged" }
+                    )
+                })
+                .join("\n");
+            if diverged.is_empty() {
+                // something we dont checksum is different
+                format!("none of the checked parts diverged\n{parts}")
+            } else {
+                format!("diverged: {diverged}\n{parts}")
+            }
+        }
+    };
+    for mut text in &mut text {
+        text.0 = format!("on frame {frame}\n{details}");
+    }
+}
@@ -1,12 +1,12 @@
 use bevy::prelude::*;
 
 use crate::{
-    game::NeedToRoll,
+    game::{NeedToRoll, Rules},
     utils::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
 };
 
 use super::{
-    GameState, Input, KatanComponent, Knights, Layout,
+    GameState, Input, KatanComponent, Layout,
     colors::{CatanColor, CurrentColor},
     development_cards::{DevelopmentCard, DevelopmentCards},
     resources::{self},
@@ -29,28 +29,26 @@ pub fn development_card_action_interaction(
         (Changed<Interaction>, With<DevelopmentCardShow>),
     >,
 
-    mut player_dev_cards_and_knights: Query<
-        '_,
-        '_,
-        (&mut DevelopmentCards, &mut Knights),
-        With<CatanColor>,
-    >,
+    player_dev_cards: Query<'_, '_, &DevelopmentCards, With<CatanColor>>,
     res: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut input: ResMut<'_, Input>,
     layout: Res<'_, Layout>,
     state_mut: ResMut<'_, NextState<GameState>>,
     state: Res<'_, State<GameState>>,
     mut commands: Commands<'_, '_>,
 ) {
-    let development_cards = player_dev_cards_and_knights.get_mut(res.0.entity);
-    if let Ok((mut development_cards, knights)) = development_cards {
+    if let Ok(development_cards) = player_dev_cards.get(res.0.entity) {
         for (entity, interaction, mut color, mut button, development_card) in &mut interaction_query
         {
-            if *state.get() == GameState::Roll && *development_card != DevelopmentCard::Knight {
+            // only knights before rolling, one card a turn and not the turn it was bought
+            if !rules.can_play_development_card(res.0.handle, *development_card) {
                 continue;
             }
             match interaction {
                 Interaction::Pressed => {
-                    *development_cards.get_mut(*development_card) -= 1;
+                    // the card is taken away once the input is applied by the rules
+                    *input = Input::PlayDevelopmentCard(*development_card);
 
                     let development_card = *development_card;
                     // little hack because this interaction is with a changed, only the
@@ -63,12 +61,12 @@ pub fn development_card_action_interaction(
                         .remove_recursive::<Children, (Button, Interaction)>();
                     *color = PRESSED_BUTTON.into();
                     button.set_changed();
-                    if development_cards.get(development_card) == 0 {
+                    if development_cards.get(development_card) == 1 {
                         println!("removing {entity} compleletly");
                         commands.entity(entity).despawn();
                     }
                     match development_card {
-                        DevelopmentCard::Knight => robber(state_mut, knights, state, commands),
+                        DevelopmentCard::Knight => robber(state_mut, state, commands),
                         DevelopmentCard::Monopoly => monopoly(state_mut),
                         DevelopmentCard::YearOfPlenty => year_of_plenty(state_mut),
                         DevelopmentCard::RoadBuilding => road_building(state_mut),
@@ -90,14 +88,12 @@ pub fn development_card_action_interaction(
 
 pub fn robber(
     mut state_mut: ResMut<'_, NextState<GameState>>,
-    mut knights: Mut<'_, Knights>,
     state: Res<'_, State<GameState>>,
     mut commands: Commands<'_, '_>,
 ) {
     if *state.get() == GameState::Roll {
         commands.init_resource::<NeedToRoll>();
     }
-    knights.0 += 1;
     state_mut.set(GameState::PlaceRobber);
 }
 
@@ -255,9 +251,14 @@ pub fn year_of_plenty_interaction(
     mut state: ResMut<'_, NextState<GameState>>,
     mut substate_mut: ResMut<'_, NextState<YearOfPlentyState>>,
     substate: Res<'_, State<YearOfPlentyState>>,
+    rules: Res<'_, Rules>,
     mut input: ResMut<'_, Input>,
 ) {
     for (interaction, mut button, mut color, kind) in &mut interaction_query {
+        // nothing left in the bank to take
+        if rules.bank.get(kind.0) == 0 {
+            continue;
+        }
         match *interaction {
             Interaction::Pressed => {
                 *color = PRESSED_BUTTON.into();
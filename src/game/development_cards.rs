@@ -2,6 +2,7 @@ use std::ops::{Add, AddAssign};
 
 use bevy::prelude::*;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::utils::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
 
@@ -13,7 +14,9 @@ use super::{
     turn_ui::DevelopmentCardButton,
 };
 
-#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Debug, Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
 #[require(KatanComponent)]
 pub enum DevelopmentCard {
     Knight,
@@ -22,6 +25,15 @@ pub enum DevelopmentCard {
     RoadBuilding,
     VictoryPoint,
 }
+impl DevelopmentCard {
+    pub const ALL: [Self; 5] = [
+        Self::Knight,
+        Self::Monopoly,
+        Self::YearOfPlenty,
+        Self::RoadBuilding,
+        Self::VictoryPoint,
+    ];
+}
 impl From<DevelopmentCard> for DevelopmentCards {
     fn from(value: DevelopmentCard) -> Self {
         match value {
@@ -83,9 +95,9 @@ impl DevelopmentCards {
         }
     }
 }
-#[derive(Debug, Resource, Clone, Default)]
+#[derive(Debug, Resource, Clone, Default, Hash)]
 pub struct DevelopmentCardsPile(pub Vec<DevelopmentCard>);
-#[derive(Debug, Component, Clone, Copy, Default)]
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[require(KatanComponent)]
 pub struct DevelopmentCards {
     knight: u8,
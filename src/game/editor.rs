@@ -0,0 +1,380 @@
+//! map editor
+//! edits a `MapFile` by clicking on the board, the finished map is saved to disk and can be picked
+//! in the lobby instead of a shuffled board
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_simple_text_input::{TextInput, TextInputTextColor, TextInputTextFont, TextInputValue};
+use bevy_ui_anchor::{AnchorPoint, AnchorUiConfig, AnchoredUiNodes};
+use itertools::Itertools;
+
+use super::{
+    Hexagon, KatanComponent, Number, Port,
+    balance::{self, BoardConstraints},
+    map::MapFile,
+    positions::{self, BuildingPosition, FPosition, Position},
+    resources,
+    setup_game::draw_board,
+};
+use crate::{
+    AppState,
+    common_ui::{self, ButtonInteraction},
+    utils::{BACKGROUND_COLOR, BORDER_COLOR_INACTIVE, NORMAL_BUTTON, TEXT_COLOR},
+};
+
+// the numbers in the order the number tool goes through them
+const NUMBERS: [u8; 10] = [2, 3, 4, 5, 6, 8, 9, 10, 11, 12];
+
+/// the map being edited and where it gets saved
+#[derive(Resource, Debug, Clone)]
+pub struct EditorMap {
+    pub map: MapFile,
+    pub path: String,
+}
+
+// what clicking on the board does
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Tool {
+    #[default]
+    Hexagon,
+    Number,
+    Robber,
+    Port(Port),
+}
+
+pub struct EditorPlugin;
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Tool>()
+            .add_systems(OnEnter(AppState::Editor), setup_editor)
+            .add_systems(OnExit(AppState::Editor), super::cleanup)
+            .add_systems(
+                Update,
+                (
+                    draw_map.run_if(
+                        resource_exists_and_changed::<EditorMap>
+                            .or(resource_exists_and_changed::<Tool>),
+                    ),
+                    show_warnings.run_if(resource_exists_and_changed::<EditorMap>),
+                    common_ui::button_system_with_generic::<ToolButton, ToolButtonState<'_>>,
+                    common_ui::button_system_with_generic::<EditorHex, EditHexState<'_>>,
+                    common_ui::button_system_with_generic::<EditorPort, EditPortState<'_>>,
+                    common_ui::button_system_with_generic::<ExportButton, ExportButtonState<'_, '_>>,
+                    common_ui::button_system_with_generic::<BackButton, BackButtonState<'_>>,
+                )
+                    .run_if(in_state(AppState::Editor).and(resource_exists::<EditorMap>)),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+struct ToolButton(Tool);
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+struct EditorHex(Position);
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+struct EditorPort(BuildingPosition);
+#[derive(Component, Clone, Copy, Debug)]
+struct EditorWarnings;
+#[derive(Component, Clone, Copy, Debug)]
+struct EditorPath;
+#[derive(Component, Clone, Copy, Debug)]
+struct ExportButton;
+#[derive(Component, Clone, Copy, Debug)]
+struct BackButton;
+
+fn button(label: impl Into<String>, marker: impl Bundle) -> impl Bundle {
+    (
+        marker,
+        Button,
+        Node {
+            display: Display::Grid,
+            padding: UiRect::all(Val::Px(5.0)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..Default::default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        BorderColor::all(BORDER_COLOR_INACTIVE),
+        children![(
+            Text::new(label),
+            TextColor(TEXT_COLOR),
+            TextFont {
+                font_size: 25.,
+                ..default()
+            },
+        )],
+    )
+}
+fn port_label(port: Port) -> String {
+    match port {
+        Port::ThreeForOne => "3:1 port".to_string(),
+        Port::TwoForOne(resource) => format!("{resource:?} port"),
+    }
+}
+
+fn setup_editor(mut commands: Commands<'_, '_>, editor: Res<'_, EditorMap>) {
+    let ports = [Port::ThreeForOne]
+        .into_iter()
+        .chain(resources::Resource::ALL.map(Port::TwoForOne));
+    commands
+        .spawn((
+            DespawnOnExit(AppState::Editor),
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.),
+                height: Val::Percent(100.),
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(5.),
+                padding: UiRect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            BackgroundColor(BACKGROUND_COLOR.with_alpha(0.9)),
+        ))
+        .with_children(|panel| {
+            panel.spawn(button("hexes", ToolButton(Tool::Hexagon)));
+            panel.spawn(button("numbers", ToolButton(Tool::Number)));
+            panel.spawn(button("robber", ToolButton(Tool::Robber)));
+            for port in ports {
+                panel.spawn(button(port_label(port), ToolButton(Tool::Port(port))));
+            }
+            panel.spawn((
+                EditorWarnings,
+                Text::default(),
+                TextColor(TEXT_COLOR),
+                TextFont {
+                    font_size: 20.,
+                    ..default()
+                },
+                Node {
+                    max_width: Val::Px(300.),
+                    ..Default::default()
+                },
+            ));
+            panel.spawn((
+                EditorPath,
+                Node {
+                    border: UiRect::all(Val::Px(1.0)),
+                    padding: UiRect::all(Val::Px(5.0)),
+                    ..default()
+                },
+                BorderColor::all(BORDER_COLOR_INACTIVE),
+                BackgroundColor(BACKGROUND_COLOR),
+                TextInput,
+                TextInputValue(editor.path.clone()),
+                TextInputTextFont(TextFont {
+                    font_size: 25.,
+                    ..default()
+                }),
+                TextInputTextColor(TextColor(TEXT_COLOR)),
+            ));
+            panel.spawn(button("save", ExportButton));
+            panel.spawn(button("back", BackButton));
+        });
+}
+
+// intersections a port can go on, with land on one side and water on the other
+fn coast_corners(map: &MapFile) -> Vec<BuildingPosition> {
+    let land = map.board().land().collect_vec();
+    land.iter()
+        .flat_map(Position::building_positions_around)
+        .filter(|corner| {
+            let BuildingPosition::All(p1, p2, p3) = *corner;
+            ![p1, p2, p3].iter().all(|hex| land.contains(hex))
+        })
+        .unique()
+        .collect()
+}
+
+fn spawn_board_button(commands: &mut Commands<'_, '_>, (x, y): (f32, f32), marker: impl Component) {
+    let multiplier = 3.0;
+    commands.spawn((
+        KatanComponent,
+        Transform::from_xyz(x * multiplier * 25.6, y * multiplier * 25.6, 0.0),
+        AnchoredUiNodes::spawn_one((
+            AnchorUiConfig {
+                anchorpoint: AnchorPoint::middle(),
+                offset: None,
+                ..Default::default()
+            },
+            Button,
+            Node {
+                width: Val::VMin(2.0),
+                height: Val::VMin(2.0),
+                ..default()
+            },
+            marker,
+            BorderRadius::MAX,
+            BackgroundColor(NORMAL_BUTTON),
+        )),
+    ));
+}
+
+// the map changes a click at a time, so we just draw everything again
+fn draw_map(
+    mut commands: Commands<'_, '_>,
+    editor: Res<'_, EditorMap>,
+    tool: Res<'_, Tool>,
+    drawn: Query<'_, '_, Entity, With<KatanComponent>>,
+    mut meshes: ResMut<'_, Assets<Mesh>>,
+    mut materials: ResMut<'_, Assets<ColorMaterial>>,
+) {
+    for entity in drawn {
+        commands.entity(entity).try_despawn();
+    }
+    let map = &editor.map;
+    let board = map.board();
+    draw_board(
+        board.hexes.iter().copied(),
+        board.ports.iter().copied(),
+        &mut materials,
+        &mut meshes,
+        &mut commands,
+        3.,
+    );
+    let (x, y) = FPosition::from(map.robber).hex_to_pixel();
+    commands.spawn((
+        KatanComponent,
+        Mesh2d(meshes.add(Circle::new(30.0))),
+        MeshMaterial2d(materials.add(NORMAL_BUTTON.with_alpha(0.9))),
+        Transform::from_xyz(x * 77.0, y * 77., 0.0),
+    ));
+    if let Tool::Port(_) = *tool {
+        for corner in coast_corners(map) {
+            spawn_board_button(
+                &mut commands,
+                corner.positon_to_pixel_coordinates(),
+                EditorPort(corner),
+            );
+        }
+    } else {
+        for position in positions::generate_postions(map.size as i8) {
+            spawn_board_button(
+                &mut commands,
+                FPosition::from(position).hex_to_pixel(),
+                EditorHex(position),
+            );
+        }
+    }
+}
+
+fn show_warnings(
+    editor: Res<'_, EditorMap>,
+    mut text: Single<'_, '_, &mut Text, With<EditorWarnings>>,
+) {
+    let warnings = editor.map.warnings();
+    let land = editor
+        .map
+        .hexes
+        .iter()
+        .copied()
+        .filter(|(_, hex, _)| !matches!(hex, Hexagon::Water | Hexagon::Port | Hexagon::Empty))
+        .collect_vec();
+    // the score is worth seeing even when the map isn't finished yet
+    let fairness = balance::fairness(&land, &BoardConstraints::default());
+    text.0 = if warnings.is_empty() {
+        format!("ready to play\n{fairness}")
+    } else {
+        format!("{}\n{fairness}", warnings.join("\n"))
+    };
+}
+
+#[derive(SystemParam)]
+struct ToolButtonState<'w> {
+    tool: ResMut<'w, Tool>,
+}
+impl ButtonInteraction<ToolButton> for ToolButtonState<'_> {
+    fn interact(&mut self, ToolButton(tool): &ToolButton) {
+        *self.tool = *tool;
+    }
+}
+
+#[derive(SystemParam)]
+struct EditHexState<'w> {
+    editor: ResMut<'w, EditorMap>,
+    tool: Res<'w, Tool>,
+}
+impl ButtonInteraction<EditorHex> for EditHexState<'_> {
+    fn interact(&mut self, EditorHex(position): &EditorHex) {
+        let map = &mut self.editor.map;
+        if *self.tool == Tool::Robber {
+            map.robber = *position;
+            return;
+        }
+        let Some(index) = map.hexes.iter().position(|(hex, _, _)| hex == position) else {
+            // unlisted hexes are water, so the next one is the first resource
+            map.hexes.push((*position, Hexagon::Wood, Number::None));
+            return;
+        };
+        let (_, hex, number) = &mut map.hexes[index];
+        match *self.tool {
+            Tool::Hexagon => {
+                // wood, brick, sheep, wheat, ore, desert, water, gold and around again
+                *hex = match *hex {
+                    Hexagon::Water => Hexagon::Gold,
+                    Hexagon::Gold => Hexagon::Wood,
+                    hex => Hexagon::from((hex as u8 + 1) % 7),
+                };
+                if !hex.produces() {
+                    *number = Number::None;
+                }
+            }
+            Tool::Number if hex.produces() => {
+                let next = NUMBERS
+                    .iter()
+                    .position(|n| Number::Number(*n) == *number)
+                    .map_or(0, |i| (i + 1) % NUMBERS.len());
+                *number = Number::Number(NUMBERS[next]);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct EditPortState<'w> {
+    editor: ResMut<'w, EditorMap>,
+    tool: Res<'w, Tool>,
+}
+impl ButtonInteraction<EditorPort> for EditPortState<'_> {
+    fn interact(&mut self, EditorPort(corner): &EditorPort) {
+        let Tool::Port(port) = *self.tool else {
+            return;
+        };
+        let ports = &mut self.editor.map.ports;
+        // clicking the same port again takes it away
+        let same = ports.contains(&(*corner, port));
+        ports.retain(|(position, _)| position != corner);
+        if !same {
+            ports.push((*corner, port));
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct ExportButtonState<'w, 's> {
+    editor: Res<'w, EditorMap>,
+    path: Single<'w, 's, &'static TextInputValue, With<EditorPath>>,
+}
+impl ButtonInteraction<ExportButton> for ExportButtonState<'_, '_> {
+    fn interact(&mut self, _: &ExportButton) {
+        let path = self.path.0.trim();
+        // unfinished maps can still be saved, they just can't be played yet
+        if let Err(error) = self.editor.map.validate() {
+            warn!("{path} can't be played yet: {error}");
+        }
+        match self.editor.map.save(path) {
+            Ok(()) => info!("saved map to {path}"),
+            Err(error) => error!("could not save map to {path}: {error}"),
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct BackButtonState<'w> {
+    app_state: ResMut<'w, NextState<AppState>>,
+}
+impl ButtonInteraction<BackButton> for BackButtonState<'_> {
+    fn interact(&mut self, _: &BackButton) {
+        self.app_state.set(AppState::Menu);
+    }
+}
@@ -0,0 +1,174 @@
+//! house rules, the numbers in the rules that players like to change
+//! picked in the lobby as a comma separated list like `vp=12, discard=9, friendly robber`, players
+//! only end up in the same room if they picked the same ones
+use std::{fmt, str::FromStr};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use super::development_cards::{DevelopmentCard, DevelopmentCards};
+use crate::utils::stable_hash;
+
+// players with this many (visible) victory points or less are safe from a friendly robber
+pub const FRIENDLY_ROBBER_POINTS: u8 = 2;
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GameRules {
+    /// victory points needed to win, `None` for the usual 10 (13 with cities & knights)
+    pub victory_points: Option<u8>,
+    /// when a 7 is rolled everyone with more resources then this discards half of them
+    pub discard_limit: u8,
+    /// the shortest road that can be the longest road
+    pub longest_road: u8,
+    /// the fewest knights that can be the largest army
+    pub largest_army: u8,
+    /// how many of each resource the bank starts with, `None` for the usual 19 (24 for 5-6
+    /// players)
+    pub bank: Option<u8>,
+    /// the development cards in the pile, `None` for the usual pile (with the extension cards for
+    /// 5-6 players)
+    pub development_cards: Option<DevelopmentCards>,
+    /// the robber can't be placed next to players with `FRIENDLY_ROBBER_POINTS` or less
+    pub friendly_robber: bool,
+}
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            victory_points: None,
+            discard_limit: 7,
+            longest_road: 5,
+            largest_army: 3,
+            bank: None,
+            development_cards: None,
+            friendly_robber: false,
+        }
+    }
+}
+impl GameRules {
+    // none of the numbers make sense as 0, the rules use one less then longest road and largest
+    // army
+    fn check(self) -> Result<Self, GameRulesError> {
+        let numbers = [
+            ("vp", self.victory_points),
+            ("discard", Some(self.discard_limit)),
+            ("road", Some(self.longest_road)),
+            ("army", Some(self.largest_army)),
+            ("bank", self.bank),
+        ];
+        match numbers.into_iter().find(|(_, number)| *number == Some(0)) {
+            Some((name, _)) => Err(GameRulesError::Value(name.to_string(), "0".to_string())),
+            None => Ok(self),
+        }
+    }
+
+    /// identifies the rules, so only players with the same rules play together
+    pub fn id(&self) -> u64 {
+        stable_hash(self)
+    }
+}
+
+#[derive(Debug)]
+pub enum GameRulesError {
+    Unknown(String),
+    Value(String, String),
+}
+impl fmt::Display for GameRulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(rule) => write!(
+                f,
+                "unknown rule {rule}, the rules are vp, discard, road, army, bank, deck and \
+                 friendly robber"
+            ),
+            Self::Value(rule, value) => write!(f, "{value} is not a valid value for {rule}"),
+        }
+    }
+}
+impl std::error::Error for GameRulesError {}
+
+// saves and replays skip parsing, so they are checked the same way after deserializing
+impl<'de> Deserialize<'de> for GameRules {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        UncheckedGameRules::deserialize(deserializer)?
+            .check()
+            .map_err(de::Error::custom)
+    }
+}
+
+// the same fields as `GameRules`, deserializing it without checking it
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(remote = "GameRules", default)]
+struct UncheckedGameRules {
+    victory_points: Option<u8>,
+    discard_limit: u8,
+    longest_road: u8,
+    largest_army: u8,
+    bank: Option<u8>,
+    development_cards: Option<DevelopmentCards>,
+    friendly_robber: bool,
+}
+
+impl FromStr for GameRules {
+    type Err = GameRulesError;
+
+    // an empty list is the usual rules
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Self::default();
+        for rule in s.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let (name, value) = rule
+                .split_once('=')
+                .map_or((rule, ""), |(name, value)| (name.trim(), value.trim()));
+            let invalid = || GameRulesError::Value(name.to_string(), value.to_string());
+            let number = || value.parse::<u8>().map_err(|_| invalid());
+            match name {
+                "vp" => rules.victory_points = Some(number()?),
+                "discard" => rules.discard_limit = number()?,
+                "road" => rules.longest_road = number()?,
+                "army" => rules.largest_army = number()?,
+                "bank" => rules.bank = Some(number()?),
+                // knight/monopoly/year of plenty/road building/victory point
+                "deck" => {
+                    let counts = value
+                        .split('/')
+                        .map(|count| count.trim().parse::<u8>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid())?;
+                    if counts.len() != DevelopmentCard::ALL.len() {
+                        return Err(invalid());
+                    }
+                    let mut development_cards = DevelopmentCards::new_player();
+                    for (card, count) in DevelopmentCard::ALL.into_iter().zip(counts) {
+                        *development_cards.get_mut(card) = count;
+                    }
+                    rules.development_cards = Some(development_cards);
+                }
+                "friendly robber" | "friendly" => rules.friendly_robber = true,
+                _ => return Err(GameRulesError::Unknown(name.to_string())),
+            }
+        }
+        rules.check()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_is_not_a_rule() {
+        assert!("road=0".parse::<GameRules>().is_err());
+        assert!("vp=12, army=0".parse::<GameRules>().is_err());
+        assert_eq!("road=7".parse::<GameRules>().unwrap().longest_road, 7);
+    }
+
+    // a save or replay doesn't go through parsing
+    #[test]
+    fn saved_rules_are_checked() {
+        let rules = serde_json::from_str::<GameRules>(r#"{"discard_limit":9}"#).unwrap();
+        assert_eq!(rules.discard_limit, 9);
+        assert_eq!(rules.longest_road, GameRules::default().longest_road);
+        assert!(serde_json::from_str::<GameRules>(r#"{"longest_road":0}"#).is_err());
+        assert!(serde_json::from_str::<GameRules>(r#"{"bank":0}"#).is_err());
+    }
+}
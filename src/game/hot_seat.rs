@@ -0,0 +1,176 @@
+//! hot seat, a few players sharing one window
+//! everyone is a local player of a sync test session, and the ui (`LocalPlayer`) follows whoever
+//! has to do something, with a screen in between so you don't see the hand of the player before you
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{
+    GameState, KatanComponent, LocalPlayer, LocalPlayerHandle, PlayerEntities, PlayerHandle, Rules,
+    bot::Bots,
+    development_card_actions::{RoadBuildingState, YearOfPlentyState},
+    development_cards::{DevelopmentCard, DevelopmentCards},
+    resources::Resources,
+    rules::{self, Phase},
+};
+use crate::{
+    AppState,
+    common_ui::{self, ButtonInteraction},
+    utils::{BACKGROUND_COLOR, BORDER_COLOR_INACTIVE, NORMAL_BUTTON, TEXT_COLOR},
+};
+
+/// while this exists the game is played in hot seat mode
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct HotSeat;
+
+pub struct HotSeatPlugin;
+impl Plugin for HotSeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                pass_device.run_if(resource_changed::<Rules>),
+                common_ui::button_system_with_generic::<PassDeviceButton, PassDeviceState<'_, '_>>,
+            )
+                .run_if(in_state(AppState::InGame).and(resource_exists::<HotSeat>)),
+        );
+    }
+}
+
+// who should have the device, players that have to discard (or pick from gold fields) go first as
+// the current player has to wait for them anyway
+// the bots don't need the device, so when its their turn the last player keeps it
+fn holder(rules: &rules::GameState, bots: &Bots) -> Option<PlayerHandle> {
+    match &rules.phase {
+        Phase::Discard(pending) | Phase::Gold(pending) => {
+            if let Some((handle, _)) = pending.iter().find(|(handle, _)| !bots.contains(*handle)) {
+                return Some(*handle);
+            }
+        }
+        // the winner has to say so
+        Phase::Finished(winner) => return Some(*winner).filter(|handle| !bots.contains(*handle)),
+        _ => {}
+    }
+    rules
+        .active_player()
+        .filter(|handle| !bots.contains(*handle))
+}
+
+#[derive(SystemParam)]
+pub struct UiStates<'w> {
+    game_state: ResMut<'w, NextState<GameState>>,
+    road_building_state: ResMut<'w, NextState<RoadBuildingState>>,
+    year_of_plenty_state: ResMut<'w, NextState<YearOfPlentyState>>,
+}
+
+fn pass_device(
+    mut commands: Commands<'_, '_>,
+    rules: Res<'_, Rules>,
+    bots: Res<'_, Bots>,
+    players: PlayerEntities<'_, '_>,
+    mut local_player: ResMut<'_, LocalPlayer>,
+    mut local_player_handle: ResMut<'_, LocalPlayerHandle>,
+    mut ui_states: UiStates<'_>,
+    mut hands: Query<'_, '_, (&mut Resources, &mut DevelopmentCards)>,
+    shown_cards: Query<'_, '_, Entity, (With<DevelopmentCard>, With<Node>)>,
+    screens: Query<'_, '_, Entity, With<PassDevice>>,
+) {
+    let Some(holder) = holder(&rules, &bots) else {
+        return;
+    };
+    if holder == local_player.0.handle {
+        return;
+    }
+    let Some(player) = players.get(holder) else {
+        return;
+    };
+    local_player.0 = player;
+    local_player_handle.0 = holder.0;
+    // the inputs already moved the ui along for the previous player, so we start from the rules
+    super::set_state_from_rules(
+        &rules,
+        holder,
+        &mut ui_states.game_state,
+        &mut ui_states.road_building_state,
+        &mut ui_states.year_of_plenty_state,
+    );
+
+    // the hand is only updated when it changes, so we make it look changed to show the new players
+    // hand
+    for card in shown_cards {
+        commands.entity(card).despawn();
+    }
+    if let Ok((mut resources, mut development_cards)) = hands.get_mut(player.entity) {
+        resources.set_changed();
+        development_cards.set_changed();
+    }
+
+    for screen in screens {
+        commands.entity(screen).despawn();
+    }
+    commands.spawn((
+        PassDevice,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            display: Display::Grid,
+            justify_content: JustifyContent::Center,
+            align_content: AlignContent::Center,
+            row_gap: Val::Percent(2.),
+            ..Default::default()
+        },
+        // above everything else, so you can't see (or click) anything of the previous player
+        GlobalZIndex(1),
+        BackgroundColor(BACKGROUND_COLOR),
+        DespawnOnExit(AppState::InGame),
+        children![
+            (
+                Text::new(format!("pass to {:?}", player.color)),
+                TextColor(player.color.to_bevy_color()),
+                TextFont {
+                    font_size: 34.,
+                    ..default()
+                }
+            ),
+            (
+                Node {
+                    display: Display::Grid,
+                    padding: UiRect::all(Val::Percent(2.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    justify_self: JustifySelf::Center,
+                    ..Default::default()
+                },
+                children![
+                    TextColor(TEXT_COLOR),
+                    Text::new("ready"),
+                    TextFont {
+                        font_size: 34.,
+                        ..default()
+                    },
+                ],
+                Button,
+                PassDeviceButton,
+                BackgroundColor(NORMAL_BUTTON),
+                BorderColor::all(BORDER_COLOR_INACTIVE),
+            ),
+        ],
+    ));
+}
+
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[require(KatanComponent)]
+pub struct PassDevice;
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[require(KatanComponent)]
+pub struct PassDeviceButton;
+#[derive(SystemParam)]
+pub struct PassDeviceState<'w, 's> {
+    commands: Commands<'w, 's>,
+    screens: Query<'w, 's, Entity, With<PassDevice>>,
+}
+impl ButtonInteraction<PassDeviceButton> for PassDeviceState<'_, '_> {
+    fn interact(&mut self, _: &PassDeviceButton) {
+        for screen in &self.screens {
+            self.commands.entity(screen).despawn();
+        }
+    }
+}
@@ -1,34 +1,24 @@
 use bevy::prelude::*;
+use bevy_ggrs::GgrsSchedule;
 
-use super::{KatanComponent, Knights, VictoryPoints};
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Resource)]
-struct LargetArmy(pub u8, pub Entity);
+use super::{KatanComponent, PlayerHandle, Rules, sync_rules};
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Component)]
 #[require(KatanComponent)]
 pub struct LargetArmyRef;
 
-fn update_larget_army(
-    players: Query<'_, '_, (Entity, &Knights), Changed<Knights>>,
-    mut points: Query<'_, '_, &mut VictoryPoints>,
-    mut current_largest_army: ResMut<'_, LargetArmy>,
+// who has largest army is decided by the rules, we just move the marker around (as part of the
+// rollback frame, so a rollback puts it back too)
+pub fn update_larget_army(
+    players: Query<'_, '_, (Entity, &PlayerHandle, Has<LargetArmyRef>)>,
+    rules: Res<'_, Rules>,
     mut commands: Commands<'_, '_>,
 ) {
-    for (entity, knights) in players {
-        if knights.0 > current_largest_army.0 {
-            if current_largest_army.1 != Entity::PLACEHOLDER {
-                commands
-                    .entity(current_largest_army.1)
-                    .remove::<LargetArmyRef>();
-                if let Ok(mut points) = points.get_mut(current_largest_army.1) {
-                    points.actual -= 2;
-                }
-            }
-            current_largest_army.0 = knights.0;
-            current_largest_army.1 = entity;
+    for (entity, handle, has_larget_army) in players {
+        let should_have_larget_army = rules.largest_army.0 == Some(*handle);
+        if should_have_larget_army && !has_larget_army {
             commands.entity(entity).insert(LargetArmyRef);
-            if let Ok(mut points) = points.get_mut(entity) {
-                points.actual += 2;
-            }
+        } else if !should_have_larget_army && has_larget_army {
+            commands.entity(entity).remove::<LargetArmyRef>();
         }
     }
 }
@@ -36,8 +26,11 @@ fn update_larget_army(
 pub struct LargestArmyPlugin;
 impl Plugin for LargestArmyPlugin {
     fn build(&self, app: &mut App) {
-        // start at two so when there is 3 it will be updated
-        app.insert_resource(LargetArmy(2, Entity::PLACEHOLDER))
-            .add_systems(Update, update_larget_army);
+        app.add_systems(
+            GgrsSchedule,
+            update_larget_army
+                .after(sync_rules)
+                .run_if(resource_exists_and_changed::<Rules>),
+        );
     }
 }
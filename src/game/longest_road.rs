@@ -1,16 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
 
 use super::{
-    BoardSize, Building, KatanComponent, VictoryPoints,
-    colors::{CatanColor, CurrentColor},
+    KatanComponent, PlayerHandle, Rules,
     positions::{BuildingPosition, RoadPosition},
-    roads::{self, RoadQuery},
+    sync_rules,
 };
-use bevy::{platform::collections::HashSet, prelude::*};
+use bevy::prelude::*;
+use bevy_ggrs::GgrsSchedule;
 use itertools::Itertools;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Resource)]
-struct LongestRoad(Entity, u8);
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Component)]
 #[require(KatanComponent)]
 pub struct LongestRoadRef;
@@ -19,214 +19,280 @@ pub struct LongestRoadPlugin;
 #[derive(Clone, PartialEq, Eq, Debug, Component)]
 #[require(KatanComponent)]
 // TODO: maybe precompute len
-pub struct PlayerLongestRoad(pub HashSet<RoadPosition>);
+pub struct PlayerLongestRoad(pub bevy::platform::collections::HashSet<RoadPosition>);
 impl Plugin for LongestRoadPlugin {
     fn build(&self, app: &mut App) {
-        // start at 2 so when someone gets 3 it will be updated
-        app.insert_resource(LongestRoad(Entity::PLACEHOLDER, 4))
-            .add_systems(Update, longest_road_road_added)
-            .add_systems(Update, longest_road_town_added);
+        app.add_systems(
+            GgrsSchedule,
+            update_longest_road_ref
+                .after(sync_rules)
+                .run_if(resource_exists_and_changed::<Rules>),
+        );
     }
 }
 
-// whenever a road is added the players longest road is recalulated
-fn longest_road_road_added(
-    road_q: Query<'_, '_, RoadQuery>,
-    road_q_changed: Query<'_, '_, RoadQuery, Changed<RoadPosition>>,
-    building_q: Query<'_, '_, (&'_ Building, &CatanColor, &'_ BuildingPosition)>,
-    mut player_q: Query<'_, '_, (Entity, &mut VictoryPoints, &mut PlayerLongestRoad)>,
-    mut current: ResMut<'_, LongestRoad>,
-    mut commmands: Commands<'_, '_>,
-    color: Res<'_, CurrentColor>,
-    size_r: Res<'_, BoardSize>,
+// who has longest road is decided by the rules, we just move the marker around (in the rollback
+// frame like the largest army)
+pub fn update_longest_road_ref(
+    players: Query<'_, '_, (Entity, &PlayerHandle, Has<LongestRoadRef>)>,
+    rules: Res<'_, Rules>,
+    mut commands: Commands<'_, '_>,
 ) {
-    // we could shortcut here if its current player who has longest road, but then total count
-    // wouldn't be accurate
-    if road_q_changed.iter().count() == 0 {
-        return;
-    }
-    println!(
-        "longest road checking road added {:?}",
-        road_q_changed.iter().map(|m| m.2).collect_vec()
-    );
-
-    let roads_by_color = road_q.iter().filter(|q| *q.1 == color.0.color);
-    let longest_road = longest_road(
-        roads_by_color.collect_vec(),
-        building_q
-            .into_iter()
-            .filter(|b| *b.1 != color.0.color)
-            .map(|b| *b.2)
-            .collect_vec(),
-        size_r.0,
-    );
-
-    if let Some(new) = longest_road {
-        println!("new longest road {new:?} {}", new.len());
-        if let Ok(mut player) = player_q.get_mut(color.0.entity) {
-            let len = new.len();
-            player.2.0 = new;
-            if len as u8 > current.1 {
-                if current.0 != color.0.entity {
-                    commmands.entity(player.0).insert(LongestRoadRef);
-                    player.1.actual += 2;
-                    if let Ok(mut player) = player_q.get_mut(current.0) {
-                        commmands.entity(player.0).remove::<LongestRoadRef>();
-                        player.1.actual -= 2;
-                    }
-                }
-                *current = LongestRoad(color.0.entity, len as u8);
-            }
+    for (entity, handle, has_longest_road) in players {
+        let should_have_longest_road = rules.longest_road.0 == Some(*handle);
+        if should_have_longest_road && !has_longest_road {
+            commands.entity(entity).insert(LongestRoadRef);
+        } else if !should_have_longest_road && has_longest_road {
+            commands.entity(entity).remove::<LongestRoadRef>();
         }
     }
 }
 
-fn longest_road(
-    roads: Vec<roads::RoadQueryItem<'_, '_>>,
-    buildings: Vec<BuildingPosition>,
-    size_r: u8,
-) -> Option<HashSet<RoadPosition>> {
-    // maybe just parralize it
-    // skip anyone how has road count equal to current longest road (if check_cut_off)
-    // always check if less roads then 3
-    // road cannot be used twice (so no loops in actual longest road)
-    if roads.len() <= 4 {
-        None
-    } else {
-        // TODO: compute this when adding roads
-        let road_matrix = roads
-            .iter()
-            .tuple_combinations()
-            .filter(|(r1, r2)| {
-                r1.2.intersect(r2.2, Some(size_r))
-                    .is_some_and(|b| !buildings.clone().contains(&b))
-            })
-            .fold(HashMap::new(), |mut matrix, (r1, r2)| {
-                matrix.entry(*r1.2).or_insert(HashSet::new()).insert(*r2.2);
-                matrix.entry(*r2.2).or_insert(HashSet::new()).insert(*r1.2);
-                matrix
+/// a players roads and ships as a graph between intersections, kept up to date as pieces are placed
+/// so finding the longest road doesn't have to work out which roads connect every time
+/// ships count as well (the longest trade route), but a road only continues into a ship through one
+/// of the players own buildings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoadGraph {
+    edges: Vec<Edge>,
+    own_buildings: BTreeSet<BuildingPosition>,
+    // other players buildings split a road
+    other_buildings: BTreeSet<BuildingPosition>,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct Edge {
+    road: RoadPosition,
+    ship: bool,
+    ends: [BuildingPosition; 2],
+    // the other edges at each end, if a route can go on to them depends on the buildings so that
+    // is only checked when searching
+    next: [Vec<usize>; 2],
+}
+impl RoadGraph {
+    pub fn new(
+        roads: &[RoadPosition],
+        ships: &[RoadPosition],
+        own_buildings: &[BuildingPosition],
+        other_buildings: &[BuildingPosition],
+    ) -> Self {
+        let mut graph = Self {
+            edges: vec![],
+            own_buildings: own_buildings.iter().copied().collect(),
+            other_buildings: other_buildings.iter().copied().collect(),
+        };
+        for road in roads {
+            graph.add_road(*road, false);
+        }
+        for ship in ships {
+            graph.add_road(*ship, true);
+        }
+        graph
+    }
+
+    pub fn add_road(&mut self, road: RoadPosition, ship: bool) {
+        if let [start, end] = road.ends()[..] {
+            let index = self.edges.len();
+            let ends = [start, end];
+            let next = ends.map(|at| {
+                self.edges
+                    .iter_mut()
+                    .enumerate()
+                    .filter_map(|(other, edge)| {
+                        let side = edge.ends.iter().position(|end| *end == at)?;
+                        edge.next[side].push(index);
+                        Some(other)
+                    })
+                    .collect()
             });
-        // TODO: for each road maybe also count how many roads connected to it (so we can do some
-        // cut offs/min maxing
-        road_matrix
-            .iter()
-            .filter_map(|(road, neighbors)| {
-                find_path(HashSet::from([*road]), neighbors, &road_matrix)
-            })
-            .max_by_key(bevy::platform::collections::HashSet::len)
+            self.edges.push(Edge {
+                road,
+                ship,
+                ends,
+                next,
+            });
+        }
     }
-}
 
-fn find_path(
-    path: HashSet<RoadPosition>,
-    neighbors: &HashSet<RoadPosition>,
-    road_matrix: &HashMap<RoadPosition, HashSet<RoadPosition>>,
-) -> Option<HashSet<RoadPosition>> {
-    neighbors
-        .iter()
-        .filter_map(|neighbor| {
-            let mut path = path.clone();
-            if path.insert(*neighbor) {
-                let neighbors = road_matrix.get(neighbor)?;
-                find_path(path, neighbors, road_matrix)
-            } else {
-                // no double crossing
-                Some(path)
+    // only ships are ever moved, so this doesn't need to be quick
+    pub fn remove_road(&mut self, road: RoadPosition) {
+        // the edges after it move down, so everything is connected up again
+        for edge in std::mem::take(&mut self.edges) {
+            if edge.road != road {
+                self.add_road(edge.road, edge.ship);
             }
-        })
-        .max_by_key(bevy::platform::collections::HashSet::len)
-}
-fn longest_road_town_added(
-    road_q: Query<'_, '_, (&ChildOf, RoadQuery)>,
-    building_q_changed: Query<
-        '_,
-        '_,
-        (&'_ Building, &CatanColor, &'_ BuildingPosition),
-        Changed<BuildingPosition>,
-    >,
-    building_q: Query<'_, '_, (&'_ Building, &CatanColor, &'_ BuildingPosition)>,
-    mut player_q: Query<
-        '_,
-        '_,
+        }
+    }
+
+    pub fn add_building(&mut self, building: BuildingPosition, own: bool) {
+        if own {
+            self.own_buildings.insert(building);
+        } else {
+            self.other_buildings.insert(building);
+        }
+    }
+
+    /// whether any of the roads or ships ends at the building
+    pub fn touches(&self, building: BuildingPosition) -> bool {
+        self.edges.iter().any(|edge| edge.ends.contains(&building))
+    }
+
+    /// how many roads and ships and buildings the graph knows about, to tell if its out of date
+    pub fn pieces(&self) -> (usize, usize) {
         (
-            Entity,
-            &mut VictoryPoints,
-            &mut PlayerLongestRoad,
-            &CatanColor,
-        ),
-    >,
-    mut current: ResMut<'_, LongestRoad>,
-    mut commmands: Commands<'_, '_>,
-    size_r: Res<'_, BoardSize>,
-) {
-    if current.0 == Entity::PLACEHOLDER || building_q_changed.iter().count() == 0 {
-        return;
-    }
-    println!("longest road checking town added");
-    if let Some(mut current_interupted) = player_q.iter_mut().find(|player| {
-        player.2.0.iter().tuple_combinations().any(|(r1, r2)| {
-            r1.intersect(r2, Some(size_r.0)).is_some_and(|b| {
-                building_q_changed
-                    .iter()
-                    .filter(|b| b.1 != player.3)
-                    .map(|b| b.2)
-                    // reason we don't "not" this is because we are looking roads intersections
-                    // that got interupted
-                    .contains(&b)
+            self.edges.len(),
+            self.own_buildings.len() + self.other_buildings.len(),
+        )
+    }
+
+    // whether a route can go from one edge to another through an intersection
+    fn continues(&self, from: &Edge, to: &Edge, through: BuildingPosition) -> bool {
+        !self.other_buildings.contains(&through)
+            && (from.ship == to.ship || self.own_buildings.contains(&through))
+    }
+
+    /// the longest route without using a road twice, as a depth first search from both ends of
+    /// every edge that remembers where it has already been (the edge, which end and the edges used
+    /// so far), so a loop is only searched once even though it can be gone around either way
+    /// the longest trail is NP-hard in general and this is still exponential in the number of
+    /// edges, but a player has at most 15 roads and 15 ships and only three edges meet at an
+    /// intersection, so a route never has more then two ways to go on
+    pub fn longest(&self) -> BTreeSet<RoadPosition> {
+        let mut memo = HashMap::new();
+        let longest = (0..self.edges.len())
+            .cartesian_product(0..2)
+            .map(|(edge, end)| self.longest_from(edge, end, 1 << edge, &mut memo))
+            .max_by_key(|used| used.count_ones())
+            .unwrap_or_default();
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| longest & (1 << index) != 0)
+            .map(|(_, edge)| edge.road)
+            .collect()
+    }
+
+    // the edges used by the longest route that got to this `end` of `edge` having used `used`
+    // a player has at most 15 roads and 15 ships so the used edges fit in a u128
+    fn longest_from(
+        &self,
+        edge: usize,
+        end: usize,
+        used: u128,
+        memo: &mut HashMap<(usize, usize, u128), u128>,
+    ) -> u128 {
+        if let Some(longest) = memo.get(&(edge, end, used)) {
+            return *longest;
+        }
+        let from = &self.edges[edge];
+        let through = from.ends[end];
+        let longest = from.next[end]
+            .iter()
+            .filter(|next| used & (1 << **next) == 0)
+            .filter(|next| self.continues(from, &self.edges[**next], through))
+            .map(|next| {
+                // it goes out the other end of the next edge
+                let next_end = usize::from(self.edges[*next].ends[0] == through);
+                self.longest_from(*next, next_end, used | (1 << next), memo)
             })
-        })
-    }) {
-        // updated interupted players longest road count
-        let roads_by_color = road_q.iter().filter(|q| q.0.parent() == current.0);
-        let new = longest_road(
-            roads_by_color.into_iter().unzip::<_, _, Vec<_>, Vec<_>>().1,
-            building_q
-                .into_iter()
-                .filter(|b| b.1 != current_interupted.3)
-                .map(|b| *b.2)
-                .collect_vec(),
-            size_r.0,
+            .max_by_key(|used| used.count_ones())
+            .unwrap_or(used);
+        memo.insert((edge, end, used), longest);
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::positions::Position;
+
+    const CENTER: Position = Position { q: 0, r: 0, s: 0 };
+    const RIGHT: Position = Position::DIRECTION_VECTORS[0];
+    const DOWN: Position = Position::DIRECTION_VECTORS[5];
+
+    fn road(p1: Position, p2: Position) -> RoadPosition {
+        RoadPosition::new(p1, p2, None).unwrap()
+    }
+    // the six roads around a hex, each one continues the one before it
+    fn ring(hex: Position) -> Vec<RoadPosition> {
+        Position::DIRECTION_VECTORS
+            .into_iter()
+            .map(|direction| road(hex, hex + direction))
+            .collect()
+    }
+    // the roads around all the hexes, the ones between two of the hexes only once
+    fn rings(hexes: &[Position]) -> Vec<RoadPosition> {
+        hexes
+            .iter()
+            .flat_map(|hex| ring(*hex))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+    fn longest(roads: &[RoadPosition]) -> BTreeSet<RoadPosition> {
+        RoadGraph::new(roads, &[], &[], &[]).longest()
+    }
+
+    #[test]
+    fn a_loop_is_all_of_it() {
+        let roads = ring(CENTER);
+        let mut graph = RoadGraph::new(&roads, &[], &[], &[]);
+        assert_eq!(graph.longest().len(), 6);
+        // the edges after it are still connected
+        graph.remove_road(roads[2]);
+        assert_eq!(graph.longest().len(), 5);
+    }
+
+    // three arms from the intersection between the center and the hexes to the right and below
+    #[test]
+    fn a_fork_takes_the_two_longest_arms() {
+        let around = ring(CENTER);
+        let arms = [
+            vec![around[0], around[1], around[2]],
+            vec![around[5], around[4]],
+            vec![road(RIGHT, DOWN)],
+        ];
+        let roads = arms.concat();
+        assert_eq!(
+            longest(&roads),
+            arms[..2].iter().flatten().copied().collect()
         );
-        if let Some(new) = new {
-            // update current holders longest road count
-            current_interupted.2.0 = new;
-        }
-        // if interuppted is the current holder than update longest road
-        if current_interupted.0 == current.0 {
-            // possible cases:
-            // Here we have to distinguish between three cases:
-
-            // If the player who up to this point had the Longest Road still meets the requirements for the Longest Road (either alone or together with another player), he keeps the card.
-            //
-            // If another player now meets the requirements for the Longest Road, he receives the card.
-            //
-            // If none of the players - or more than one player - meets the requirements for the Longest Road, none of the players receives the card.
-
-            // we deducat the points here even if the current holder still holds it to get
-            // around mutablitity issues
-            current_interupted.1.actual -= 2;
-            let mut new = player_q
-                .iter_mut()
-                .filter(|p| p.2.0.len() >= 5)
-                .max_set_by_key(|p| p.2.0.len());
-
-            if let Some(mut new_current) = new.pop_if(|(e, _, _, _)| *e == current.0) {
-                // same player but less roads (ussually)
-                current.1 = new_current.2.0.len() as u8;
-
-                new_current.1.actual += 2;
-            } else {
-                commmands.entity(current.0).remove::<LongestRoadRef>();
-                if let [new_player] = &mut new[..] {
-                    // no tie for second
-                    *current = LongestRoad(new_player.0, new_player.2.0.len() as u8);
-                    commmands.entity(new_player.0).insert(LongestRoadRef);
-                    new_player.1.actual += 2;
-                } else {
-                    // tie for longest road (not including current longest road holder)
-                    *current = LongestRoad(Entity::PLACEHOLDER, 4);
-                }
-            }
-        }
+    }
+
+    // the road between two loops is used by both of them
+    #[test]
+    fn a_figure_eight_is_all_of_it() {
+        let roads = rings(&[CENTER, RIGHT]);
+        assert_eq!(roads.len(), 11);
+        assert_eq!(longest(&roads).len(), 11);
+    }
+
+    #[test]
+    fn ships_continue_roads_only_through_own_buildings() {
+        let around = ring(CENTER);
+        let (roads, ships) = (&around[..2], &around[2..4]);
+        let junction = around[1].intersect(&around[2], None).unwrap();
+        assert_eq!(RoadGraph::new(roads, ships, &[], &[]).longest().len(), 2);
+        assert_eq!(
+            RoadGraph::new(roads, ships, &[junction], &[])
+                .longest()
+                .len(),
+            4
+        );
+        assert_eq!(
+            RoadGraph::new(roads, ships, &[], &[junction])
+                .longest()
+                .len(),
+            2
+        );
+    }
+
+    // every road a player has, as three loops that meet in the middle
+    #[test]
+    fn all_the_roads() {
+        let roads = rings(&[CENTER, RIGHT, DOWN]);
+        assert_eq!(roads.len(), 15);
+        // four intersections have three roads, so one of them can't be used
+        assert_eq!(longest(&roads).len(), 14);
     }
 }
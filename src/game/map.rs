@@ -0,0 +1,390 @@
+//! custom maps
+//! a map is the board of the rules (hexes, numbers and ports) and where the robber starts, so
+//! instead of shuffling the board from the seed every peer plays on the same hand made board
+use std::{fmt, path::Path};
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BoardSize, Hexagon, Number, Port,
+    positions::{self, BuildingPosition, Position},
+    resources::Resource,
+    rules, setup_game,
+};
+use crate::utils::stable_hash;
+
+/// bump this whenever the format changes
+pub const MAP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MapFile {
+    pub version: u32,
+    /// how many rings of land the board has, only water can go on the ring around that
+    pub size: u8,
+    // the edge of the board doesn't have to be listed, every hex that isn't is left empty like
+    // around a generated board (water makes it a seafarers map)
+    pub hexes: Vec<(Position, Hexagon, Number)>,
+    // like the generated boards each port is listed once for each of its two intersections
+    pub ports: Vec<(BuildingPosition, Port)>,
+    pub robber: Position,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+    Size(u8),
+    OffBoard(Position),
+    Duplicate(Position),
+    Number(Position),
+    Port(BuildingPosition),
+    Robber(Position),
+}
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Format(error) => write!(f, "not a valid map: {error}"),
+            Self::Version(version) => write!(
+                f,
+                "map is from version {version}, but only version {MAP_VERSION} is supported"
+            ),
+            Self::Size(size) => write!(
+                f,
+                "map size {size} is not between {} and {}",
+                BoardSize::MIN,
+                BoardSize::MAX
+            ),
+            Self::OffBoard(position) => write!(f, "{position:?} is outside of the map"),
+            Self::Duplicate(position) => write!(f, "{position:?} is in the map more than once"),
+            Self::Number(position) => write!(
+                f,
+                "{position:?} should have a number (2-12 but not 7) if and only if it is a resource"
+            ),
+            Self::Port(port) => write!(f, "port at {port:?} is not on the coast"),
+            Self::Robber(position) => write!(f, "robber starts at {position:?} which is not land"),
+        }
+    }
+}
+impl std::error::Error for MapError {}
+impl From<std::io::Error> for MapError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<serde_json::Error> for MapError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl MapFile {
+    /// an all desert map for the editor
+    pub fn blank(size: u8) -> Self {
+        Self {
+            version: MAP_VERSION,
+            size,
+            hexes: positions::generate_postions(size as i8)
+                .map(|position| (position, Hexagon::Desert, Number::None))
+                .collect(),
+            ports: vec![],
+            robber: Position { q: 0, r: 0, s: 0 },
+        }
+    }
+    /// the built in seafarers map, the usual island in the middle with small islands (and gold)
+    /// across the sea
+    pub fn seafarers() -> Self {
+        let size = 5;
+        // q, r, hex, number (0 for none)
+        let land = [
+            (0, 0, Hexagon::Desert, 0),
+            (-1, 1, Hexagon::Wood, 11),
+            (0, 1, Hexagon::Brick, 3),
+            (1, 0, Hexagon::Sheep, 6),
+            (1, -1, Hexagon::Wheat, 5),
+            (0, -1, Hexagon::Ore, 4),
+            (-1, 0, Hexagon::Sheep, 9),
+            (-2, 2, Hexagon::Wood, 10),
+            (-1, 2, Hexagon::Wheat, 8),
+            (0, 2, Hexagon::Ore, 3),
+            (1, 1, Hexagon::Brick, 11),
+            (2, 0, Hexagon::Sheep, 4),
+            (2, -1, Hexagon::Wheat, 5),
+            (2, -2, Hexagon::Wood, 10),
+            (1, -2, Hexagon::Sheep, 8),
+            (0, -2, Hexagon::Brick, 2),
+            (-1, -1, Hexagon::Wheat, 9),
+            (-2, 0, Hexagon::Wood, 12),
+            (-2, 1, Hexagon::Ore, 6),
+            (4, -2, Hexagon::Gold, 5),
+            (4, -1, Hexagon::Ore, 10),
+            (-4, 2, Hexagon::Gold, 9),
+            (-4, 1, Hexagon::Wheat, 4),
+            (-2, -2, Hexagon::Sheep, 6),
+            (-1, -3, Hexagon::Wood, 11),
+            (2, 2, Hexagon::Brick, 8),
+            (1, 3, Hexagon::Sheep, 3),
+        ];
+        let land = land.map(|(q, r, hex, number)| {
+            (
+                Position { q, r, s: -q - r },
+                hex,
+                if number == 0 {
+                    Number::None
+                } else {
+                    Number::Number(number)
+                },
+            )
+        });
+        let water = positions::generate_postions(size as i8)
+            .filter(|position| !land.iter().any(|(hex, _, _)| hex == position))
+            .map(|position| (position, Hexagon::Water, Number::None));
+        let ports = [
+            Port::ThreeForOne,
+            Port::TwoForOne(Resource::Wood),
+            Port::ThreeForOne,
+            Port::TwoForOne(Resource::Brick),
+            Port::TwoForOne(Resource::Sheep),
+            Port::ThreeForOne,
+            Port::TwoForOne(Resource::Wheat),
+            Port::ThreeForOne,
+            Port::TwoForOne(Resource::Ore),
+        ];
+        Self {
+            version: MAP_VERSION,
+            size,
+            hexes: land.into_iter().chain(water).collect(),
+            // the main island is the normal board so the ports go where they usually do
+            ports: setup_game::generate_port_positions(3)
+                .zip(ports.iter().flat_map(|port| [*port, *port]))
+                .collect(),
+            robber: Position { q: 0, r: 0, s: 0 },
+        }
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        let map = Self::read(path)?;
+        map.validate()?;
+        Ok(map)
+    }
+    /// like `load` but without validating, so the editor can open a map that isn't finished yet
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        // same as saves, check the version first
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let file = std::fs::read_to_string(path)?;
+        let Version { version } = serde_json::from_str(&file)?;
+        if version != MAP_VERSION {
+            return Err(MapError::Version(version));
+        }
+        Ok(serde_json::from_str(&file)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    /// identifies the map, so we can check that every peer loaded the same one
+    pub fn id(&self) -> u64 {
+        stable_hash(self)
+    }
+    pub fn validate(&self) -> Result<(), MapError> {
+        if !(BoardSize::MIN..=BoardSize::MAX).contains(&self.size) {
+            return Err(MapError::Size(self.size));
+        }
+        // land has to be inside the size, water can also be on the ring around it
+        let within = |position: &Position, bound: u8| {
+            position.all_points_are(|p| -(bound as i8) <= p && p <= bound as i8)
+                && position.q + position.r + position.s == 0
+        };
+        let board = self.board();
+        let land = board.land().collect_vec();
+        for (position, hex, number) in &self.hexes {
+            let bound = if land.contains(position) {
+                self.size - 1
+            } else {
+                self.size
+            };
+            if !within(position, bound) {
+                return Err(MapError::OffBoard(*position));
+            }
+            let valid_number = if hex.produces() {
+                matches!(number, Number::Number(2..=6 | 8..=12))
+            } else {
+                *number == Number::None
+            };
+            if !valid_number {
+                return Err(MapError::Number(*position));
+            }
+        }
+        if let Some(position) = self
+            .hexes
+            .iter()
+            .map(|(position, _, _)| position)
+            .duplicates()
+            .next()
+        {
+            return Err(MapError::Duplicate(*position));
+        }
+        // a port needs land to build on and water for the ships
+        for (port, _) in &self.ports {
+            let BuildingPosition::All(p1, p2, p3) = *port;
+            let hexes = [p1, p2, p3];
+            let on_coast = hexes.iter().all(|hex| within(hex, self.size))
+                // also checks that the hexes are in the same order as everywhere else
+                && BuildingPosition::new(p1, p2, p3, Some(self.size)) == Some(*port)
+                && board.touches_land(&hexes)
+                && !hexes.iter().all(|hex| land.contains(hex));
+            if !on_coast {
+                return Err(MapError::Port(*port));
+            }
+        }
+        if !land.contains(&self.robber) {
+            return Err(MapError::Robber(self.robber));
+        }
+        Ok(())
+    }
+    /// whatever is wrong with the map, and things that are allowed but probably not what you want
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if let Err(error) = self.validate() {
+            warnings.push(error.to_string());
+        }
+        // same as `setup_game::fix_numbers`, the random boards never have 6s and 8s next to each
+        // other
+        let reds = self
+            .hexes
+            .iter()
+            .filter(|(_, _, number)| matches!(number, Number::Number(6 | 8)))
+            .map(|(position, _, _)| *position)
+            .collect_vec();
+        for (red, other) in reds.iter().tuple_combinations() {
+            if Position::DIRECTION_VECTORS
+                .iter()
+                .any(|direction| *red + *direction == *other)
+            {
+                warnings.push(format!("6/8 at {red:?} is next to 6/8 at {other:?}"));
+            }
+        }
+        if self.ports.is_empty() {
+            warnings.push("there are no ports".to_string());
+        }
+        warnings
+    }
+    /// the board for the rules, with the hexes that aren't listed filled in as empty
+    pub fn board(&self) -> rules::Board {
+        let mut hexes = self.hexes.clone();
+        hexes.extend(
+            positions::generate_postions(self.size as i8 + 1)
+                .filter(|position| !self.hexes.iter().any(|(hex, _, _)| hex == position))
+                .map(|position| (position, Hexagon::Empty, Number::None)),
+        );
+        rules::Board {
+            size: self.size,
+            hexes,
+            ports: self.ports.clone(),
+        }
+    }
+}
+
+/// the map picked in the lobby, the board is set up from this instead of from the seed
+#[derive(Resource, Debug, Clone)]
+pub struct LoadedMap(pub MapFile);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: Position = Position { q: 0, r: 0, s: 0 };
+
+    // a blank map with a wood in the middle, and a port where the generated boards put one
+    fn map() -> MapFile {
+        let mut map = MapFile::blank(3);
+        set(&mut map, CENTER, Hexagon::Wood, Number::Number(6));
+        map.ports = setup_game::generate_port_positions(3)
+            .take(2)
+            .map(|port| (port, Port::ThreeForOne))
+            .collect();
+        map
+    }
+    fn set(map: &mut MapFile, position: Position, hex: Hexagon, number: Number) {
+        map.hexes.retain(|(other, _, _)| *other != position);
+        map.hexes.push((position, hex, number));
+    }
+    fn validate_with(position: Position, hex: Hexagon, number: Number) -> Result<(), MapError> {
+        let mut map = map();
+        set(&mut map, position, hex, number);
+        map.validate()
+    }
+
+    #[test]
+    fn built_in_maps_are_valid() {
+        for size in BoardSize::MIN..=BoardSize::MAX {
+            assert!(MapFile::blank(size).validate().is_ok());
+        }
+        assert!(MapFile::seafarers().validate().is_ok());
+        assert!(map().validate().is_ok());
+    }
+
+    #[test]
+    fn ports_are_on_the_coast() {
+        let mut map = map();
+        let inland = CENTER.building_positions_around()[0];
+        map.ports.push((inland, Port::ThreeForOne));
+        assert!(matches!(map.validate(), Err(MapError::Port(port)) if port == inland));
+    }
+
+    #[test]
+    fn only_resources_have_numbers() {
+        let position = Position { q: 1, r: 0, s: -1 };
+        for (hex, number) in [
+            (Hexagon::Desert, Number::Number(8)),
+            (Hexagon::Water, Number::Number(8)),
+            (Hexagon::Wood, Number::None),
+            (Hexagon::Wood, Number::Number(7)),
+        ] {
+            assert!(
+                matches!(
+                    validate_with(position, hex, number),
+                    Err(MapError::Number(at)) if at == position
+                ),
+                "{hex:?} {number:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hexes_are_listed_once() {
+        let mut map = map();
+        let first = map.hexes[0];
+        map.hexes.push(first);
+        assert!(matches!(map.validate(), Err(MapError::Duplicate(at)) if at == first.0));
+    }
+
+    // land has to be inside the size, water can be on the ring around it
+    #[test]
+    fn hexes_are_on_the_board() {
+        let edge = Position { q: 3, r: 0, s: -3 };
+        assert!(validate_with(edge, Hexagon::Water, Number::None).is_ok());
+        assert!(matches!(
+            validate_with(edge, Hexagon::Sheep, Number::Number(4)),
+            Err(MapError::OffBoard(at)) if at == edge
+        ));
+        let outside = Position { q: 4, r: 0, s: -4 };
+        assert!(matches!(
+            validate_with(outside, Hexagon::Water, Number::None),
+            Err(MapError::OffBoard(at)) if at == outside
+        ));
+    }
+
+    #[test]
+    fn robber_starts_on_land() {
+        assert!(matches!(
+            validate_with(CENTER, Hexagon::Water, Number::None),
+            Err(MapError::Robber(at)) if at == CENTER
+        ));
+    }
+}
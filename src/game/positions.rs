@@ -1,6 +1,7 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Ordering,
     mem,
     ops::{Add, Div},
 };
@@ -15,12 +16,6 @@ pub struct Position {
     pub r: i8,
     pub s: i8,
 }
-pub fn generate_postions_ring(n: i8) -> impl Iterator<Item = Position> {
-    let has_big_coordinate = move |i: i8| i == -n || i == n;
-    generate_postions(n + 1).filter(move |q| {
-        has_big_coordinate(q.q) || has_big_coordinate(q.r) || has_big_coordinate(q.s)
-    })
-}
 pub fn generate_postions(n: i8) -> impl Iterator<Item = Position> {
     (0..3)
         .map(|_| -n + 1..n)
@@ -33,6 +28,12 @@ pub fn generate_postions(n: i8) -> impl Iterator<Item = Position> {
         })
 }
 
+// the 5-6 player board is a hexagon without the left most hex of each row, so for size 4 it has
+// rows of 3, 4, 5, 6, 5, 4 and 3 hexes
+pub fn generate_stretched_postions(n: i8) -> impl Iterator<Item = Position> {
+    generate_postions(n).filter(move |p| p.q != -n + 1 && p.s != n - 1)
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct FPosition {
     pub q: f32,
@@ -220,7 +221,7 @@ pub enum RoadPosition {
     Both(Position, Position, Coordinate),
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub enum Coordinate {
     Q,
     R,
@@ -278,6 +279,16 @@ impl RoadPosition {
             }
         }
     }
+    /// the intersections at both ends of the road
+    pub fn ends(&self) -> Vec<BuildingPosition> {
+        let Self::Both(p1, p2, _) = *self;
+        let (n1, n2) = self.neighboring_two(None);
+        [n1, n2]
+            .into_iter()
+            .flatten()
+            .filter_map(|n| BuildingPosition::new(p1, p2, n, None))
+            .collect()
+    }
     pub const fn shared_coordinate(&self) -> Coordinate {
         match self {
             Self::Both(_, _, coordinate) => *coordinate,
@@ -318,8 +329,34 @@ impl PartialEq for RoadPosition {
         }
     }
 }
+// a road is the same either way around, so it is ordered by its positions smallest first (this way
+// sets of roads in the rules come out the same on every peer)
+impl Ord for RoadPosition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |Self::Both(p1, p2, coordinate): &Self| (*p1.min(p2), *p1.max(p2), *coordinate);
+        key(self).cmp(&key(other))
+    }
+}
+impl PartialOrd for RoadPosition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-#[derive(Component, PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Resource)]
+#[derive(
+    Component,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+    Deserialize,
+    Serialize,
+    Debug,
+    Resource,
+)]
 #[require(KatanComponent)]
 pub enum BuildingPosition {
     /// Do not use this
@@ -411,3 +448,242 @@ impl Add<Position> for BuildingPosition {
         }
     }
 }
+
+// every hex, road and intersection is checked on every board size, instead of sampling some of them
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::game::{
+        BoardSize,
+        rules::{buildings_on_road, roads_around, setup_road_placements},
+        setup_game::port_positions,
+    };
+
+    const SIZES: std::ops::RangeInclusive<u8> = 1..=7;
+
+    fn hexes(size: u8) -> Vec<Position> {
+        generate_postions(size as i8).collect()
+    }
+    fn on_board(size: u8, position: Position) -> bool {
+        position.all_points_are(|p| p.unsigned_abs() < size)
+    }
+    fn roads(size: u8) -> Vec<RoadPosition> {
+        setup_road_placements(size, &[], &[])
+    }
+    fn intersections(size: u8) -> HashSet<BuildingPosition> {
+        hexes(size)
+            .iter()
+            .flat_map(Position::building_positions_around)
+            .collect()
+    }
+    fn points(BuildingPosition::All(p1, p2, p3): BuildingPosition) -> [Position; 3] {
+        [p1, p2, p3]
+    }
+    fn coastal(size: u8, building: BuildingPosition) -> bool {
+        points(building)
+            .into_iter()
+            .any(|position| !on_board(size, position))
+    }
+    fn rotate_road(road: RoadPosition, n: u8) -> RoadPosition {
+        let RoadPosition::Both(p1, p2, _) = road;
+        RoadPosition::new(p1.rotate_right_n(n), p2.rotate_right_n(n), None).unwrap()
+    }
+    fn distance(p1: Position, p2: Position) -> u8 {
+        p1.q.abs_diff(p2.q)
+            .max(p1.r.abs_diff(p2.r))
+            .max(p1.s.abs_diff(p2.s))
+    }
+
+    #[test]
+    fn positions_add_up_to_zero() {
+        for size in SIZES {
+            let hexes = hexes(size);
+            assert_eq!(
+                hexes.len(),
+                3 * usize::from(size) * usize::from(size - 1) + 1
+            );
+            for Position { q, r, s } in hexes {
+                assert_eq!(q + r + s, 0);
+                assert!(Position::new(q, r, s, Some(size - 1)).is_some());
+                assert!(Position::new(q + 1, r, s, None).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn rotating_six_times_goes_all_the_way_around() {
+        for size in SIZES {
+            for hex in hexes(size) {
+                assert_eq!(hex.rotate_right_n(6), hex);
+                assert!(on_board(size, hex.rotate_right()));
+            }
+            for building in intersections(size) {
+                assert_eq!(building.rotate_right_n(6), building);
+                assert_eq!(
+                    building.rotate_right_n(2),
+                    building.rotate_right().rotate_right()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotations_preserve_adjacency() {
+        for size in SIZES {
+            let hexes = hexes(size);
+            for (p1, p2) in hexes.iter().tuple_combinations() {
+                for n in 1..6 {
+                    assert_eq!(
+                        distance(p1.rotate_right_n(n), p2.rotate_right_n(n)),
+                        distance(*p1, *p2)
+                    );
+                }
+            }
+            let intersections = intersections(size);
+            for road in roads(size) {
+                let ends = buildings_on_road(size, road).collect_vec();
+                for n in 1..6 {
+                    let rotated = rotate_road(road, n);
+                    // the board is the same after rotating, so the road is still on it
+                    let RoadPosition::Both(p1, p2, _) = rotated;
+                    assert!(RoadPosition::new(p1, p2, Some(size)).is_some());
+                    let rotated_ends = buildings_on_road(size, rotated).collect::<HashSet<_>>();
+                    let expected = ends
+                        .iter()
+                        .map(|end| end.rotate_right_n(n))
+                        .collect::<HashSet<_>>();
+                    assert_eq!(rotated_ends, expected);
+                    assert!(expected.iter().all(|end| intersections.contains(end)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn building_points_are_sorted() {
+        for size in SIZES {
+            for building in intersections(size) {
+                for [p1, p2, p3] in points(building)
+                    .into_iter()
+                    .permutations(3)
+                    .map(|p| [p[0], p[1], p[2]])
+                {
+                    assert_eq!(
+                        BuildingPosition::new(p1, p2, p3, Some(size)),
+                        Some(building)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn roads_are_the_same_either_way_around() {
+        for size in SIZES {
+            for road in roads(size) {
+                let RoadPosition::Both(p1, p2, coordinate) = road;
+                let reversed = RoadPosition::new(p2, p1, Some(size)).unwrap();
+                assert_eq!(reversed, road);
+                assert_eq!(reversed.shared_coordinate(), coordinate);
+            }
+        }
+    }
+
+    #[test]
+    fn interior_intersections_have_three_roads() {
+        for size in SIZES {
+            let roads = roads(size);
+            for building in intersections(size) {
+                let around = roads_around(size, building).collect_vec();
+                let expected = if coastal(size, building) {
+                    // the two hexes off the board don't have a road between them
+                    let off_board = points(building)
+                        .into_iter()
+                        .filter(|position| !on_board(size, *position))
+                        .count();
+                    if off_board == 2 { 2 } else { 3 }
+                } else {
+                    3
+                };
+                assert_eq!(around.len(), expected, "{building:?}");
+                // and those are the roads that end there
+                let ending = roads
+                    .iter()
+                    .filter(|road| buildings_on_road(size, **road).contains(&building))
+                    .count();
+                assert_eq!(ending, expected, "{building:?}");
+                assert!(around.iter().all(|road| roads.contains(road)));
+            }
+        }
+    }
+
+    #[test]
+    fn buildings_on_road_and_intersect_agree() {
+        for size in SIZES {
+            let roads = roads(size);
+            for road in &roads {
+                let ends = buildings_on_road(size, *road).collect_vec();
+                assert_eq!(ends.len(), 2, "{road:?}");
+                assert_eq!(road.ends(), ends);
+            }
+            for (r1, r2) in roads.iter().tuple_combinations() {
+                let shared = buildings_on_road(size, *r1)
+                    .filter(|end| buildings_on_road(size, *r2).contains(end))
+                    .collect_vec();
+                assert!(shared.len() <= 1);
+                let shared = shared.first().copied();
+                assert_eq!(r1.intersect(r2, Some(size)), shared, "{r1:?} {r2:?}");
+                assert_eq!(r2.intersect(r1, Some(size)), shared, "{r2:?} {r1:?}");
+            }
+        }
+    }
+
+    // V = 6n^2, E = 9n^2 - 3n and the coast is 6 * (2n - 1) intersections, where `n` is the size
+    // (so the usual board is 3)
+    #[test]
+    fn counts_match_the_formulas() {
+        for size in SIZES {
+            let n = usize::from(size);
+            let intersections = intersections(size);
+            let roads = roads(size);
+            assert_eq!(intersections.len(), 6 * n * n, "size {size}");
+            assert_eq!(roads.len(), 9 * n * n - 3 * n, "size {size}");
+            let coast = intersections
+                .iter()
+                .filter(|building| coastal(size, **building))
+                .count();
+            assert_eq!(coast, 6 * (2 * n - 1), "size {size}");
+            // euler: V - E + F = 1 for the hexes as faces
+            assert_eq!(
+                intersections.len() + hexes(size).len(),
+                roads.len() + 1,
+                "size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn ports_are_pairs_on_the_coast() {
+        for size in SIZES {
+            let ports = port_positions(&hexes(size), BoardSize(size), false);
+            assert_eq!(ports.len() % 2, 0, "size {size}");
+            assert_eq!(ports.iter().unique().count(), ports.len(), "size {size}");
+            assert!(ports.iter().all(|port| coastal(size, *port)), "size {size}");
+            // each port is on one road along the coast
+            let roads = roads(size);
+            for pair in ports.chunks(2) {
+                let pair = pair.iter().copied().collect::<HashSet<_>>();
+                assert!(
+                    roads
+                        .iter()
+                        .any(|road| buildings_on_road(size, *road).collect::<HashSet<_>>() == pair),
+                    "size {size} {pair:?}"
+                );
+            }
+        }
+        // the usual board has the 9 ports from the box
+        assert_eq!(port_positions(&hexes(3), BoardSize(3), false).len(), 18);
+    }
+}
@@ -0,0 +1,383 @@
+//! recording and replaying games
+//! the rules only change through inputs and the dice/robber only use the `SessionRng`, so the seed,
+//! the player count, the board size and constraints (or map), if its cities & knights, the house
+//! rules and every accepted input (`Moves`) are enough to play the game again
+//! a replay runs as a sync test session where every player is local, and the inputs come from the
+//! file instead of the ui
+use std::path::Path;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ggrs::{
+    GgrsTime, LocalInputs, LocalPlayers, ReadInputs, Session,
+    ggrs::{self, PlayerType},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BoardSize, GameState, GgrsSessionConfig, Input, KatanComponent, Layout, LocalPlayerHandle,
+    Moves, PlayerCount, PlayerHandle, SessionSeed,
+    balance::BoardConstraints,
+    cities_and_knights::PlayCitiesAndKnights,
+    game_rules::GameRules,
+    map::{LoadedMap, MapFile},
+    save::{LoadedGame, SAVE_VERSION, SaveError, SaveFile},
+};
+use crate::{
+    AppState,
+    common_ui::{self, ButtonInteraction},
+    utils::NORMAL_BUTTON,
+};
+
+// frames between moves when playing, so you can actually see what happens
+const REPLAY_DELAY: usize = 30;
+// frames between moves when seeking, the ui needs a frame to change state after some inputs
+const SEEK_DELAY: usize = 2;
+// how many moves the seek buttons skip
+const SEEK: usize = 10;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReplayFile {
+    // replays contain a save (for loaded games), so they share the version
+    pub version: u32,
+    pub seed: u64,
+    pub player_count: usize,
+    // older replays don't have this, they were played on the default board for their player count
+    #[serde(default)]
+    pub board_size: Option<u8>,
+    // older replays were all shuffled without any constraints
+    #[serde(default)]
+    pub constraints: Option<BoardConstraints>,
+    #[serde(default)]
+    pub cities_and_knights: bool,
+    #[serde(default)]
+    pub game_rules: GameRules,
+    // games on a custom map keep the whole map, so the replay doesn't need the map file
+    #[serde(default)]
+    pub map: Option<MapFile>,
+    // for games that were loaded from a save, the game starts from the save instead of the seed
+    pub start: Option<SaveFile>,
+    pub moves: Vec<(PlayerHandle, Input)>,
+}
+impl ReplayFile {
+    pub fn new(start: &GameStart, moves: &Moves) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            seed: start.seed,
+            player_count: start.player_count,
+            board_size: Some(start.board_size),
+            constraints: start.constraints,
+            cities_and_knights: start.cities_and_knights,
+            game_rules: start.game_rules,
+            map: start.map.clone(),
+            start: start.start.clone(),
+            moves: moves.0.clone(),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let file = std::fs::read_to_string(path)?;
+        let Version { version } = serde_json::from_str(&file)?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version(version));
+        }
+        Ok(serde_json::from_str(&file)?)
+    }
+}
+
+/// how the current game started, `LoadedGame` is gone by the time we write the replay so we keep
+/// it here
+#[derive(Resource, Debug, Clone)]
+pub struct GameStart {
+    pub seed: u64,
+    pub player_count: usize,
+    pub board_size: u8,
+    pub constraints: Option<BoardConstraints>,
+    pub cities_and_knights: bool,
+    pub game_rules: GameRules,
+    pub map: Option<MapFile>,
+    pub start: Option<SaveFile>,
+}
+
+/// the replay being watched, while this exists inputs come from the file instead of the ui
+#[derive(Resource, Debug)]
+pub struct Replay {
+    file: ReplayFile,
+    // index of the next move to feed
+    next: usize,
+    playing: bool,
+    // moves before this are fed as fast as possible (for stepping and seeking)
+    target: usize,
+    // frames until the next move
+    wait: usize,
+}
+impl Replay {
+    pub const fn new(file: ReplayFile) -> Self {
+        Self {
+            file,
+            next: 0,
+            playing: false,
+            target: 0,
+            wait: REPLAY_DELAY,
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), record_start)
+            .add_systems(
+                OnEnter(AppState::GameOver),
+                save_replay.run_if(not(resource_exists::<Replay>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    common_ui::button_system_with_generic::<
+                        SaveReplayButton,
+                        SaveReplayButtonState<'_>,
+                    >
+                        .run_if(in_state(AppState::InGame)),
+                    start_replay.run_if(in_state(AppState::Menu).and(resource_exists::<Replay>)),
+                    (
+                        common_ui::button_system_with_generic::<
+                            ReplayControl,
+                            ReplayControlState<'_, '_>,
+                        >,
+                        update_progress.run_if(resource_changed::<Replay>),
+                    )
+                        .chain()
+                        .run_if(resource_exists::<Replay>),
+                ),
+            )
+            .add_systems(ReadInputs, feed_replay.run_if(resource_exists::<Replay>))
+            .add_systems(
+                OnEnter(GameState::Start),
+                show_controls.run_if(resource_exists::<Replay>),
+            );
+    }
+}
+
+fn record_start(
+    mut commands: Commands<'_, '_>,
+    seed: Res<'_, SessionSeed>,
+    player_count: Res<'_, PlayerCount>,
+    board_size: Res<'_, BoardSize>,
+    constraints: Option<Res<'_, BoardConstraints>>,
+    cities_and_knights: Option<Res<'_, PlayCitiesAndKnights>>,
+    game_rules: Option<Res<'_, GameRules>>,
+    map: Option<Res<'_, LoadedMap>>,
+    loaded: Option<Res<'_, LoadedGame>>,
+) {
+    commands.insert_resource(GameStart {
+        seed: seed.0,
+        player_count: player_count.0.into(),
+        board_size: board_size.0,
+        constraints: constraints.as_deref().copied(),
+        cities_and_knights: cities_and_knights.is_some(),
+        game_rules: game_rules.as_deref().copied().unwrap_or_default(),
+        map: map.map(|map| map.0.clone()),
+        start: loaded.map(|loaded| loaded.0.clone()),
+    });
+    commands.insert_resource(Moves::default());
+}
+
+fn write_replay(start: &GameStart, moves: &Moves, local_player: LocalPlayerHandle) {
+    let path = format!("katan-{}.replay", local_player.0);
+    match ReplayFile::new(start, moves).save(&path) {
+        Ok(()) => info!("saved replay to {path}"),
+        Err(error) => error!("could not save replay to {path}: {error}"),
+    }
+}
+
+fn save_replay(
+    start: Res<'_, GameStart>,
+    moves: Res<'_, Moves>,
+    local_player: Res<'_, LocalPlayerHandle>,
+) {
+    write_replay(&start, &moves, *local_player);
+}
+
+#[derive(Component, Default, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct SaveReplayButton;
+#[derive(SystemParam)]
+pub struct SaveReplayButtonState<'w> {
+    start: Res<'w, GameStart>,
+    moves: Res<'w, Moves>,
+    local_player: Res<'w, LocalPlayerHandle>,
+}
+impl ButtonInteraction<SaveReplayButton> for SaveReplayButtonState<'_> {
+    fn interact(&mut self, _: &SaveReplayButton) {
+        write_replay(&self.start, &self.moves, *self.local_player);
+    }
+}
+
+// everyone is local, so we see the game from the first players point of view
+fn start_replay(
+    mut commands: Commands<'_, '_>,
+    mut replay: ResMut<'_, Replay>,
+    mut app_state: ResMut<'_, NextState<AppState>>,
+) {
+    let player_count = replay.file.player_count;
+    let mut session_builder = ggrs::SessionBuilder::<GgrsSessionConfig>::new()
+        .with_num_players(player_count)
+        // nothing is ever predicted so there is nothing to check
+        .with_check_distance(0);
+    for handle in 0..player_count {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+    let session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+
+    commands.insert_resource(SessionSeed(replay.file.seed));
+    commands.insert_resource(PlayerCount(player_count as u8));
+    commands.insert_resource(
+        replay
+            .file
+            .board_size
+            .map_or_else(|| BoardSize::for_players(player_count), BoardSize),
+    );
+    if let Some(constraints) = replay.file.constraints {
+        commands.insert_resource(constraints);
+    } else {
+        commands.remove_resource::<BoardConstraints>();
+    }
+    if replay.file.cities_and_knights {
+        commands.insert_resource(PlayCitiesAndKnights);
+    } else {
+        commands.remove_resource::<PlayCitiesAndKnights>();
+    }
+    commands.insert_resource(replay.file.game_rules);
+    commands.insert_resource(LocalPlayerHandle(0));
+    if let Some(map) = &replay.file.map {
+        commands.insert_resource(LoadedMap(map.clone()));
+    } else {
+        commands.remove_resource::<LoadedMap>();
+    }
+    if let Some(start) = &replay.file.start {
+        commands.insert_resource(LoadedGame(start.clone()));
+    } else {
+        commands.remove_resource::<LoadedGame>();
+    }
+    commands.insert_resource(Session::SyncTest(session));
+    replay.next = 0;
+    replay.wait = REPLAY_DELAY;
+    app_state.set(AppState::InGame);
+}
+
+// every player gets no input, except the one whose move is next (if its time for it)
+fn feed_replay(mut commands: Commands<'_, '_>, mut replay: ResMut<'_, Replay>) {
+    let mut inputs = vec![Input::None; replay.file.player_count];
+    if replay.wait > 0 {
+        replay.wait -= 1;
+    } else if replay.playing || replay.next < replay.target {
+        if let Some((player, input)) = replay.file.moves.get(replay.next).copied()
+            && let Some(slot) = inputs.get_mut(player.0)
+        {
+            *slot = input;
+        }
+        replay.next = (replay.next + 1).min(replay.file.moves.len());
+        replay.wait = if replay.next < replay.target {
+            SEEK_DELAY
+        } else {
+            REPLAY_DELAY
+        };
+    }
+    commands.insert_resource(LocalInputs::<GgrsSessionConfig>(
+        inputs.into_iter().enumerate().collect(),
+    ));
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub enum ReplayControl {
+    PlayPause,
+    Step,
+    Back,
+    Forward,
+}
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct ReplayProgress;
+
+fn show_controls(mut commands: Commands<'_, '_>, layout: Res<'_, Layout>) {
+    commands
+        .entity(layout.setting_pull_out)
+        .with_children(|parent| {
+            parent.spawn((ReplayProgress, Text::default()));
+            for (control, label) in [
+                (ReplayControl::PlayPause, "play/pause"),
+                (ReplayControl::Step, "step"),
+                (ReplayControl::Back, "-10"),
+                (ReplayControl::Forward, "+10"),
+            ] {
+                parent.spawn((
+                    Button,
+                    control,
+                    Text::new(label),
+                    BackgroundColor(NORMAL_BUTTON),
+                ));
+            }
+        });
+}
+
+#[derive(SystemParam)]
+pub struct ReplayControlState<'w, 's> {
+    replay: ResMut<'w, Replay>,
+    commands: Commands<'w, 's>,
+    app_state: ResMut<'w, NextState<AppState>>,
+}
+impl ButtonInteraction<ReplayControl> for ReplayControlState<'_, '_> {
+    fn interact(&mut self, control: &ReplayControl) {
+        let len = self.replay.file.moves.len();
+        match control {
+            ReplayControl::PlayPause => self.replay.playing = !self.replay.playing,
+            ReplayControl::Step => {
+                self.replay.playing = false;
+                self.replay.target = (self.replay.next + 1).min(len);
+                self.replay.wait = self.replay.wait.min(SEEK_DELAY);
+            }
+            ReplayControl::Forward => {
+                self.replay.target = (self.replay.next + SEEK).min(len);
+                self.replay.wait = self.replay.wait.min(SEEK_DELAY);
+            }
+            // moves can't be undone, so we start the replay over and seek to where we want to be
+            ReplayControl::Back => {
+                self.replay.target = self.replay.next.saturating_sub(SEEK);
+                self.commands.remove_resource::<LocalPlayers>();
+                self.commands
+                    .remove_resource::<Session<GgrsSessionConfig>>();
+                // https://github.com/gschup/bevy_ggrs/issues/93
+                self.commands.insert_resource(Time::new_with(GgrsTime));
+                self.app_state.set(AppState::Menu);
+            }
+        }
+    }
+}
+
+fn update_progress(
+    replay: Res<'_, Replay>,
+    mut text: Query<'_, '_, &mut Text, With<ReplayProgress>>,
+) {
+    let state = if replay.next < replay.target {
+        "seeking"
+    } else if replay.playing {
+        "playing"
+    } else {
+        "paused"
+    };
+    for mut text in &mut text {
+        text.0 = format!("move {}/{} ({state})", replay.next, replay.file.moves.len());
+    }
+}
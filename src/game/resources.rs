@@ -4,7 +4,7 @@ use crate::utils::CheckedSub;
 
 use super::KatanComponent;
 use bevy::prelude::*;
-use rand::seq::IteratorRandom;
+use rand::{Rng, seq::IteratorRandom};
 use serde::{Deserialize, Serialize};
 
 pub const CITY_RESOURCES: Resources = Resources {
@@ -28,6 +28,29 @@ pub const ROAD_RESOURCES: Resources = Resources {
     wheat: 0,
     ore: 0,
 };
+// seafarers
+pub const SHIP_RESOURCES: Resources = Resources {
+    wood: 1,
+    brick: 0,
+    sheep: 1,
+    wheat: 0,
+    ore: 0,
+};
+// cities & knights, promoting a knight costs the same as building one
+pub const KNIGHT_RESOURCES: Resources = Resources {
+    wood: 0,
+    brick: 0,
+    sheep: 1,
+    wheat: 0,
+    ore: 1,
+};
+pub const ACTIVATE_KNIGHT_RESOURCES: Resources = Resources {
+    wood: 0,
+    brick: 0,
+    sheep: 0,
+    wheat: 1,
+    ore: 0,
+};
 pub const DEVELOPMENT_CARD_RESOURCES: Resources = Resources {
     wood: 0,
     brick: 0,
@@ -36,7 +59,7 @@ pub const DEVELOPMENT_CARD_RESOURCES: Resources = Resources {
     ore: 1,
 };
 #[derive(
-    Debug, Component, Resource, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq,
+    Debug, Component, Resource, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash,
 )]
 pub struct Resources {
     pub wood: u8,
@@ -55,6 +78,7 @@ pub enum Resource {
     Ore,
 }
 impl Resource {
+    pub const ALL: [Self; 5] = [Self::Wood, Self::Brick, Self::Sheep, Self::Wheat, Self::Ore];
     pub const fn color(&self) -> Color {
         match self {
             Self::Wood => Color::srgb_u8(161, 102, 47),
@@ -189,6 +213,11 @@ impl Resources {
     pub const fn new_game() -> Self {
         Self::new(19, 19, 19, 19, 19)
     }
+    /// the bank for 5-6 players
+    #[must_use]
+    pub const fn new_extension_game() -> Self {
+        Self::new(24, 24, 24, 24, 24)
+    }
     #[must_use]
     pub const fn new(wood: u8, brick: u8, sheep: u8, wheat: u8, ore: u8) -> Self {
         Self {
@@ -210,8 +239,59 @@ impl Resources {
         }
     }
 }
+/// cities & knights, cities next to forests, pastures and mountains make these instead of a second
+/// resource, they are only used for city improvements
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Commodities {
+    pub paper: u8,
+    pub cloth: u8,
+    pub coin: u8,
+}
+#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize, Debug, Hash)]
+pub enum Commodity {
+    Paper,
+    Cloth,
+    Coin,
+}
+impl Commodity {
+    pub const ALL: [Self; 3] = [Self::Paper, Self::Cloth, Self::Coin];
+    pub const fn color(&self) -> Color {
+        match self {
+            Self::Paper => Color::srgb_u8(238, 232, 170),
+            Self::Cloth => Color::srgb_u8(144, 238, 144),
+            Self::Coin => Color::srgb_u8(192, 192, 192),
+        }
+    }
+}
+impl Commodities {
+    pub const fn get(&self, selector: Commodity) -> u8 {
+        match selector {
+            Commodity::Paper => self.paper,
+            Commodity::Cloth => self.cloth,
+            Commodity::Coin => self.coin,
+        }
+    }
+    pub const fn get_mut(&mut self, selector: Commodity) -> &mut u8 {
+        match selector {
+            Commodity::Paper => &mut self.paper,
+            Commodity::Cloth => &mut self.cloth,
+            Commodity::Coin => &mut self.coin,
+        }
+    }
+    pub const fn count(self) -> u8 {
+        self.paper + self.cloth + self.coin
+    }
+}
+impl AddAssign for Commodities {
+    fn add_assign(&mut self, rhs: Self) {
+        self.paper += rhs.paper;
+        self.cloth += rhs.cloth;
+        self.coin += rhs.coin;
+    }
+}
+
 /// assumption: other player has at least on resource
-pub fn take_resource(other_color_resources: &Resources) -> Option<Resource> {
+pub fn take_resource(other_color_resources: &Resources, rng: &mut impl Rng) -> Option<Resource> {
     [
         Resource::Brick,
         Resource::Wood,
@@ -222,5 +302,5 @@ pub fn take_resource(other_color_resources: &Resources) -> Option<Resource> {
     .into_iter()
     // verifiing that the player has the resources we are trying to take randomly
     .filter(|r| other_color_resources.get(*r) > 0)
-    .choose(&mut rand::rng())
+    .choose(rng)
 }
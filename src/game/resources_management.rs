@@ -1,6 +1,6 @@
 use std::{
     fmt,
-    ops::{Add, AddAssign, Sub, SubAssign},
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
 };
 
 use bevy::{ecs::system::SystemParam, prelude::*};
@@ -9,16 +9,17 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     AppState,
-    utils::{CheckedAdd, CheckedSub, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON},
+    utils::{CheckedAdd, CheckedSub},
 };
 
 use super::{
-    GameState, Input, KatanComponent, Layout, LocalPlayer,
-    colors::CatanColorRef,
+    GameState, Input, KatanComponent, Layout, LocalPlayer, PlayerHandle, Rules,
     colors::{CatanColor, CurrentColor},
     common_ui::{self, ButtonInteraction, SpinnerButtonInteraction, Value},
     resources::{self, Resources},
+    rules::TradeId,
     setup_game::Ports,
+    special_building,
 };
 #[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent)]
@@ -29,136 +30,189 @@ pub struct BankTradeButton;
 #[derive(SystemParam)]
 struct TradeState<'w> {
     trade: Res<'w, TradingResources>,
+    target: Res<'w, TradeTarget>,
     // maybe a with<..> just in case there more entities that also have color
     input: ResMut<'w, Input>,
 }
 
-#[derive(Component)]
+/// who the trade is offered to, `None` for everyone
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct TradeTarget(pub Option<PlayerHandle>);
+#[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent)]
-pub struct AcceptTrade {
-    pub trade: TradingResources,
+pub struct TradeTargetButton;
+#[derive(SystemParam)]
+struct TradeTargetState<'w> {
+    target: ResMut<'w, TradeTarget>,
+    rules: Res<'w, Rules>,
+    local_player: Res<'w, LocalPlayer>,
 }
-#[derive(Component)]
-#[require(KatanComponent)]
-pub struct RejectTrade;
-
-// interaction for current player (whose turn it is)
-fn accept_trade_interaction_current(
-    mut commands: Commands<'_, '_>,
-    mut input: ResMut<'_, Input>,
-    interaction_query: Query<
-        '_,
-        '_,
-        (
-            &mut Button,
-            &mut BackgroundColor,
-            &Interaction,
-            &AcceptTrade,
-            &ChildOf,
-            &CatanColorRef,
-        ),
-        Changed<Interaction>,
-    >,
-    player_resources: Query<'_, '_, &Resources>,
-    current_player: Res<'_, LocalPlayer>,
+impl ButtonInteraction<TradeTargetButton> for TradeTargetState<'_> {
+    // everyone, then each of the other players in turn
+    fn interact(&mut self, _: &TradeTargetButton) {
+        let local = self.local_player.0.handle;
+        self.target.0 = (self.target.0.map_or(0, |to| to.0 + 1)..self.rules.players.len())
+            .map(PlayerHandle)
+            .find(|handle| *handle != local);
+    }
+}
+fn show_trade_target(
+    target: Res<'_, TradeTarget>,
+    rules: Res<'_, Rules>,
+    mut text: Single<'_, '_, &mut Text, With<TradeTargetButton>>,
 ) {
-    for (mut button, mut color, interaction, accept_trade, parent, trader) in interaction_query {
-        if let Ok(player_resources) = player_resources.get(current_player.0.entity)
-            && player_resources.checked_add(accept_trade.trade).is_some()
-        {
-            match interaction {
-                Interaction::Pressed => {
-                    *color = PRESSED_BUTTON.into();
-                    button.set_changed();
-                    *input = Input::TradeAccept(accept_trade.trade, trader.entity);
+    ***text = target
+        .0
+        .and_then(|to| rules.player(to))
+        .map_or_else(|| "all".to_string(), |to| format!("{:?}", to.color));
+}
 
-                    commands.entity(parent.parent()).despawn();
-                    break;
-                }
-                Interaction::Hovered => {
-                    *color = HOVERED_BUTTON.into();
-                    button.set_changed();
-                }
-                Interaction::None => {
-                    *color = NORMAL_BUTTON.into();
-                }
-            }
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct TradeRow(pub TradeId);
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub enum TradeOfferButton {
+    // the current player takes the trade, everyone else says they would take it
+    Accept(TradeId),
+    // offer what is in the trading resources instead
+    Counter(TradeId),
+    Cancel(TradeId),
+    // just hides the offer for us
+    Reject(TradeId),
+}
+#[derive(SystemParam)]
+struct TradeOfferState<'w, 's> {
+    input: ResMut<'w, Input>,
+    rules: Res<'w, Rules>,
+    local_player: Res<'w, LocalPlayer>,
+    trading_resources: Res<'w, TradingResources>,
+    rows: Query<'w, 's, (&'static TradeRow, &'static mut Node)>,
+}
+impl TradeOfferState<'_, '_> {
+    fn is_current(&self) -> bool {
+        self.rules.current == self.local_player.0.handle
+    }
+    // the trading resources are from our point of view, offers are from the current players
+    fn counter_offer(&self) -> TradingResources {
+        if self.is_current() {
+            *self.trading_resources
+        } else {
+            -*self.trading_resources
         }
     }
 }
-
-fn accept_trade_interaction(
-    mut commands: Commands<'_, '_>,
-    mut input: ResMut<'_, Input>,
-    interaction_query: Query<
-        '_,
-        '_,
-        (
-            &mut Button,
-            &mut BackgroundColor,
-            &Interaction,
-            &AcceptTrade,
-            &ChildOf,
-        ),
-        Changed<Interaction>,
-    >,
-    player_resources: Query<'_, '_, &Resources>,
-    player: Res<'_, LocalPlayer>,
-) {
-    for (mut button, mut color, interaction, accept_trade, parent) in interaction_query {
-        if let Ok(player_resources) = player_resources.get(player.0.entity)
-            && player_resources.checked_sub(accept_trade.trade).is_some()
-        {
-            match interaction {
-                Interaction::Pressed => {
-                    *color = PRESSED_BUTTON.into();
-                    button.set_changed();
-                    *input = Input::TradeResponce(accept_trade.trade);
-
-                    commands.entity(parent.parent()).despawn();
-                    break;
-                }
-                Interaction::Hovered => {
-                    *color = HOVERED_BUTTON.into();
-                    button.set_changed();
-                }
-                Interaction::None => {
-                    *color = NORMAL_BUTTON.into();
+impl ButtonInteraction<TradeOfferButton> for TradeOfferState<'_, '_> {
+    fn interact(&mut self, button: &TradeOfferButton) {
+        let input = match *button {
+            TradeOfferButton::Accept(id) if self.is_current() => Input::TradeAccept(id),
+            TradeOfferButton::Accept(id) => Input::TradeResponce(id),
+            TradeOfferButton::Counter(id) => Input::CounterOffer(id, self.counter_offer()),
+            TradeOfferButton::Cancel(id) => Input::CancelTrade(id),
+            TradeOfferButton::Reject(id) => {
+                for (row, mut node) in &mut self.rows {
+                    if row.0 == id {
+                        node.display = Display::None;
+                    }
                 }
+                Input::None
             }
+        };
+        if input != Input::None {
+            *self.input = input;
+        }
+    }
+    fn verify(&mut self, button: &TradeOfferButton) -> bool {
+        let Some(player) = self.rules.player(self.local_player.0.handle) else {
+            return false;
+        };
+        match *button {
+            TradeOfferButton::Accept(id) => self
+                .rules
+                .trades
+                .iter()
+                .find(|offer| offer.id == id)
+                .is_some_and(|offer| {
+                    if self.is_current() {
+                        player.resources.checked_add(offer.trade).is_some()
+                    } else {
+                        player.resources.checked_sub(offer.trade).is_some()
+                    }
+                }),
+            TradeOfferButton::Counter(_) => {
+                let (giving, taking) = self.trading_resources.given_and_taken();
+                !giving.is_empty() && !taking.is_empty()
+            }
+            TradeOfferButton::Cancel(_) | TradeOfferButton::Reject(_) => true,
         }
     }
 }
-fn reject_trade_interaction(
+// the offers are drawn from the rules, so each row goes away by itself once its trade is accepted,
+// cancelled or can't happen anymore
+fn draw_trades(
     mut commands: Commands<'_, '_>,
-    interaction_query: Query<
-        '_,
-        '_,
-        (&mut Button, &mut BackgroundColor, &Interaction, &ChildOf),
-        (Changed<Interaction>, With<RejectTrade>),
-    >,
+    rules: Res<'_, Rules>,
+    local_player: Res<'_, LocalPlayer>,
+    layout: Res<'_, Layout>,
+    rows: Query<'_, '_, (Entity, &TradeRow)>,
 ) {
-    for (mut button, mut color, interaction, parent) in interaction_query {
-        match interaction {
-            Interaction::Pressed => {
-                *color = PRESSED_BUTTON.into();
-                button.set_changed();
-                commands.entity(parent.parent()).despawn();
-                break;
-            }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
-                button.set_changed();
-            }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
-            }
+    let local = local_player.0.handle;
+    // with hot seat the offers we see change with the player
+    let redraw = local_player.is_changed();
+    for (entity, row) in rows {
+        if redraw || !rules.trades.iter().any(|offer| offer.id == row.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for offer in rules
+        .trades
+        .iter()
+        .filter(|offer| offer.from == local || offer.for_player(local))
+    {
+        if !redraw && rows.iter().any(|(_, row)| row.0 == offer.id) {
+            continue;
+        }
+        let Some(from) = rules.player(offer.from) else {
+            continue;
+        };
+        let text = Text::new(format!("{:?}: {}", from.color, offer.trade));
+        let row = (
+            TradeRow(offer.id),
+            Node {
+                display: Display::Grid,
+                grid_template_columns: vec![
+                    GridTrack::auto(),
+                    GridTrack::auto(),
+                    GridTrack::auto(),
+                    GridTrack::auto(),
+                ],
+                ..Default::default()
+            },
+        );
+        if offer.from == local {
+            commands.entity(layout.trades).with_child((
+                row,
+                children![
+                    text,
+                    (Button, Text::new("x"), TradeOfferButton::Cancel(offer.id))
+                ],
+            ));
+        } else {
+            commands.entity(layout.trades).with_child((
+                row,
+                children![
+                    text,
+                    (Button, Text::new("x"), TradeOfferButton::Reject(offer.id)),
+                    (Button, Text::new("c"), TradeOfferButton::Counter(offer.id)),
+                    (Button, Text::new("Ok"), TradeOfferButton::Accept(offer.id))
+                ],
+            ));
         }
     }
 }
 impl ButtonInteraction<TradeButton> for TradeState<'_> {
     fn interact(&mut self, _: &TradeButton) {
-        *self.input = Input::Trade(*self.trade);
+        *self.input = Input::Trade(*self.trade, self.target.0);
     }
     fn verify(&mut self, _: &TradeButton) -> bool {
         let (giving, taking) = self.trade.given_and_taken();
@@ -174,6 +228,7 @@ struct BankTradeState<'w, 's> {
     current_color: Res<'w, CurrentColor>,
     player_resources_and_ports:
         Query<'w, 's, (&'static mut Resources, &'static Ports), With<CatanColor>>,
+    rules: Res<'w, Rules>,
     input: ResMut<'w, Input>,
 }
 
@@ -194,11 +249,14 @@ impl ButtonInteraction<BankTradeButton> for BankTradeState<'_, '_> {
                     (*count % trade_rate as i8 == 0).then_some(count / trade_rate as i8)
                 })
                 .sum();
-            // TODO: verify there is enough resources in bank
             println!("{giving} -> {taking}");
 
             (giving == -(taking)) && giving != 0 && taking != 0
-        })
+        }) && self
+            .rules
+            .bank
+            .checked_sub(*self.trading_resources)
+            .is_some()
     }
     fn interact(&mut self, _: &BankTradeButton) {
         *self.input = Input::BankTrade(*self.trading_resources);
@@ -232,6 +290,19 @@ pub fn show_player_resources(
 #[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent)]
 pub struct TradingText;
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct BankText;
+// the bank is a `Resources` resource, kept in sync with the rules
+pub fn show_bank(bank: Res<'_, Resources>, mut text: Single<'_, '_, &mut Text, With<BankText>>) {
+    ***text = format!(
+        "bank: {}",
+        resources::Resource::ALL
+            .into_iter()
+            .map(|resource| format!("{} {resource:?}", bank.get(resource)))
+            .join(", ")
+    );
+}
 pub fn setup_players_resources(mut commands: Commands<'_, '_>, layout: Res<'_, Layout>) {
     let children = children![
         resource_slider(&mut commands, resources::Resource::Wood),
@@ -245,7 +316,8 @@ pub fn setup_players_resources(mut commands: Commands<'_, '_>, layout: Res<'_, L
             Node {
                 display: Display::Grid,
                 grid_template_columns: vec![
-                    GridTrack::percent(70.),
+                    GridTrack::percent(55.),
+                    GridTrack::percent(15.),
                     GridTrack::percent(10.),
                     GridTrack::percent(10.),
                     GridTrack::percent(10.)
@@ -278,6 +350,20 @@ pub fn setup_players_resources(mut commands: Commands<'_, '_>, layout: Res<'_, L
                     },
                     Text::new("x")
                 ),
+                (
+                    Button,
+                    Node {
+                        display: Display::Grid,
+                        ..default()
+                    },
+                    TextFont {
+                        font_size: 10.,
+                        ..default()
+                    },
+                    TradeTargetButton,
+                    // who the trade is for
+                    Text::new("all")
+                ),
                 (
                     Button,
                     Node {
@@ -322,6 +408,14 @@ pub fn setup_players_resources(mut commands: Commands<'_, '_>, layout: Res<'_, L
                 ..default()
             },
             children
+        ),
+        (
+            BankText,
+            TextFont {
+                font_size: 10.,
+                ..default()
+            },
+            Text::new("")
         )
     ],));
 }
@@ -416,6 +510,20 @@ impl SubAssign<TradingResources> for Resources {
         *self = *self - rhs;
     }
 }
+// the same trade from the other players point of view
+impl Neg for TradingResources {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            wood: -self.wood,
+            brick: -self.brick,
+            sheep: -self.sheep,
+            wheat: -self.wheat,
+            ore: -self.ore,
+        }
+    }
+}
 
 impl fmt::Display for TradingResources {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -455,23 +563,17 @@ impl TradingResources {
         }
     }
 
-    fn given_and_taken(
+    pub fn given_and_taken(
         &self,
     ) -> (
         Vec<(resources::Resource, i8)>,
         Vec<(resources::Resource, i8)>,
     ) {
-        [
-            resources::Resource::Wood,
-            resources::Resource::Brick,
-            resources::Resource::Sheep,
-            resources::Resource::Wheat,
-            resources::Resource::Ore,
-        ]
-        .map(|r| (r, self.get(r)))
-        .into_iter()
-        .filter(|(_, count)| *count != 0)
-        .partition(|(_, count)| *count < 0)
+        resources::Resource::ALL
+            .map(|r| (r, self.get(r)))
+            .into_iter()
+            .filter(|(_, count)| *count != 0)
+            .partition(|(_, count)| *count < 0)
     }
 }
 #[derive(Debug, Component, Clone, Copy)]
@@ -480,7 +582,8 @@ pub struct TradingResourceSpinner(resources::Resource);
 #[derive(SystemParam)]
 struct TradingSpinnerState<'w, 's> {
     trading_resources: ResMut<'w, TradingResources>,
-    current_color: Res<'w, CurrentColor>,
+    // counter offers are made by the other players too
+    local_player: Res<'w, LocalPlayer>,
     player_resources_q: Query<'w, 's, &'static Resources, With<CatanColor>>,
 }
 
@@ -496,7 +599,7 @@ impl SpinnerButtonInteraction<TradingResourceSpinner> for TradingSpinnerState<'_
         current_value > 0
             || self
                 .player_resources_q
-                .get(self.current_color.0.entity)
+                .get(self.local_player.0.entity)
                 .is_ok_and(|r| r.get(resource.0) > current_value.unsigned_abs())
     }
 }
@@ -518,12 +621,21 @@ pub struct ResourceManagmentPlugin;
 impl Plugin for ResourceManagmentPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(TradingResources::default())
+            .init_resource::<TradeTarget>()
             .add_systems(OnEnter(GameState::Start), setup_players_resources)
             .add_systems(
                 Update,
                 show_player_resources.run_if(in_state(AppState::InGame)),
             )
             .add_systems(Update, show_player_trade)
+            .add_systems(
+                Update,
+                show_bank.run_if(
+                    in_state(AppState::InGame).and(
+                        resource_changed::<Resources>.or(any_match_filter::<Added<BankText>>),
+                    ),
+                ),
+            )
             .add_systems(OnEnter(GameState::Roll), show_player_resources)
             .add_systems(OnEnter(GameState::Roll), reset_trading_resources)
             .add_systems(
@@ -532,27 +644,43 @@ impl Plugin for ResourceManagmentPlugin {
                     TradingResourceSpinner,
                     TradingSpinnerState<'_, '_>,
                 >(),)
-                    .run_if(in_state(GameState::Turn)),
+                    .run_if(in_state(GameState::Turn).or(in_state(GameState::NotActive))),
             )
             .add_systems(
                 Update,
                 (common_ui::button_system_with_generic::<TradeButton, TradeState<'_>>,)
-                    .run_if(in_state(GameState::Turn)),
+                    .run_if(in_state(GameState::Turn).and(not(special_building))),
             )
             .add_systems(
                 Update,
                 (common_ui::button_system_with_generic::<BankTradeButton, BankTradeState<'_, '_>>,)
-                    .run_if(in_state(GameState::Turn)),
+                    .run_if(in_state(GameState::Turn).and(not(special_building))),
             )
             .add_systems(
                 Update,
-                (accept_trade_interaction_current, reject_trade_interaction)
-                    .run_if(in_state(GameState::Turn)),
+                (
+                    common_ui::button_system_with_generic::<
+                        TradeTargetButton,
+                        TradeTargetState<'_>,
+                    >,
+                    show_trade_target.run_if(resource_changed::<TradeTarget>),
+                )
+                    .run_if(in_state(GameState::Turn).and(not(special_building))),
             )
             .add_systems(
                 Update,
-                (accept_trade_interaction, reject_trade_interaction)
-                    .run_if(in_state(GameState::NotActive)),
+                common_ui::button_system_with_generic::<
+                    TradeOfferButton,
+                    TradeOfferState<'_, '_>,
+                >
+                    .run_if(in_state(GameState::Turn).or(in_state(GameState::NotActive))),
+            )
+            .add_systems(
+                Update,
+                draw_trades.run_if(
+                    in_state(AppState::InGame)
+                        .and(resource_changed::<Rules>.or(resource_changed::<LocalPlayer>)),
+                ),
             )
             .add_systems(
                 Update,
@@ -560,7 +688,7 @@ impl Plugin for ResourceManagmentPlugin {
                     TradingResourceResetButton,
                     ResMut<'_, TradingResources>,
                 >,)
-                    .run_if(in_state(GameState::Turn)),
+                    .run_if(in_state(GameState::Turn).or(in_state(GameState::NotActive))),
             );
     }
 }
@@ -1,43 +1,29 @@
-use bevy::{
-    ecs::{query::QueryData, system::SystemParam},
-    prelude::*,
-};
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_ui_anchor::{AnchorPoint, AnchorUiConfig, AnchoredUiNodes};
-use itertools::Itertools;
 
 use crate::{game::PlaceButton, utils::NORMAL_BUTTON};
 
 use super::{
-    BoardSize, Building, GameState, Input, KatanComponent, Left, UI,
+    GameState, Input, KatanComponent, Left, Rules, UI,
     colors::{CatanColor, CurrentColor},
     common_ui::ButtonInteraction,
     development_card_actions::RoadBuildingState,
-    positions::{self, BuildingPosition, Coordinate, Position, RoadPosition},
+    positions::{Coordinate, RoadPosition},
     resources::{ROAD_RESOURCES, Resources},
-    towns::{buildings_on_road, check_no_touching_buildings},
 };
 
-#[derive(QueryData, Debug, Clone, Copy)]
-pub struct RoadQuery(
-    pub &'static Road,
-    pub &'static CatanColor,
-    pub &'static RoadPosition,
-);
-
 #[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent, PlaceButton)]
-pub struct RoadPlaceButton(Resources, RoadPosition);
-#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct RoadPlaceButton(RoadPosition);
+#[derive(Debug, Component, Clone, Copy, Hash, Default)]
 #[require(KatanComponent)]
 pub struct Road;
 /// if `RESOURCE_MULTIPLIER` is zero then its free (default is 1, normal price)
 pub fn place_normal_road<const RESOURCE_MULTIPLIER: u8>(
     mut commands: Commands<'_, '_>,
     color_r: Res<'_, CurrentColor>,
-    size_r: Res<'_, BoardSize>,
     road_free_q: Query<'_, '_, &Left<Road>, With<CatanColor>>,
-    road_q: Query<'_, '_, RoadQuery>,
-    building_q: Query<'_, '_, (&'_ Building, &CatanColor, &'_ BuildingPosition)>,
+    rules: Res<'_, Rules>,
     mut game_state: ResMut<'_, NextState<GameState>>,
 ) {
     let unplaced_roads_correct_color = road_free_q.get(color_r.0.entity).ok();
@@ -47,76 +33,14 @@ pub fn place_normal_road<const RESOURCE_MULTIPLIER: u8>(
         return;
     };
 
-    let (current_color_roads, _): (Vec<_>, Vec<_>) =
-        road_q.into_iter().partition(|r| *r.1 == color_r.0.color);
-
-    // we don't check current color roads is empty b/c by iterating over them we are essentially
-    // doing that already
-    // roads are between two hexes (if one coordiante is the same
-    // if q same then its flat (assuming hex is flat)
-    // if r is same then its diagonol like '\'
-    // if s is same then its diagonol like '/'
-    // if there is a new place to put road down
-    // 1) the new hex has to share one coordianate with one hex and another differenet one with the
-    //    other hex (more constraint (i.e cannot be 50 square of in another direction)
-    let possibles_roads = current_color_roads
-        .into_iter()
-        .flat_map(|RoadQueryItem(_, _, road)| {
-            match road {
-                RoadPosition::Both(p1, p2, _) => {
-                    let (p3, p4) = road.neighboring_two(Some(size_r.0));
-                    let make_road_pos = |p, option_p1: Option<_>, p2: &Position| {
-                        option_p1.and_then(|p1| {
-                            RoadPosition::new(p, p1, Some(size_r.0)).map(|r| (*p2, r))
-                        })
-                    };
-                    [
-                        (
-                            // the other point (used to check for towns/cities)
-                            // the postion of the road
-                            make_road_pos(*p2, p3, p1)
-                        ),
-                        (make_road_pos(*p2, p4, p1)),
-                        (make_road_pos(*p1, p3, p2)),
-                        (make_road_pos(*p1, p4, p2)),
-                    ]
-                    .into_iter()
-                    .flatten()
-                }
-            }
-        });
-
-    // 2) make sure that there is no road already there (whether that color or not)
-    let possible_roads =
-        possibles_roads.filter(|(_, r)| !road_q.iter().any(|RoadQueryItem(_, _, r1)| r == r1));
-
-    // 3) make sure there is no differeent color town at the three itersection
-    // partition into other color used towns with single partiton
-    fn filter_by_building<'a>(
-        (road1, road2): &(Position, RoadPosition),
-        mut building_q: impl Iterator<Item = &'a BuildingPosition>,
-    ) -> bool {
-        let road_intersection = match road2 {
-            RoadPosition::Both(position, position1, _) => unsafe {
-                BuildingPosition::new_unchecked(*road1, *position, *position1)
-            },
-        };
-        !building_q.any(|bp| &road_intersection == bp)
-    }
-    let possible_roads = possible_roads.filter(|r| {
-        filter_by_building(
-            r,
-            building_q
-                .iter()
-                .filter_map(|(_, color, pos)| Some(pos).filter(|_| *color != color_r.0.color)),
-        )
-    });
+    let possible_roads = rules.road_placements(color_r.0.handle);
 
     let multiplier = 3.0;
     let count = possible_roads
+        .into_iter()
         .filter_map(|p| {
-            let (x, y) = p.1.positon_to_pixel_coordinates();
-            (x != 0. || y != 0.).then_some((x, y, p.1))
+            let (x, y) = p.positon_to_pixel_coordinates();
+            (x != 0. || y != 0.).then_some((x, y, p))
         })
         .map(|(x, y, p)| {
             (
@@ -133,7 +57,7 @@ pub fn place_normal_road<const RESOURCE_MULTIPLIER: u8>(
                         height: Val::VMin(2.0),
                         ..default()
                     },
-                    RoadPlaceButton(ROAD_RESOURCES * RESOURCE_MULTIPLIER, p),
+                    RoadPlaceButton(p),
                     BorderRadius::MAX,
                     BackgroundColor(NORMAL_BUTTON),
                 )),
@@ -143,38 +67,22 @@ pub fn place_normal_road<const RESOURCE_MULTIPLIER: u8>(
             commands.spawn(b);
         })
         .count();
-    if count == 0 {
+    // with the road building card there might still be ships to place
+    if count == 0
+        && (RESOURCE_MULTIPLIER != 0 || rules.ship_placements(color_r.0.handle).is_empty())
+    {
         game_state.set(GameState::Turn);
     }
 }
-fn get_setup_road_placements(
-    size_r: Res<'_, BoardSize>,
-    road_q: Query<'_, '_, RoadQuery>,
-
-    building_q: Query<'_, '_, (&'_ Building, &CatanColor, &'_ BuildingPosition)>,
-) -> impl Iterator<Item = RoadPosition> {
-    let size = BoardSize(size_r.0);
-    // generate all road possobilties
-    // generate the ring around it for edge roads
-    positions::generate_postions(4)
-        .array_combinations::<2>()
-        .filter_map(move |[p1, p2]| RoadPosition::new(p1, p2, Some(size_r.0)))
-        // filter out ones that are already placed
-        .filter(move |road| !road_q.iter().map(|r| r.2).contains(road))
-        // only show road if town can placed near it
-        .filter(move |r| {
-            buildings_on_road(size, *r).any(|b| check_no_touching_buildings(&b, building_q, size.0))
-        })
-}
 pub fn place_setup_road(
     mut commands: Commands<'_, '_>,
-    size_r: Res<'_, BoardSize>,
-    road_q: Query<'_, '_, RoadQuery>,
-    building_q: Query<'_, '_, (&'_ Building, &CatanColor, &'_ BuildingPosition)>,
+    rules: Res<'_, Rules>,
     mut game_state: ResMut<'_, NextState<GameState>>,
 ) {
     let multiplier = 3.0;
-    let count = get_setup_road_placements(size_r, road_q, building_q)
+    let count = rules
+        .setup_road_placements()
+        .into_iter()
         .filter_map(|p| {
             let (x, y) = p.positon_to_pixel_coordinates();
             (x != 0. || y != 0.).then_some((x, y, p))
@@ -194,7 +102,7 @@ pub fn place_setup_road(
                         height: Val::VMin(2.0),
                         ..default()
                     },
-                    RoadPlaceButton(Resources::default(), p),
+                    RoadPlaceButton(p),
                     BorderRadius::MAX,
                     BackgroundColor(NORMAL_BUTTON),
                 )),
@@ -253,7 +161,7 @@ pub struct PlaceRoadButtonState<'w> {
     substate: Option<Res<'w, State<RoadBuildingState>>>,
 }
 impl ButtonInteraction<RoadPlaceButton> for PlaceRoadButtonState<'_> {
-    fn interact(&mut self, RoadPlaceButton(cost, position): &RoadPlaceButton) {
+    fn interact(&mut self, RoadPlaceButton(position): &RoadPlaceButton) {
         let PlaceRoadButtonState {
             game_state,
             game_state_mut,
@@ -262,7 +170,7 @@ impl ButtonInteraction<RoadPlaceButton> for PlaceRoadButtonState<'_> {
             input,
         } = self;
 
-        **input = Input::AddRoad(*position, *cost);
+        **input = Input::AddRoad(*position);
         match *game_state.get() {
             GameState::Nothing
             | GameState::Monopoly
@@ -278,7 +186,15 @@ impl ButtonInteraction<RoadPlaceButton> for PlaceRoadButtonState<'_> {
             | GameState::PlaceCity
             | GameState::NotActive
             | GameState::NotActiveSetup
-            | GameState::RobberPickColor => {}
+            | GameState::RobberPickColor
+            | GameState::PlaceShip
+            | GameState::MoveShip
+            | GameState::PickGold
+            | GameState::PlaceKnight
+            | GameState::PromoteKnight
+            | GameState::ActivateKnight
+            | GameState::ChaseRobber
+            | GameState::CommodityMonopoly => {}
 
             GameState::PlaceRoad => {
                 game_state_mut.set(GameState::Turn);
@@ -8,18 +8,18 @@ use crate::{
 };
 
 use super::{
-    Building, GameState, Input, KatanComponent, LocalPlayer, PlayerHandle,
+    Building, GameState, Input, KatanComponent, LocalPlayer, PlayerEntities, PlayerHandle, Rules,
     colors::{CatanColor, CatanColorRef, CurrentColor},
     common_ui::{self, SpinnerButtonInteraction, Value},
-    positions::{BuildingPosition, FPosition, Position, generate_postions},
-    resources::{self, Resources, take_resource},
+    positions::{BuildingPosition, FPosition, Position},
+    resources::{self, Resources},
 };
 
 #[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
 #[require(KatanComponent)]
 // marker component to mark the 2d mesh that represent the robber
 pub struct RobberHighlighter;
-#[derive(Resource, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Resource, PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub struct Robber(pub Position);
 impl Default for Robber {
     fn default() -> Self {
@@ -41,11 +41,20 @@ pub fn counter_text_update(
 #[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
 #[require(KatanComponent)]
 pub struct RobberButton;
-pub fn place_robber(mut commands: Commands<'_, '_>, robber: Res<'_, Robber>) {
+// seafarers, the robber can be moved to the sea as the pirate instead
+#[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct PirateButton;
+pub fn place_robber(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+) {
     let multiplier = 3.0;
-    generate_postions(3)
-        // skip current robber pos
-        .filter(|p| *p != robber.0)
+    rules
+        // not the current robber pos (or next to someone the friendly robber leaves alone)
+        .robber_placements(color_r.0.handle)
+        .into_iter()
         .map(|p| {
             let pos: FPosition = p.into();
             let (x, y) = pos.hex_to_pixel();
@@ -74,6 +83,44 @@ pub fn place_robber(mut commands: Commands<'_, '_>, robber: Res<'_, Robber>) {
                 )),
             ));
         });
+    if let Some(pirate) = rules.pirate {
+        rules
+            .board
+            .sea()
+            .filter(|p| *p != pirate)
+            .map(|p| {
+                let pos: FPosition = p.into();
+                let (x, y) = pos.hex_to_pixel();
+                (x, y, p)
+            })
+            .for_each(|(x, y, p)| {
+                commands.spawn((
+                    Transform::from_xyz(x * multiplier * 25.6, y * multiplier * 25.6, 0.0),
+                    AnchoredUiNodes::spawn_one((
+                        AnchorUiConfig {
+                            anchorpoint: AnchorPoint::middle(),
+                            offset: None,
+                            ..Default::default()
+                        },
+                        Button,
+                        Node {
+                            width: Val::VMin(2.0),
+                            height: Val::VMin(2.0),
+                            ..default()
+                        },
+                        p,
+                        PirateButton,
+                        BorderRadius::MAX,
+                        BackgroundColor(NORMAL_BUTTON),
+                        Outline {
+                            width: Val::Px(2.),
+                            offset: Val::Px(0.),
+                            color: Color::BLACK,
+                        },
+                    )),
+                ));
+            });
+    }
 
     // show ui to place robber
     // on every hex besides for current robber hex
@@ -124,9 +171,98 @@ pub fn place_robber_interaction(
         }
     }
 }
+pub fn place_pirate_interaction(
+    mut pirate_places_query: Query<
+        '_,
+        '_,
+        (&Interaction, &Position, &mut Button, &mut BackgroundColor),
+        (Changed<Interaction>, With<PirateButton>),
+    >,
+    rules: Res<'_, Rules>,
+    players: PlayerEntities<'_, '_>,
+    current_color: Res<'_, CurrentColor>,
+    mut commands: Commands<'_, '_>,
+    mut state: ResMut<'_, NextState<GameState>>,
+    mut input: ResMut<'_, Input>,
+    still_needs_to_roll: Option<Res<'_, NeedToRoll>>,
+) {
+    for (interaction, position, mut button, mut color) in &mut pirate_places_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                button.set_changed();
+                // ships aren't buildings, so who is next to the pirate comes from the rules
+                let colors = rules
+                    .pirate_victims(current_color.0.handle, *position)
+                    .into_iter()
+                    .filter_map(|victim| players.get(victim))
+                    .collect_vec();
+                match colors[..] {
+                    [] => {
+                        *input = Input::MovePirate(*position);
+                        knight_next_time(&mut commands, &mut state, &still_needs_to_roll);
+                    }
+                    [victim] => {
+                        *input = Input::Pirate(victim.handle, *position);
+                        knight_next_time(&mut commands, &mut state, &still_needs_to_roll);
+                    }
+                    _ => {
+                        for (i, color) in colors.iter().enumerate() {
+                            spawn_choose_color_button(&mut commands, i, *color, *position, true);
+                        }
+                        state.set(GameState::RobberPickColor);
+                    }
+                }
+                break;
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                button.set_changed();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
 #[derive(Component)]
 #[require(KatanComponent)]
-pub struct RobberChooseColorButton;
+pub struct RobberChooseColorButton {
+    // the pirate takes from ships instead of buildings
+    pirate: bool,
+}
+fn spawn_choose_color_button(
+    commands: &mut Commands<'_, '_>,
+    i: usize,
+    color: CatanColorRef,
+    position: Position,
+    pirate: bool,
+) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::End,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        children![(
+            Button,
+            Node {
+                width: Val::Px(25.0),
+                height: Val::Px(25.0),
+                bottom: Val::Px(35.),
+                left: Val::Px((i * 30) as f32),
+                ..default()
+            },
+            RobberChooseColorButton { pirate },
+            color,
+            position,
+            BorderRadius::MAX,
+            BackgroundColor(color.to_bevy_color()),
+        )],
+    ));
+}
 fn choose_player_to_take_from(
     position: &Position,
     color: CurrentColor,
@@ -159,10 +295,7 @@ fn choose_player_to_take_from(
         .collect_vec();
     if colors.len() == 1 {
         let other_color = colors.remove(0);
-        let (_, other_color_resources, _) = player_resources.get(other_color.entity).unwrap();
-        if let Some(resource) = take_resource(other_color_resources) {
-            *input = Input::Knight(other_color.entity, resource, *position);
-        }
+        *input = Input::Knight(other_color.handle, *position);
 
         knight_next_time(&mut commands, &mut state, &still_needs_to_roll);
     } else if colors.is_empty() {
@@ -173,30 +306,7 @@ fn choose_player_to_take_from(
     } else {
         // show options of how to pick from
         for (i, color) in colors.iter().enumerate() {
-            commands.spawn((
-                Node {
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    align_items: AlignItems::End,
-                    justify_content: JustifyContent::Center,
-                    ..default()
-                },
-                children![(
-                    Button,
-                    Node {
-                        width: Val::Px(25.0),
-                        height: Val::Px(25.0),
-                        bottom: Val::Px(35.),
-                        left: Val::Px((i * 30) as f32),
-                        ..default()
-                    },
-                    RobberChooseColorButton,
-                    *color,
-                    *position,
-                    BorderRadius::MAX,
-                    BackgroundColor(color.to_bevy_color()),
-                )],
-            ));
+            spawn_choose_color_button(&mut commands, i, *color, *position, false);
         }
         state.set(GameState::RobberPickColor);
     }
@@ -216,7 +326,6 @@ fn knight_next_time(
 }
 
 pub fn choose_player_to_take_from_interaction(
-    player_resources: Query<'_, '_, (&CatanColor, &Resources)>,
     mut robber_taking_query: Query<
         '_,
         '_,
@@ -226,25 +335,27 @@ pub fn choose_player_to_take_from_interaction(
             &mut Button,
             &mut BackgroundColor,
             &Position,
+            &RobberChooseColorButton,
         ),
-        (Changed<Interaction>, With<RobberChooseColorButton>),
+        Changed<Interaction>,
     >,
     mut state: ResMut<'_, NextState<GameState>>,
     mut input: ResMut<'_, Input>,
     still_needs_to_roll: Option<Res<'_, NeedToRoll>>,
     mut commands: Commands<'_, '_>,
 ) {
-    for (interaction, color, mut button, mut button_color, new_robber_positon) in
+    for (interaction, color, mut button, mut button_color, new_robber_positon, choose) in
         &mut robber_taking_query
     {
         match *interaction {
             Interaction::Pressed => {
                 button.set_changed();
 
-                let (_, other_color_resources) = player_resources.get(color.entity).unwrap();
-                if let Some(resource) = take_resource(other_color_resources) {
-                    *input = Input::Knight(color.entity, resource, *new_robber_positon);
-                }
+                *input = if choose.pirate {
+                    Input::Pirate(color.handle, *new_robber_positon)
+                } else {
+                    Input::Knight(color.handle, *new_robber_positon)
+                };
                 // either we are coming from roll(7) or in middle of turn(dev card) but we always go back to
                 // turn
                 //
@@ -271,11 +382,12 @@ pub fn take_extra_resources(
     mut commands: Commands<'_, '_>,
     player_resources: Query<'_, '_, (Entity, &CatanColor, &mut Resources)>,
     local_player: Res<'_, LocalPlayer>,
+    rules: Res<'_, Rules>,
 ) {
     if let Some(r) = player_resources
         .get(local_player.0.entity)
         .ok()
-        .filter(|resources| resources.2.count() > 7)
+        .filter(|resources| resources.2.count() > rules.game_rules.discard_limit)
     {
         setup_take_extra_resources(&mut commands, *r.2, r.2.count() / 2);
     }
@@ -329,9 +441,18 @@ pub fn counter_sumbit_interaction(
 pub fn done_discarding(
     player_resources: Query<'_, '_, &mut Resources, With<CatanColor>>,
     mut mut_state: ResMut<'_, NextState<GameState>>,
+    rules: Res<'_, Rules>,
 ) {
-    if player_resources.iter().all(|r| r.count() <= 7) {
-        mut_state.set(GameState::PlaceRobber);
+    if player_resources
+        .iter()
+        .all(|r| r.count() <= rules.game_rules.discard_limit)
+    {
+        // with cities & knights the robber only moves after the first barbarian attack
+        mut_state.set(if rules.robber_active() {
+            GameState::PlaceRobber
+        } else {
+            GameState::Turn
+        });
     }
 }
 fn setup_take_extra_resources(
@@ -0,0 +1,2948 @@
+//! headless rules engine for the core game state
+//! holds the board, players, bank, development card pile and robber as plain data, so that a game
+//! can be simulated, tested or analysed without an `App`, the ecs side is just a view over this
+//! the seafarers rules are used for boards with sea on them, the cities & knights rules only when
+//! `GameState::cities_and_knights` is set
+use std::{collections::BTreeSet, fmt};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{CheckedAdd, CheckedSub};
+
+use super::{
+    Hexagon, Number, PlayerHandle, Port, VictoryPoints,
+    cities_and_knights::{
+        BARBARIAN_DISTANCE, CitiesAndKnights, EventDie, Improvements, KNIGHT_LEVELS,
+        KNIGHTS_PER_LEVEL, Knight, MAX_IMPROVEMENT, MIGHTY_KNIGHT_IMPROVEMENT, PROGRESS_CARD_LIMIT,
+        ProgressCard, Track,
+    },
+    colors::CatanColor,
+    development_cards::{DevelopmentCard, DevelopmentCards},
+    game_rules::{FRIENDLY_ROBBER_POINTS, GameRules},
+    longest_road::RoadGraph,
+    positions::{self, BuildingPosition, Position, RoadPosition},
+    resources::{
+        ACTIVATE_KNIGHT_RESOURCES, CITY_RESOURCES, Commodities, Commodity,
+        DEVELOPMENT_CARD_RESOURCES, KNIGHT_RESOURCES, ROAD_RESOURCES, Resource, Resources,
+        SHIP_RESOURCES, TOWN_RESOURCES,
+    },
+    resources_management::TradingResources,
+    setup_game::Ports,
+};
+
+pub const SHIPS: u8 = 15;
+// for the first town on an island that isn't one of the players home islands
+const ISLAND_POINTS: u8 = 2;
+const VICTORY_POINTS: u8 = 10;
+const CITIES_AND_KNIGHTS_VICTORY_POINTS: u8 = 13;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Player {
+    pub color: CatanColor,
+    pub resources: Resources,
+    pub development_cards: DevelopmentCards,
+    pub victory_points: VictoryPoints,
+    pub ports: Ports,
+    pub knights: u8,
+    pub roads_left: u8,
+    pub towns_left: u8,
+    pub cities_left: u8,
+    /// the players longest path (not if the player has longest road)
+    pub longest_road: BTreeSet<RoadPosition>,
+    // saves from before it get it rebuilt the first time its needed
+    #[serde(default)]
+    pub road_graph: RoadGraph,
+    #[serde(default = "ships")]
+    pub ships_left: u8,
+    /// indexes into `Board::islands` of every island the player has built on
+    #[serde(default)]
+    pub islands: Vec<usize>,
+    // cities & knights
+    #[serde(default)]
+    pub commodities: Commodities,
+    #[serde(default)]
+    pub improvements: Improvements,
+    #[serde(default)]
+    pub progress_cards: Vec<ProgressCard>,
+    /// development cards bought this turn, they can't be played until the next turn
+    #[serde(default)]
+    pub new_development_cards: DevelopmentCards,
+    // only one development card can be played a turn
+    #[serde(default)]
+    pub played_development_card: bool,
+}
+const fn ships() -> u8 {
+    SHIPS
+}
+impl Player {
+    pub fn new(color: CatanColor) -> Self {
+        Self {
+            color,
+            resources: Resources::new_player(),
+            development_cards: DevelopmentCards::new_player(),
+            victory_points: VictoryPoints::default(),
+            ports: Ports::new_player(),
+            knights: 0,
+            roads_left: 15,
+            towns_left: 5,
+            cities_left: 4,
+            longest_road: BTreeSet::new(),
+            road_graph: RoadGraph::default(),
+            ships_left: SHIPS,
+            islands: vec![],
+            commodities: Commodities::default(),
+            improvements: Improvements::default(),
+            progress_cards: vec![],
+            new_development_cards: DevelopmentCards::new_player(),
+            played_development_card: false,
+        }
+    }
+    pub const fn total_victory_points(&self) -> u8 {
+        self.victory_points.actual + self.victory_points.from_development_cards
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Board {
+    pub size: u8,
+    pub hexes: Vec<(Position, Hexagon, Number)>,
+    pub ports: Vec<(BuildingPosition, Port)>,
+}
+impl Board {
+    /// every hex the robber can be placed on
+    pub fn land(&self) -> impl Iterator<Item = Position> + '_ {
+        self.hexes
+            .iter()
+            .filter(|(_, hex, _)| !matches!(hex, Hexagon::Water | Hexagon::Port | Hexagon::Empty))
+            .map(|(position, _, _)| *position)
+    }
+    /// pieces have to be next to at least one land hex, so roads and towns along the coast are
+    /// fine but not ones out at sea (boards that aren't a hexagon have water inside the board size)
+    pub fn touches_land(&self, hexes: &[Position]) -> bool {
+        self.land().any(|land| hexes.contains(&land))
+    }
+    /// boards with sea or gold fields on them are played with the seafarers rules
+    pub fn seafarers(&self) -> bool {
+        self.hexes
+            .iter()
+            .any(|(_, hex, _)| matches!(hex, Hexagon::Water | Hexagon::Gold))
+    }
+    /// every hex the pirate can be placed on
+    pub fn sea(&self) -> impl Iterator<Item = Position> + '_ {
+        self.hexes
+            .iter()
+            .filter(|(_, hex, _)| matches!(hex, Hexagon::Water))
+            .map(|(position, _, _)| *position)
+    }
+    /// ships need sea on at least one side, the empty hexes around the board count as sea for
+    /// sailing along the coast but a ship can't be between two of them
+    pub fn ship_edge(&self, hexes: &[Position]) -> bool {
+        let sea = self.sea().any(|sea| hexes.contains(&sea));
+        let empty = hexes.iter().any(|position| {
+            self.hexes
+                .iter()
+                .find(|(hex, _, _)| hex == position)
+                .is_none_or(|(_, hex, _)| matches!(hex, Hexagon::Empty))
+        });
+        sea || (empty && self.touches_land(hexes))
+    }
+    /// land hexes that are connected to each other, in board order
+    pub fn islands(&self) -> Vec<Vec<Position>> {
+        let land = self.land().collect_vec();
+        let mut islands: Vec<Vec<Position>> = vec![];
+        for start in &land {
+            if islands.iter().any(|island| island.contains(start)) {
+                continue;
+            }
+            let mut island = vec![*start];
+            let mut stack = vec![*start];
+            while let Some(position) = stack.pop() {
+                for neighbor in Position::DIRECTION_VECTORS
+                    .iter()
+                    .map(|direction| position + *direction)
+                {
+                    if land.contains(&neighbor) && !island.contains(&neighbor) {
+                        island.push(neighbor);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            islands.push(island);
+        }
+        islands
+    }
+    /// which island a building is on
+    pub fn island(&self, building: BuildingPosition) -> Option<usize> {
+        self.islands()
+            .iter()
+            .position(|island| island.iter().any(|hex| building.contains(hex)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Piece {
+    Town,
+    City,
+}
+impl Piece {
+    // how many resources it gets from a hex
+    const fn multiplier(self) -> u8 {
+        match self {
+            Self::Town => 1,
+            Self::City => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Phase {
+    /// initial placement, `turn` indexes into the setup order (each player forward and then
+    /// backward), the road is placed first and then the town
+    Setup {
+        turn: usize,
+        road_placed: bool,
+    },
+    Roll,
+    /// players with to many cards after a 7 and how many they still have to discard
+    Discard(Vec<(PlayerHandle, u8)>),
+    PlaceRobber {
+        // knight played before rolling
+        then_roll: bool,
+    },
+    Turn,
+    /// with 5-6 players everyone else gets to build (and buy development cards) after a turn,
+    /// before the next player rolls
+    SpecialBuilding(PlayerHandle),
+    RoadBuilding(u8),
+    YearOfPlenty(u8),
+    /// also the resource monopoly progress card
+    Monopoly,
+    /// the trade monopoly progress card
+    CommodityMonopoly,
+    /// players with a building next to a gold field that produced and how many resources they
+    /// still get to pick
+    Gold(Vec<(PlayerHandle, u8)>),
+    Finished(PlayerHandle),
+}
+
+/// identifies a trade offer, they count up for the whole game so an old offer is never mistaken
+/// for a new one
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize,
+)]
+pub struct TradeId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TradeOffer {
+    pub id: TradeId,
+    pub from: PlayerHandle,
+    // `None` for everyone
+    pub to: Option<PlayerHandle>,
+    // from the current players point of view, whoever offered it
+    pub trade: TradingResources,
+}
+impl TradeOffer {
+    // only the current player trades, so the other side is whoever isn't the current player
+    const fn parties(&self, current: PlayerHandle) -> (PlayerHandle, Option<PlayerHandle>) {
+        if self.from.0 == current.0 {
+            (self.from, self.to)
+        } else {
+            (current, Some(self.from))
+        }
+    }
+    pub fn for_player(&self, player: PlayerHandle) -> bool {
+        self.from != player && self.to.is_none_or(|to| to == player)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PlaceRoad(RoadPosition),
+    PlaceTown(BuildingPosition),
+    PlaceCity(BuildingPosition),
+    BuyDevelopmentCard,
+    PlayDevelopmentCard(DevelopmentCard),
+    Roll(u8, u8),
+    Discard(Resources),
+    // where to, and who to take from and what (if there is anyone to take from)
+    MoveRobber(Position, Option<(PlayerHandle, Resource)>),
+    Monopoly(Resource),
+    YearOfPlenty(Resource),
+    BankTrade(TradingResources),
+    // trade between the current player and another player (from the current players point of view)
+    Trade(PlayerHandle, TradingResources),
+    // the current player offers a trade to someone (or everyone)
+    OfferTrade(Option<PlayerHandle>, TradingResources),
+    // answer an offer with another one, the same trade to take it as it is
+    CounterOffer(TradeId, TradingResources),
+    // only the one who offered it can take it back
+    CancelTrade(TradeId),
+    // the current player takes an offer made to them
+    AcceptTrade(TradeId),
+    EndTurn,
+    PlaceShip(RoadPosition),
+    // an open ship from where it is to where it goes
+    MoveShip(RoadPosition, RoadPosition),
+    // like the robber but on the sea, taking from players with ships next to it
+    MovePirate(Position, Option<(PlayerHandle, Resource)>),
+    PickGold(Resource),
+    // cities & knights, the dice and the event die
+    RollWithEvent(u8, u8, EventDie),
+    Improve(Track),
+    PlaceKnight(BuildingPosition),
+    PromoteKnight(BuildingPosition),
+    ActivateKnight(BuildingPosition),
+    // an active knight next to the robber sends it somewhere else
+    ChaseRobber(BuildingPosition),
+    PlayProgressCard(ProgressCard),
+    CommodityMonopoly(Commodity),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    RoadPlaced(PlayerHandle, RoadPosition),
+    TownPlaced(PlayerHandle, BuildingPosition),
+    CityPlaced(PlayerHandle, BuildingPosition),
+    DevelopmentCardBought(PlayerHandle, DevelopmentCard),
+    DevelopmentCardPlayed(PlayerHandle, DevelopmentCard),
+    Rolled(u8, u8),
+    Produced(PlayerHandle, Resources),
+    // the bank ran out of a resource so (some) players didn't get it
+    BankShortage(Resource),
+    Discarded(PlayerHandle, Resources),
+    RobberMoved(Position),
+    Stole {
+        thief: PlayerHandle,
+        victim: PlayerHandle,
+        resource: Resource,
+    },
+    Monopolized(PlayerHandle, Resource, u8),
+    YearOfPlenty(PlayerHandle, Resource),
+    BankTraded(PlayerHandle, TradingResources),
+    Traded(PlayerHandle, PlayerHandle, TradingResources),
+    TradeOffered(TradeOffer),
+    TradeCancelled(TradeId),
+    // someone no longer has what they would have to give
+    TradeInvalidated(TradeId),
+    LongestRoad(Option<PlayerHandle>),
+    LargestArmy(PlayerHandle),
+    NextPlayer(PlayerHandle),
+    SpecialBuilding(PlayerHandle),
+    Won(PlayerHandle),
+    // the winners victory point cards are shown to everyone
+    VictoryPointCardsRevealed(PlayerHandle, u8),
+    ShipPlaced(PlayerHandle, RoadPosition),
+    ShipMoved(PlayerHandle, RoadPosition, RoadPosition),
+    PirateMoved(Position),
+    PickedGold(PlayerHandle, Resource),
+    IslandDiscovered(PlayerHandle),
+    CommoditiesProduced(PlayerHandle, Commodities),
+    Improved(PlayerHandle, Track, u8),
+    KnightPlaced(PlayerHandle, BuildingPosition),
+    KnightPromoted(PlayerHandle, BuildingPosition),
+    KnightActivated(PlayerHandle, BuildingPosition),
+    RobberChased(PlayerHandle, BuildingPosition),
+    ProgressCardDrawn(PlayerHandle, ProgressCard),
+    ProgressCardPlayed(PlayerHandle, ProgressCard),
+    CommodityMonopolized(PlayerHandle, Commodity, u8),
+    BarbariansMoved(u8),
+    BarbariansAttacked {
+        strength: u8,
+        defence: u8,
+    },
+    DefenderOfCatan(PlayerHandle),
+    // a city back to a town
+    Pillaged(PlayerHandle, BuildingPosition),
+}
+pub type Events = Vec<Event>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleError {
+    GameOver,
+    UnknownPlayer,
+    NotYourTurn,
+    WrongPhase,
+    CannotAfford,
+    BankCannotAfford,
+    NoPiecesLeft,
+    IllegalPlacement,
+    NoDevelopmentCards,
+    DoesNotHaveCard,
+    InvalidDice,
+    InvalidDiscard,
+    InvalidRobber,
+    InvalidTrade,
+    InvalidPirate,
+    InvalidKnight,
+    InvalidImprovement,
+    AlreadyPlayedDevelopmentCard,
+    NewDevelopmentCard,
+    UnknownTrade,
+}
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::GameOver => "the game is over",
+            Self::UnknownPlayer => "no such player",
+            Self::NotYourTurn => "not this players turn",
+            Self::WrongPhase => "not allowed right now",
+            Self::CannotAfford => "not enough resources",
+            Self::BankCannotAfford => "not enough resources in the bank",
+            Self::NoPiecesLeft => "no pieces of that kind left",
+            Self::IllegalPlacement => "cannot place there",
+            Self::NoDevelopmentCards => "no development cards left",
+            Self::DoesNotHaveCard => "player does not have that development card",
+            Self::InvalidDice => "dice have to be between 1 and 6",
+            Self::InvalidDiscard => "wrong amount of resources discarded",
+            Self::InvalidRobber => "cannot move the robber there or take from that player",
+            Self::InvalidTrade => "invalid trade",
+            Self::InvalidPirate => "cannot move the pirate there or take from that player",
+            Self::InvalidKnight => "no knight there that can do that",
+            Self::InvalidImprovement => "cannot improve that any further (or without a city)",
+            Self::AlreadyPlayedDevelopmentCard => "already played a development card this turn",
+            Self::NewDevelopmentCard => "cannot play a development card the turn it was bought",
+            Self::UnknownTrade => "that trade is not offered (anymore)",
+        })
+    }
+}
+impl std::error::Error for RuleError {}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GameState {
+    pub board: Board,
+    pub players: Vec<Player>,
+    pub bank: Resources,
+    // top of the pile is the end
+    pub development_cards: Vec<DevelopmentCard>,
+    pub robber: Position,
+    pub roads: Vec<(PlayerHandle, RoadPosition)>,
+    pub buildings: Vec<(PlayerHandle, BuildingPosition, Piece)>,
+    pub phase: Phase,
+    pub current: PlayerHandle,
+    pub last_roll: Option<(u8, u8)>,
+    // start one below `GameRules::longest_road` so when someone gets there it will be updated
+    pub longest_road: (Option<PlayerHandle>, u8),
+    // same for `GameRules::largest_army`
+    pub largest_army: (Option<PlayerHandle>, u8),
+    // seafarers
+    #[serde(default)]
+    pub ships: Vec<(PlayerHandle, RoadPosition)>,
+    #[serde(default)]
+    pub pirate: Option<Position>,
+    // ships built this turn can't be moved, and only one ship can be moved a turn
+    #[serde(default)]
+    pub new_ships: Vec<RoadPosition>,
+    #[serde(default)]
+    pub ship_moved: bool,
+    // cities & knights, `None` when playing without it
+    #[serde(default)]
+    pub cities_and_knights: Option<CitiesAndKnights>,
+    // house rules, saves from before them were played with the usual rules
+    #[serde(default)]
+    pub game_rules: GameRules,
+    // open trade offers this turn
+    #[serde(default)]
+    pub trades: Vec<TradeOffer>,
+    #[serde(default)]
+    pub next_trade: TradeId,
+}
+
+impl GameState {
+    /// players are in turn order, so the colors index is the players handle
+    pub fn new(
+        board: Board,
+        colors: impl IntoIterator<Item = CatanColor>,
+        development_cards: Vec<DevelopmentCard>,
+    ) -> Self {
+        let players = colors.into_iter().map(Player::new).collect_vec();
+        // the 5-6 player extension comes with more of each resource
+        let bank = if players.len() > 4 {
+            Resources::new_extension_game()
+        } else {
+            Resources::new_game()
+        };
+        // robber starts on the desert
+        let robber = board
+            .hexes
+            .iter()
+            .find(|(_, hex, _)| matches!(hex, Hexagon::Desert))
+            .map_or(Position { q: 0, r: 0, s: 0 }, |(position, _, _)| *position);
+        // and the pirate on the first bit of sea (if there is any)
+        let pirate = board.sea().next();
+        Self {
+            board,
+            players,
+            bank,
+            development_cards,
+            robber,
+            roads: vec![],
+            buildings: vec![],
+            phase: Phase::Setup {
+                turn: 0,
+                road_placed: false,
+            },
+            current: PlayerHandle(0),
+            last_roll: None,
+            longest_road: (None, 4),
+            largest_army: (None, 2),
+            ships: vec![],
+            pirate,
+            new_ships: vec![],
+            ship_moved: false,
+            cities_and_knights: None,
+            game_rules: GameRules::default(),
+            trades: vec![],
+            next_trade: TradeId(0),
+        }
+    }
+
+    /// for a new game, the bank and when someone gets longest road or largest army depend on them
+    pub fn set_game_rules(&mut self, game_rules: GameRules) {
+        if let Some(bank) = game_rules.bank {
+            self.bank = Resources::new(bank, bank, bank, bank, bank);
+        }
+        // one less so the first one to get there gets it
+        self.longest_road = (None, game_rules.longest_road - 1);
+        self.largest_army = (None, game_rules.largest_army - 1);
+        self.game_rules = game_rules;
+    }
+
+    pub fn player(&self, handle: PlayerHandle) -> Option<&Player> {
+        self.players.get(handle.0)
+    }
+
+    /// in cities & knights the robber doesn't move until the barbarians attacked for the first time
+    pub fn robber_active(&self) -> bool {
+        self.cities_and_knights
+            .as_ref()
+            .is_none_or(|cities_and_knights| cities_and_knights.attacked)
+    }
+
+    pub const fn victory_points_to_win(&self) -> u8 {
+        match self.game_rules.victory_points {
+            Some(victory_points) => victory_points,
+            None if self.cities_and_knights.is_some() => CITIES_AND_KNIGHTS_VICTORY_POINTS,
+            None => VICTORY_POINTS,
+        }
+    }
+
+    /// who has to do something next (besides for discarding)
+    pub fn active_player(&self) -> Option<PlayerHandle> {
+        match self.phase {
+            Phase::Setup { turn, .. } => self.setup_player(turn),
+            Phase::SpecialBuilding(builder) => Some(builder),
+            Phase::Finished(_) => None,
+            _ => Some(self.current),
+        }
+    }
+
+    const fn setup_player(&self, turn: usize) -> Option<PlayerHandle> {
+        let count = self.players.len();
+        if turn < count {
+            Some(PlayerHandle(turn))
+        } else if turn < 2 * count {
+            Some(PlayerHandle(2 * count - 1 - turn))
+        } else {
+            None
+        }
+    }
+
+    fn expect_active(&self, player: PlayerHandle) -> Result<(), RuleError> {
+        (self.active_player() == Some(player))
+            .then_some(())
+            .ok_or(RuleError::NotYourTurn)
+    }
+
+    fn player_roads(&self, player: PlayerHandle) -> Vec<RoadPosition> {
+        self.roads
+            .iter()
+            .filter(|(owner, _)| *owner == player)
+            .map(|(_, road)| *road)
+            .collect()
+    }
+    fn player_ships(&self, player: PlayerHandle) -> Vec<RoadPosition> {
+        self.ships
+            .iter()
+            .filter(|(owner, _)| *owner == player)
+            .map(|(_, ship)| *ship)
+            .collect()
+    }
+    // roads and ships can't share an edge
+    fn all_roads(&self) -> Vec<RoadPosition> {
+        self.roads
+            .iter()
+            .chain(&self.ships)
+            .map(|(_, road)| *road)
+            .collect()
+    }
+    fn player_buildings(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        self.buildings
+            .iter()
+            .filter(|(owner, _, _)| *owner == player)
+            .map(|(_, building, _)| *building)
+            .collect()
+    }
+    fn all_buildings(&self) -> Vec<BuildingPosition> {
+        self.buildings
+            .iter()
+            .map(|(_, building, _)| *building)
+            .collect()
+    }
+    // buildings that break up a players roads
+    fn other_buildings(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        self.buildings
+            .iter()
+            .filter(|(owner, _, _)| *owner != player)
+            .map(|(_, building, _)| *building)
+            .collect()
+    }
+
+    fn road_on_land(&self, road: RoadPosition) -> bool {
+        match road {
+            RoadPosition::Both(p1, p2, _) => self.board.touches_land(&[p1, p2]),
+        }
+    }
+    fn building_on_land(&self, building: BuildingPosition) -> bool {
+        match building {
+            BuildingPosition::All(p1, p2, p3) => self.board.touches_land(&[p1, p2, p3]),
+        }
+    }
+    // the pirate blocks every edge around it
+    fn ship_on_sea(&self, ship: RoadPosition) -> bool {
+        match ship {
+            RoadPosition::Both(p1, p2, _) => {
+                self.board.ship_edge(&[p1, p2])
+                    && self
+                        .pirate
+                        .is_none_or(|pirate| pirate != p1 && pirate != p2)
+            }
+        }
+    }
+
+    pub fn setup_road_placements(&self) -> Vec<RoadPosition> {
+        setup_road_placements(self.board.size, &self.all_roads(), &self.all_buildings())
+            .into_iter()
+            .filter(|road| self.road_on_land(*road))
+            .collect()
+    }
+    pub fn road_placements(&self, player: PlayerHandle) -> Vec<RoadPosition> {
+        let size = self.board.size;
+        let roads = self.all_roads();
+        distinct(
+            possible_road_placements(
+                size,
+                &self.player_roads(player),
+                &roads,
+                &self.other_buildings(player),
+            )
+            .into_iter()
+            // a town reached by ship doesn't have any roads yet
+            .chain(
+                self.player_buildings(player)
+                    .into_iter()
+                    .flat_map(|building| roads_around(size, building))
+                    .filter(|road| !roads.contains(road)),
+            )
+            .filter(|road| self.road_on_land(*road)),
+        )
+    }
+    pub fn town_placements(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        let mut routes = self.player_roads(player);
+        routes.extend(self.player_ships(player));
+        let knights = self.knight_positions();
+        possible_town_placements(self.board.size, &routes, &self.all_buildings())
+            .into_iter()
+            .filter(|building| self.building_on_land(*building) && !knights.contains(building))
+            .collect()
+    }
+    /// empty when not playing seafarers
+    pub fn ship_placements(&self, player: PlayerHandle) -> Vec<RoadPosition> {
+        if !self.board.seafarers() {
+            return vec![];
+        }
+        self.ship_placements_from(player, &self.player_ships(player))
+    }
+    // ships go from the players buildings or the end of their ships, like roads they can't go
+    // through another players building
+    fn ship_placements_from(
+        &self,
+        player: PlayerHandle,
+        own_ships: &[RoadPosition],
+    ) -> Vec<RoadPosition> {
+        let size = self.board.size;
+        let roads = self.all_roads();
+        let other_buildings = self.other_buildings(player);
+        distinct(
+            own_ships
+                .iter()
+                .flat_map(|ship| buildings_on_road(size, *ship))
+                .filter(|end| !other_buildings.contains(end))
+                .chain(self.player_buildings(player))
+                .flat_map(|end| roads_around(size, end))
+                .filter(|ship| !roads.contains(ship) && self.ship_on_sea(*ship)),
+        )
+    }
+    /// every open ship (one at the end of a line of ships) and where it can be moved to, ships
+    /// built this turn can't be moved and only one ship can be moved a turn
+    pub fn ship_moves(&self, player: PlayerHandle) -> Vec<(RoadPosition, RoadPosition)> {
+        if self.ship_moved {
+            return vec![];
+        }
+        let size = self.board.size;
+        let ships = self.player_ships(player);
+        let buildings = self.player_buildings(player);
+        let open = |ship: &RoadPosition| {
+            buildings_on_road(size, *ship).any(|end| {
+                !buildings.contains(&end)
+                    && !ships.iter().any(|other| {
+                        other != ship && buildings_on_road(size, *other).contains(&end)
+                    })
+            })
+        };
+        ships
+            .iter()
+            .filter(|ship| !self.new_ships.contains(ship) && self.ship_on_sea(**ship) && open(ship))
+            .flat_map(|ship| {
+                let rest = ships
+                    .iter()
+                    .filter(|other| *other != ship)
+                    .copied()
+                    .collect_vec();
+                self.ship_placements_from(player, &rest)
+                    .into_iter()
+                    .map(move |to| (*ship, to))
+            })
+            .collect()
+    }
+    pub fn city_placements(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        self.buildings
+            .iter()
+            .filter(|(owner, _, piece)| *owner == player && *piece == Piece::Town)
+            .map(|(_, building, _)| *building)
+            .collect()
+    }
+    fn player_cities(&self, player: PlayerHandle) -> usize {
+        self.buildings
+            .iter()
+            .filter(|(owner, _, piece)| *owner == player && *piece == Piece::City)
+            .count()
+    }
+
+    // empty when not playing cities & knights
+    fn knights(&self) -> &[Knight] {
+        self.cities_and_knights
+            .as_ref()
+            .map_or(&[], |cities_and_knights| &cities_and_knights.knights)
+    }
+    fn knight_positions(&self) -> Vec<BuildingPosition> {
+        self.knights()
+            .iter()
+            .map(|knight| knight.position)
+            .collect()
+    }
+    fn player_knight(&self, player: PlayerHandle, position: BuildingPosition) -> Option<&Knight> {
+        self.knights()
+            .iter()
+            .find(|knight| knight.owner == player && knight.position == position)
+    }
+    // there are only two knights of each level for every player
+    fn knights_left(&self, player: PlayerHandle, level: u8) -> bool {
+        self.knights()
+            .iter()
+            .filter(|knight| knight.owner == player && knight.level == level)
+            .count()
+            < KNIGHTS_PER_LEVEL
+    }
+    /// free intersections along the players roads, knights don't have to be apart like towns
+    pub fn knight_placements(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        if self.cities_and_knights.is_none() || !self.knights_left(player, 1) {
+            return vec![];
+        }
+        let taken = self
+            .all_buildings()
+            .into_iter()
+            .chain(self.knight_positions())
+            .collect_vec();
+        self.player_roads(player)
+            .into_iter()
+            .flat_map(|road| buildings_on_road(self.board.size, road))
+            .filter(|building| !taken.contains(building) && self.building_on_land(*building))
+            .unique()
+            .collect()
+    }
+    /// knights that can go up a level, mighty knights need a fortress
+    pub fn knight_promotions(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        let this = &self.players[player.0];
+        self.knights()
+            .iter()
+            .filter(|knight| {
+                knight.owner == player
+                    && knight.level < KNIGHT_LEVELS
+                    && self.knights_left(player, knight.level + 1)
+                    && (knight.level + 1 < KNIGHT_LEVELS
+                        || this.improvements.politics >= MIGHTY_KNIGHT_IMPROVEMENT)
+            })
+            .map(|knight| knight.position)
+            .collect()
+    }
+    pub fn knight_activations(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        self.knights()
+            .iter()
+            .filter(|knight| knight.owner == player && !knight.active)
+            .map(|knight| knight.position)
+            .collect()
+    }
+    /// active knights next to the robber (once the robber can be moved)
+    pub fn robber_chases(&self, player: PlayerHandle) -> Vec<BuildingPosition> {
+        if !self.robber_active() {
+            return vec![];
+        }
+        self.knights()
+            .iter()
+            .filter(|knight| {
+                knight.owner == player && knight.active && knight.position.contains(&self.robber)
+            })
+            .map(|knight| knight.position)
+            .collect()
+    }
+    /// improvements the player has the commodities for, they need at least one city
+    pub fn improvements(&self, player: PlayerHandle) -> Vec<Track> {
+        let this = &self.players[player.0];
+        if self.cities_and_knights.is_none() || self.player_cities(player) == 0 {
+            return vec![];
+        }
+        Track::ALL
+            .into_iter()
+            .filter(|track| {
+                let level = this.improvements.get(*track);
+                level < MAX_IMPROVEMENT && this.commodities.get(track.commodity()) > level
+            })
+            .collect()
+    }
+
+    /// other players with a ship next to the hex that have something to take
+    pub fn pirate_victims(&self, player: PlayerHandle, position: Position) -> Vec<PlayerHandle> {
+        self.ships
+            .iter()
+            .filter(|(owner, ship)| {
+                *owner != player
+                    && match ship {
+                        RoadPosition::Both(p1, p2, _) => *p1 == position || *p2 == position,
+                    }
+                    && self.players[owner.0].resources.count() > 0
+            })
+            .map(|(owner, _)| *owner)
+            .unique()
+            .collect()
+    }
+    // every way to take from one of the victims
+    fn steals(&self, victims: Vec<PlayerHandle>) -> Vec<Option<(PlayerHandle, Resource)>> {
+        if victims.is_empty() {
+            return vec![None];
+        }
+        victims
+            .into_iter()
+            .flat_map(|victim| {
+                let resources = self.players[victim.0].resources;
+                Resource::ALL
+                    .into_iter()
+                    .filter(move |resource| resources.get(*resource) > 0)
+                    .map(move |resource| Some((victim, resource)))
+            })
+            .collect()
+    }
+
+    /// every hex the robber can be moved to, with the friendly robber it can't go next to players
+    /// with too few points (unless thats every hex)
+    pub fn robber_placements(&self, player: PlayerHandle) -> Vec<Position> {
+        let placements = self
+            .board
+            .land()
+            .filter(|position| *position != self.robber)
+            .collect_vec();
+        if !self.game_rules.friendly_robber {
+            return placements;
+        }
+        let friendly = placements
+            .iter()
+            .copied()
+            .filter(|position| {
+                !self.buildings.iter().any(|(owner, building, _)| {
+                    *owner != player
+                        && building.contains(position)
+                        && self.players[owner.0].victory_points.actual <= FRIENDLY_ROBBER_POINTS
+                })
+            })
+            .collect_vec();
+        if friendly.is_empty() {
+            placements
+        } else {
+            friendly
+        }
+    }
+
+    /// other players with a building on the hex that have something to take
+    pub fn robber_victims(&self, player: PlayerHandle, position: Position) -> Vec<PlayerHandle> {
+        self.buildings
+            .iter()
+            .filter(|(owner, building, _)| {
+                *owner != player
+                    && building.contains(&position)
+                    && self.players[owner.0].resources.count() > 0
+            })
+            .map(|(owner, _, _)| *owner)
+            .unique()
+            .collect()
+    }
+
+    /// every action this player could do right now, trades with other players are not included as
+    /// they need the other player to agree
+    pub fn legal_actions(&self, player: PlayerHandle) -> Vec<Action> {
+        let Some(this) = self.player(player) else {
+            return vec![];
+        };
+        if let Phase::Discard(pending) = &self.phase {
+            return pending
+                .iter()
+                .find(|(handle, _)| *handle == player)
+                .map(|(_, amount)| {
+                    discards(this.resources, *amount)
+                        .into_iter()
+                        .map(Action::Discard)
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        if let Phase::Gold(pending) = &self.phase {
+            if !pending.iter().any(|(handle, _)| *handle == player) {
+                return vec![];
+            }
+            return Resource::ALL
+                .into_iter()
+                .filter(|resource| self.bank.get(*resource) > 0)
+                .map(Action::PickGold)
+                .collect();
+        }
+        if self.active_player() != Some(player) {
+            return vec![];
+        }
+        match &self.phase {
+            Phase::Setup {
+                road_placed: false, ..
+            } => self
+                .setup_road_placements()
+                .into_iter()
+                .map(Action::PlaceRoad)
+                .collect(),
+            Phase::Setup {
+                road_placed: true, ..
+            } => self
+                .town_placements(player)
+                .into_iter()
+                .map(Action::PlaceTown)
+                .collect(),
+            Phase::Roll if self.cities_and_knights.is_some() => (1..=6)
+                .cartesian_product(1..=6)
+                .cartesian_product(EventDie::ALL)
+                .map(|((d1, d2), event)| Action::RollWithEvent(d1, d2, event))
+                .collect(),
+            Phase::Roll => (1..=6)
+                .cartesian_product(1..=6)
+                .map(|(d1, d2)| Action::Roll(d1, d2))
+                .chain(
+                    self.can_play_development_card(player, DevelopmentCard::Knight)
+                        .then_some(Action::PlayDevelopmentCard(DevelopmentCard::Knight)),
+                )
+                .collect(),
+            Phase::PlaceRobber { .. } => self
+                .robber_placements(player)
+                .into_iter()
+                .flat_map(|position| {
+                    self.steals(self.robber_victims(player, position))
+                        .into_iter()
+                        .map(move |steal| Action::MoveRobber(position, steal))
+                })
+                .chain(
+                    self.board
+                        .sea()
+                        .filter(|position| self.pirate.is_some_and(|pirate| pirate != *position))
+                        .flat_map(|position| {
+                            self.steals(self.pirate_victims(player, position))
+                                .into_iter()
+                                .map(move |steal| Action::MovePirate(position, steal))
+                        }),
+                )
+                .collect(),
+            Phase::Turn => self.turn_actions(player, this),
+            Phase::SpecialBuilding(_) => {
+                let mut actions = self.build_actions(player, this);
+                actions.push(Action::EndTurn);
+                actions
+            }
+            Phase::RoadBuilding(_) => {
+                let mut actions = vec![];
+                if this.roads_left > 0 {
+                    actions.extend(
+                        self.road_placements(player)
+                            .into_iter()
+                            .map(Action::PlaceRoad),
+                    );
+                }
+                if this.ships_left > 0 {
+                    actions.extend(
+                        self.ship_placements(player)
+                            .into_iter()
+                            .map(Action::PlaceShip),
+                    );
+                }
+                actions
+            }
+            Phase::YearOfPlenty(_) => Resource::ALL
+                .into_iter()
+                .filter(|resource| self.bank.get(*resource) > 0)
+                .map(Action::YearOfPlenty)
+                .collect(),
+            Phase::Monopoly => Resource::ALL.into_iter().map(Action::Monopoly).collect(),
+            Phase::CommodityMonopoly => Commodity::ALL
+                .into_iter()
+                .map(Action::CommodityMonopoly)
+                .collect(),
+            Phase::Discard(_) | Phase::Gold(_) | Phase::Finished(_) => vec![],
+        }
+    }
+
+    // everything that can be built (or bought), during a turn or the special building phase
+    fn build_actions(&self, player: PlayerHandle, this: &Player) -> Vec<Action> {
+        let mut actions = vec![];
+        if this.roads_left > 0 && this.resources.contains(ROAD_RESOURCES) {
+            actions.extend(
+                self.road_placements(player)
+                    .into_iter()
+                    .map(Action::PlaceRoad),
+            );
+        }
+        if this.ships_left > 0 && this.resources.contains(SHIP_RESOURCES) {
+            actions.extend(
+                self.ship_placements(player)
+                    .into_iter()
+                    .map(Action::PlaceShip),
+            );
+        }
+        if this.towns_left > 0 && this.resources.contains(TOWN_RESOURCES) {
+            actions.extend(
+                self.town_placements(player)
+                    .into_iter()
+                    .map(Action::PlaceTown),
+            );
+        }
+        if this.cities_left > 0 && this.resources.contains(CITY_RESOURCES) {
+            actions.extend(
+                self.city_placements(player)
+                    .into_iter()
+                    .map(Action::PlaceCity),
+            );
+        }
+        // cities & knights has progress cards instead
+        if self.cities_and_knights.is_none()
+            && !self.development_cards.is_empty()
+            && this.resources.contains(DEVELOPMENT_CARD_RESOURCES)
+        {
+            actions.push(Action::BuyDevelopmentCard);
+        }
+        if this.resources.contains(KNIGHT_RESOURCES) {
+            actions.extend(
+                self.knight_placements(player)
+                    .into_iter()
+                    .map(Action::PlaceKnight),
+            );
+            actions.extend(
+                self.knight_promotions(player)
+                    .into_iter()
+                    .map(Action::PromoteKnight),
+            );
+        }
+        if this.resources.contains(ACTIVATE_KNIGHT_RESOURCES) {
+            actions.extend(
+                self.knight_activations(player)
+                    .into_iter()
+                    .map(Action::ActivateKnight),
+            );
+        }
+        actions.extend(self.improvements(player).into_iter().map(Action::Improve));
+        actions
+    }
+
+    fn turn_actions(&self, player: PlayerHandle, this: &Player) -> Vec<Action> {
+        let mut actions = self.build_actions(player, this);
+        actions.extend(
+            [
+                DevelopmentCard::Knight,
+                DevelopmentCard::Monopoly,
+                DevelopmentCard::YearOfPlenty,
+                DevelopmentCard::RoadBuilding,
+            ]
+            .into_iter()
+            .filter(|card| self.can_play_development_card(player, *card))
+            .map(Action::PlayDevelopmentCard),
+        );
+        actions.extend(
+            this.progress_cards
+                .iter()
+                .filter(|card| self.can_play_progress_card(**card))
+                .unique()
+                .map(|card| Action::PlayProgressCard(*card)),
+        );
+        actions.extend(
+            self.robber_chases(player)
+                .into_iter()
+                .map(Action::ChaseRobber),
+        );
+        actions.extend(
+            self.ship_moves(player)
+                .into_iter()
+                .map(|(from, to)| Action::MoveShip(from, to)),
+        );
+        actions.extend(
+            Resource::ALL
+                .into_iter()
+                .cartesian_product(Resource::ALL)
+                .filter(|(giving, taking)| giving != taking)
+                .filter_map(|(giving, taking)| {
+                    let rate = this.ports.get_trade_rate(giving);
+                    (this.resources.get(giving) >= rate && self.bank.get(taking) > 0).then(|| {
+                        let mut trade = TradingResources::default();
+                        *trade.get_mut(giving) = -(rate as i8);
+                        *trade.get_mut(taking) = 1;
+                        Action::BankTrade(trade)
+                    })
+                }),
+        );
+        actions.push(Action::EndTurn);
+        actions
+    }
+
+    /// applies the action if its legal, if its not nothing is changed
+    pub fn apply(&mut self, player: PlayerHandle, action: Action) -> Result<Events, RuleError> {
+        if matches!(self.phase, Phase::Finished(_)) {
+            return Err(RuleError::GameOver);
+        }
+        if self.player(player).is_none() {
+            return Err(RuleError::UnknownPlayer);
+        }
+        let mut events = match action {
+            Action::PlaceRoad(road) => self.place_road(player, road),
+            Action::PlaceTown(town) => self.place_town(player, town),
+            Action::PlaceCity(city) => self.place_city(player, city),
+            Action::BuyDevelopmentCard => self.buy_development_card(player),
+            Action::PlayDevelopmentCard(card) => self.play_development_card(player, card),
+            Action::Roll(d1, d2) => self.roll(player, d1, d2),
+            Action::Discard(resources) => self.discard(player, resources),
+            Action::MoveRobber(position, steal) => self.move_robber(player, position, steal),
+            Action::Monopoly(resource) => self.monopoly(player, resource),
+            Action::YearOfPlenty(resource) => self.year_of_plenty(player, resource),
+            Action::BankTrade(trade) => self.bank_trade(player, trade),
+            Action::Trade(other, trade) => self.trade(player, other, trade),
+            Action::OfferTrade(to, trade) => self.offer_trade(player, to, trade),
+            Action::CounterOffer(id, trade) => self.counter_offer(player, id, trade),
+            Action::CancelTrade(id) => self.cancel_trade(player, id),
+            Action::AcceptTrade(id) => self.accept_trade(player, id),
+            Action::EndTurn => self.end_turn(player),
+            Action::PlaceShip(ship) => self.place_ship(player, ship),
+            Action::MoveShip(from, to) => self.move_ship(player, from, to),
+            Action::MovePirate(position, steal) => self.move_pirate(player, position, steal),
+            Action::PickGold(resource) => self.pick_gold(player, resource),
+            Action::RollWithEvent(d1, d2, event) => self.roll_with_event(player, d1, d2, event),
+            Action::Improve(track) => self.improve(player, track),
+            Action::PlaceKnight(position) => self.place_knight(player, position),
+            Action::PromoteKnight(position) => self.promote_knight(player, position),
+            Action::ActivateKnight(position) => self.activate_knight(player, position),
+            Action::ChaseRobber(position) => self.chase_robber(player, position),
+            Action::PlayProgressCard(card) => self.play_progress_card(player, card),
+            Action::CommodityMonopoly(commodity) => self.commodity_monopoly(player, commodity),
+        }?;
+        self.invalidate_trades(&mut events);
+        self.check_for_winner(&mut events);
+        Ok(events)
+    }
+
+    fn place_road(
+        &mut self,
+        player: PlayerHandle,
+        road: RoadPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.players[player.0].roads_left == 0 {
+            return Err(RuleError::NoPiecesLeft);
+        }
+        let cost = match self.phase {
+            Phase::Setup {
+                road_placed: false, ..
+            } => {
+                if !self.setup_road_placements().contains(&road) {
+                    return Err(RuleError::IllegalPlacement);
+                }
+                Resources::empty()
+            }
+            Phase::Turn | Phase::SpecialBuilding(_) | Phase::RoadBuilding(_) => {
+                if !self.road_placements(player).contains(&road) {
+                    return Err(RuleError::IllegalPlacement);
+                }
+                if matches!(self.phase, Phase::RoadBuilding(_)) {
+                    Resources::empty()
+                } else {
+                    ROAD_RESOURCES
+                }
+            }
+            _ => return Err(RuleError::WrongPhase),
+        };
+        self.pay(player, cost)?;
+        self.players[player.0].roads_left -= 1;
+        self.roads.push((player, road));
+        self.players[player.0].road_graph.add_road(road, false);
+        let mut events = vec![Event::RoadPlaced(player, road)];
+        match self.phase {
+            Phase::Setup { turn, .. } => {
+                self.phase = Phase::Setup {
+                    turn,
+                    road_placed: true,
+                };
+            }
+            Phase::RoadBuilding(_) => self.next_road_building(player),
+            _ => {}
+        }
+        self.update_longest_road([player], &mut events);
+        Ok(events)
+    }
+
+    fn place_ship(
+        &mut self,
+        player: PlayerHandle,
+        ship: RoadPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.players[player.0].ships_left == 0 {
+            return Err(RuleError::NoPiecesLeft);
+        }
+        let cost = match self.phase {
+            Phase::Turn | Phase::SpecialBuilding(_) | Phase::RoadBuilding(_) => {
+                if !self.ship_placements(player).contains(&ship) {
+                    return Err(RuleError::IllegalPlacement);
+                }
+                if matches!(self.phase, Phase::RoadBuilding(_)) {
+                    Resources::empty()
+                } else {
+                    SHIP_RESOURCES
+                }
+            }
+            _ => return Err(RuleError::WrongPhase),
+        };
+        self.pay(player, cost)?;
+        self.players[player.0].ships_left -= 1;
+        self.ships.push((player, ship));
+        self.players[player.0].road_graph.add_road(ship, true);
+        self.new_ships.push(ship);
+        let mut events = vec![Event::ShipPlaced(player, ship)];
+        self.next_road_building(player);
+        self.update_longest_road([player], &mut events);
+        Ok(events)
+    }
+
+    fn move_ship(
+        &mut self,
+        player: PlayerHandle,
+        from: RoadPosition,
+        to: RoadPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        if !self.ship_moves(player).contains(&(from, to)) {
+            return Err(RuleError::IllegalPlacement);
+        }
+        let Some(index) = self
+            .ships
+            .iter()
+            .position(|(owner, ship)| *owner == player && *ship == from)
+        else {
+            return Err(RuleError::IllegalPlacement);
+        };
+        self.ships[index].1 = to;
+        let road_graph = &mut self.players[player.0].road_graph;
+        road_graph.remove_road(from);
+        road_graph.add_road(to, true);
+        self.ship_moved = true;
+        let mut events = vec![Event::ShipMoved(player, from, to)];
+        // moving a ship can make the route shorter as well as longer
+        self.update_longest_road([player], &mut events);
+        Ok(events)
+    }
+
+    // how many more roads and ships the player could build with the road building card
+    fn road_building_pieces(&self, player: PlayerHandle) -> u8 {
+        let this = &self.players[player.0];
+        let roads = if self.road_placements(player).is_empty() {
+            0
+        } else {
+            this.roads_left
+        };
+        let ships = if self.ship_placements(player).is_empty() {
+            0
+        } else {
+            this.ships_left
+        };
+        roads + ships
+    }
+    fn next_road_building(&mut self, player: PlayerHandle) {
+        if let Phase::RoadBuilding(left) = self.phase {
+            self.phase = if left > 1 && self.road_building_pieces(player) > 0 {
+                Phase::RoadBuilding(left - 1)
+            } else {
+                Phase::Turn
+            };
+        }
+    }
+
+    fn place_town(
+        &mut self,
+        player: PlayerHandle,
+        town: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.players[player.0].towns_left == 0 {
+            return Err(RuleError::NoPiecesLeft);
+        }
+        let cost = match self.phase {
+            Phase::Setup {
+                road_placed: true, ..
+            } => {
+                if !self.town_placements(player).contains(&town) {
+                    return Err(RuleError::IllegalPlacement);
+                }
+                Resources::empty()
+            }
+            Phase::Turn | Phase::SpecialBuilding(_) => {
+                if !self.town_placements(player).contains(&town) {
+                    return Err(RuleError::IllegalPlacement);
+                }
+                TOWN_RESOURCES
+            }
+            _ => return Err(RuleError::WrongPhase),
+        };
+        self.pay(player, cost)?;
+        self.buildings.push((player, town, Piece::Town));
+        for (handle, other) in self.players.iter_mut().enumerate() {
+            other.road_graph.add_building(town, handle == player.0);
+        }
+        let island = self
+            .board
+            .seafarers()
+            .then(|| self.board.island(town))
+            .flatten();
+        let this = &mut self.players[player.0];
+        this.towns_left -= 1;
+        this.victory_points.actual += 1;
+        if let Some((_, port)) = self.board.ports.iter().find(|(port, _)| *port == town) {
+            this.ports += *port;
+        }
+        let mut events = vec![Event::TownPlaced(player, town)];
+        if let Some(island) = island
+            && !this.islands.contains(&island)
+        {
+            this.islands.push(island);
+            // the islands of the setup towns are home islands, any other one was discovered
+            if !matches!(self.phase, Phase::Setup { .. }) {
+                this.victory_points.actual += ISLAND_POINTS;
+                events.push(Event::IslandDiscovered(player));
+            }
+        }
+        if let Phase::Setup { turn, .. } = self.phase {
+            // if this player is done all their towns then add the resources from their last
+            // pick
+            if self.players[player.0].towns_left == 3 {
+                let initial_resources = self
+                    .board
+                    .hexes
+                    .iter()
+                    .filter(|(position, _, _)| town.contains(position))
+                    .filter_map(|(_, hex, _)| hex.to_resources())
+                    .fold(Resources::empty(), |total, resources| total + resources);
+                if let Some(bank) = self.bank.checked_sub(initial_resources) {
+                    self.bank = bank;
+                    self.players[player.0].resources += initial_resources;
+                    events.push(Event::Produced(player, initial_resources));
+                }
+            }
+            if let Some(next) = self.setup_player(turn + 1) {
+                self.phase = Phase::Setup {
+                    turn: turn + 1,
+                    road_placed: false,
+                };
+                events.push(Event::NextPlayer(next));
+            } else {
+                self.phase = Phase::Roll;
+                self.current = PlayerHandle(0);
+                events.push(Event::NextPlayer(self.current));
+            }
+        }
+        // a town can split up other players roads, or join the players roads and ships
+        let affected = (0..self.players.len())
+            .map(PlayerHandle)
+            .filter(|handle| self.players[handle.0].road_graph.touches(town))
+            .collect_vec();
+        self.update_longest_road(affected, &mut events);
+        Ok(events)
+    }
+
+    fn place_city(
+        &mut self,
+        player: PlayerHandle,
+        city: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if !matches!(self.phase, Phase::Turn | Phase::SpecialBuilding(_)) {
+            return Err(RuleError::WrongPhase);
+        }
+        if self.players[player.0].cities_left == 0 {
+            return Err(RuleError::NoPiecesLeft);
+        }
+        let Some(index) = self.buildings.iter().position(|(owner, building, piece)| {
+            *owner == player && *building == city && *piece == Piece::Town
+        }) else {
+            return Err(RuleError::IllegalPlacement);
+        };
+        self.pay(player, CITY_RESOURCES)?;
+        self.buildings[index].2 = Piece::City;
+        let this = &mut self.players[player.0];
+        this.towns_left += 1;
+        this.cities_left -= 1;
+        this.victory_points.actual += 1;
+        Ok(vec![Event::CityPlaced(player, city)])
+    }
+
+    fn buy_development_card(&mut self, player: PlayerHandle) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if !matches!(self.phase, Phase::Turn | Phase::SpecialBuilding(_)) {
+            return Err(RuleError::WrongPhase);
+        }
+        if self.development_cards.is_empty() {
+            return Err(RuleError::NoDevelopmentCards);
+        }
+        self.pay(player, DEVELOPMENT_CARD_RESOURCES)?;
+        let Some(card) = self.development_cards.pop() else {
+            return Err(RuleError::NoDevelopmentCards);
+        };
+        let this = &mut self.players[player.0];
+        if card == DevelopmentCard::VictoryPoint {
+            this.victory_points.from_development_cards += 1;
+        }
+        *this.development_cards.get_mut(card) += 1;
+        *this.new_development_cards.get_mut(card) += 1;
+        Ok(vec![Event::DevelopmentCardBought(player, card)])
+    }
+
+    fn play_development_card(
+        &mut self,
+        player: PlayerHandle,
+        card: DevelopmentCard,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        match (&self.phase, card) {
+            (_, DevelopmentCard::VictoryPoint) => return Err(RuleError::DoesNotHaveCard),
+            (Phase::Roll, DevelopmentCard::Knight) | (Phase::Turn, _) => {}
+            _ => return Err(RuleError::WrongPhase),
+        }
+        if card == DevelopmentCard::YearOfPlenty && self.bank.count() == 0 {
+            return Err(RuleError::BankCannotAfford);
+        }
+        let this = &mut self.players[player.0];
+        if this.development_cards.get(card) == 0 {
+            return Err(RuleError::DoesNotHaveCard);
+        }
+        if this.played_development_card {
+            return Err(RuleError::AlreadyPlayedDevelopmentCard);
+        }
+        if this.development_cards.get(card) <= this.new_development_cards.get(card) {
+            return Err(RuleError::NewDevelopmentCard);
+        }
+        *this.development_cards.get_mut(card) -= 1;
+        this.played_development_card = true;
+        let mut events = vec![Event::DevelopmentCardPlayed(player, card)];
+        self.phase = match card {
+            DevelopmentCard::Knight => {
+                self.players[player.0].knights += 1;
+                self.update_largest_army(player, &mut events);
+                Phase::PlaceRobber {
+                    then_roll: self.phase == Phase::Roll,
+                }
+            }
+            DevelopmentCard::Monopoly => Phase::Monopoly,
+            DevelopmentCard::YearOfPlenty => Phase::YearOfPlenty(2),
+            DevelopmentCard::RoadBuilding => match self.road_building_pieces(player) {
+                0 => Phase::Turn,
+                pieces => Phase::RoadBuilding(pieces.min(2)),
+            },
+            DevelopmentCard::VictoryPoint => unreachable!("you cannot play a vp"),
+        };
+        Ok(events)
+    }
+
+    fn check_roll(&self, player: PlayerHandle, d1: u8, d2: u8) -> Result<(), RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Roll {
+            return Err(RuleError::WrongPhase);
+        }
+        if !((1..=6).contains(&d1) && (1..=6).contains(&d2)) {
+            return Err(RuleError::InvalidDice);
+        }
+        Ok(())
+    }
+
+    fn roll(&mut self, player: PlayerHandle, d1: u8, d2: u8) -> Result<Events, RuleError> {
+        // cities & knights needs the event die as well
+        if self.cities_and_knights.is_some() {
+            return Err(RuleError::InvalidDice);
+        }
+        self.check_roll(player, d1, d2)?;
+        self.last_roll = Some((d1, d2));
+        let mut events = vec![Event::Rolled(d1, d2)];
+        self.produce(d1 + d2, &mut events);
+        Ok(events)
+    }
+
+    // the event die is resolved before anything is produced, `d1` is the red die
+    fn roll_with_event(
+        &mut self,
+        player: PlayerHandle,
+        d1: u8,
+        d2: u8,
+        event: EventDie,
+    ) -> Result<Events, RuleError> {
+        if self.cities_and_knights.is_none() {
+            return Err(RuleError::InvalidDice);
+        }
+        self.check_roll(player, d1, d2)?;
+        self.last_roll = Some((d1, d2));
+        let mut events = vec![Event::Rolled(d1, d2)];
+        if let Some(cities_and_knights) = &mut self.cities_and_knights {
+            cities_and_knights.last_event = Some(event);
+        }
+        match event {
+            EventDie::Ship => self.advance_barbarians(&mut events),
+            EventDie::Gate(track) => self.open_gate(track, d1, &mut events),
+        }
+        self.produce(d1 + d2, &mut events);
+        Ok(events)
+    }
+
+    fn produce(&mut self, roll: u8, events: &mut Events) {
+        if roll == 7 {
+            let pending = self
+                .players
+                .iter()
+                .enumerate()
+                .filter(|(_, player)| player.resources.count() > self.game_rules.discard_limit)
+                .map(|(handle, player)| (PlayerHandle(handle), player.resources.count() / 2))
+                .collect_vec();
+            self.phase = if !pending.is_empty() {
+                Phase::Discard(pending)
+            } else if self.robber_active() {
+                Phase::PlaceRobber { then_roll: false }
+            } else {
+                Phase::Turn
+            };
+            return;
+        }
+        for (handle, gained) in self.bank_production(roll, events).into_iter().enumerate() {
+            if gained.count() > 0 {
+                self.bank -= gained;
+                self.players[handle].resources += gained;
+                events.push(Event::Produced(PlayerHandle(handle), gained));
+            }
+        }
+        // there is no limit on commodities
+        for (handle, gained) in self.commodity_production(roll).into_iter().enumerate() {
+            if gained.count() > 0 {
+                self.players[handle].commodities += gained;
+                events.push(Event::CommoditiesProduced(PlayerHandle(handle), gained));
+            }
+        }
+        let gold = self.gold(roll);
+        self.phase = if gold.is_empty() {
+            Phase::Turn
+        } else {
+            Phase::Gold(gold)
+        };
+    }
+
+    // when the bank doesn't have enough of a resource for everyone nobody gets any of it, unless
+    // only one player gets that resource, then they get what is left
+    fn bank_production(&self, roll: u8, events: &mut Events) -> Vec<Resources> {
+        let mut production = self.production(roll);
+        for resource in Resource::ALL {
+            let left = self.bank.get(resource);
+            let wanted: u8 = production.iter().map(|gained| gained.get(resource)).sum();
+            if wanted <= left {
+                continue;
+            }
+            let getting = production
+                .iter()
+                .filter(|gained| gained.get(resource) > 0)
+                .count();
+            for gained in &mut production {
+                let count = gained.get_mut(resource);
+                *count = if getting == 1 { (*count).min(left) } else { 0 };
+            }
+            events.push(Event::BankShortage(resource));
+        }
+        production
+    }
+
+    // hexes that produce for this roll (the robber stops its hex from producing)
+    fn producing(&self, roll: u8) -> impl Iterator<Item = (Position, Hexagon)> + '_ {
+        self.board
+            .hexes
+            .iter()
+            .filter(move |(position, _, number)| {
+                *position != self.robber && matches!(number, Number::Number(n) if *n == roll)
+            })
+            .map(|(position, hex, _)| (*position, *hex))
+    }
+
+    /// what each player would get from this roll (indexed by player handle)
+    pub fn production(&self, roll: u8) -> Vec<Resources> {
+        let mut production = vec![Resources::empty(); self.players.len()];
+        for (owner, building, piece) in &self.buildings {
+            for (_, hex) in self
+                .producing(roll)
+                .filter(|(position, _)| building.contains(position))
+            {
+                if let Some(gained) = hex.to_resources() {
+                    // in cities & knights a city gets a commodity instead of the second resource
+                    let multiplier =
+                        if self.cities_and_knights.is_some() && hex.commodity().is_some() {
+                            1
+                        } else {
+                            piece.multiplier()
+                        };
+                    production[owner.0] += gained * multiplier;
+                }
+            }
+        }
+        production
+    }
+
+    /// what commodities each player would get from this roll (indexed by player handle), only
+    /// cities make them
+    pub fn commodity_production(&self, roll: u8) -> Vec<Commodities> {
+        let mut production = vec![Commodities::default(); self.players.len()];
+        if self.cities_and_knights.is_none() {
+            return production;
+        }
+        for (owner, building, piece) in &self.buildings {
+            if *piece != Piece::City {
+                continue;
+            }
+            for (_, hex) in self
+                .producing(roll)
+                .filter(|(position, _)| building.contains(position))
+            {
+                if let Some(commodity) = hex.commodity() {
+                    *production[owner.0].get_mut(commodity) += 1;
+                }
+            }
+        }
+        production
+    }
+
+    /// how many resources each player gets to pick from gold fields for this roll, only as many as
+    /// the bank has
+    pub fn gold(&self, roll: u8) -> Vec<(PlayerHandle, u8)> {
+        let mut gold = vec![0; self.players.len()];
+        for (owner, building, piece) in &self.buildings {
+            gold[owner.0] += self
+                .producing(roll)
+                .filter(|(position, hex)| {
+                    building.contains(position) && matches!(hex, Hexagon::Gold)
+                })
+                .count() as u8
+                * piece.multiplier();
+        }
+        let mut bank = self.bank.count();
+        gold.into_iter()
+            .enumerate()
+            .map(|(handle, picks)| {
+                let picks = picks.min(bank);
+                bank -= picks;
+                (PlayerHandle(handle), picks)
+            })
+            .filter(|(_, picks)| *picks > 0)
+            .collect()
+    }
+
+    fn pick_gold(&mut self, player: PlayerHandle, resource: Resource) -> Result<Events, RuleError> {
+        let Phase::Gold(pending) = &mut self.phase else {
+            return Err(RuleError::WrongPhase);
+        };
+        let Some(index) = pending.iter().position(|(handle, _)| *handle == player) else {
+            return Err(RuleError::NotYourTurn);
+        };
+        if self.bank.get(resource) == 0 {
+            return Err(RuleError::BankCannotAfford);
+        }
+        pending[index].1 -= 1;
+        if pending[index].1 == 0 {
+            pending.remove(index);
+        }
+        if pending.is_empty() {
+            self.phase = Phase::Turn;
+        }
+        *self.bank.get_mut(resource) -= 1;
+        *self.players[player.0].resources.get_mut(resource) += 1;
+        Ok(vec![Event::PickedGold(player, resource)])
+    }
+
+    fn discard(&mut self, player: PlayerHandle, resources: Resources) -> Result<Events, RuleError> {
+        let Phase::Discard(pending) = &mut self.phase else {
+            return Err(RuleError::WrongPhase);
+        };
+        let Some(index) = pending.iter().position(|(handle, _)| *handle == player) else {
+            return Err(RuleError::NotYourTurn);
+        };
+        if pending[index].1 != resources.count() {
+            return Err(RuleError::InvalidDiscard);
+        }
+        let Some(left) = self.players[player.0].resources.checked_sub(resources) else {
+            return Err(RuleError::CannotAfford);
+        };
+        pending.remove(index);
+        if pending.is_empty() {
+            self.phase = if self.robber_active() {
+                Phase::PlaceRobber { then_roll: false }
+            } else {
+                Phase::Turn
+            };
+        }
+        self.players[player.0].resources = left;
+        self.bank += resources;
+        Ok(vec![Event::Discarded(player, resources)])
+    }
+
+    fn move_robber(
+        &mut self,
+        player: PlayerHandle,
+        position: Position,
+        steal: Option<(PlayerHandle, Resource)>,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        let Phase::PlaceRobber { then_roll } = self.phase else {
+            return Err(RuleError::WrongPhase);
+        };
+        if !self.robber_placements(player).contains(&position) {
+            return Err(RuleError::InvalidRobber);
+        }
+        let victims = self.robber_victims(player, position);
+        if !self.steals(victims).contains(&steal) {
+            return Err(RuleError::InvalidRobber);
+        }
+        self.robber = position;
+        let mut events = vec![Event::RobberMoved(position)];
+        self.steal(player, steal, &mut events);
+        self.phase = if then_roll { Phase::Roll } else { Phase::Turn };
+        Ok(events)
+    }
+
+    fn move_pirate(
+        &mut self,
+        player: PlayerHandle,
+        position: Position,
+        steal: Option<(PlayerHandle, Resource)>,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        let Phase::PlaceRobber { then_roll } = self.phase else {
+            return Err(RuleError::WrongPhase);
+        };
+        if self.pirate.is_none_or(|pirate| pirate == position)
+            || !self.board.sea().contains(&position)
+        {
+            return Err(RuleError::InvalidPirate);
+        }
+        let victims = self.pirate_victims(player, position);
+        if !self.steals(victims).contains(&steal) {
+            return Err(RuleError::InvalidPirate);
+        }
+        self.pirate = Some(position);
+        let mut events = vec![Event::PirateMoved(position)];
+        self.steal(player, steal, &mut events);
+        self.phase = if then_roll { Phase::Roll } else { Phase::Turn };
+        Ok(events)
+    }
+
+    // you can only not take anything if there is no one to take from, which is checked before this
+    fn steal(
+        &mut self,
+        thief: PlayerHandle,
+        steal: Option<(PlayerHandle, Resource)>,
+        events: &mut Events,
+    ) {
+        if let Some((victim, resource)) = steal {
+            *self.players[victim.0].resources.get_mut(resource) -= 1;
+            *self.players[thief.0].resources.get_mut(resource) += 1;
+            events.push(Event::Stole {
+                thief,
+                victim,
+                resource,
+            });
+        }
+    }
+
+    fn monopoly(&mut self, player: PlayerHandle, resource: Resource) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Monopoly {
+            return Err(RuleError::WrongPhase);
+        }
+        // the resource monopoly progress card only takes up to two from each player
+        let limit = if self.cities_and_knights.is_some() {
+            2
+        } else {
+            u8::MAX
+        };
+        let taken = self
+            .players
+            .iter_mut()
+            .enumerate()
+            .filter(|(handle, _)| *handle != player.0)
+            .map(|(_, other)| {
+                let left = other.resources.get_mut(resource);
+                let taken = (*left).min(limit);
+                *left -= taken;
+                taken
+            })
+            .sum::<u8>();
+        *self.players[player.0].resources.get_mut(resource) += taken;
+        self.phase = Phase::Turn;
+        Ok(vec![Event::Monopolized(player, resource, taken)])
+    }
+
+    fn commodity_monopoly(
+        &mut self,
+        player: PlayerHandle,
+        commodity: Commodity,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::CommodityMonopoly {
+            return Err(RuleError::WrongPhase);
+        }
+        let taken = self
+            .players
+            .iter_mut()
+            .enumerate()
+            .filter(|(handle, _)| *handle != player.0)
+            .map(|(_, other)| {
+                let left = other.commodities.get_mut(commodity);
+                let taken = (*left).min(1);
+                *left -= taken;
+                taken
+            })
+            .sum::<u8>();
+        *self.players[player.0].commodities.get_mut(commodity) += taken;
+        self.phase = Phase::Turn;
+        Ok(vec![Event::CommodityMonopolized(player, commodity, taken)])
+    }
+
+    fn year_of_plenty(
+        &mut self,
+        player: PlayerHandle,
+        resource: Resource,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        let Phase::YearOfPlenty(left) = self.phase else {
+            return Err(RuleError::WrongPhase);
+        };
+        if self.bank.get(resource) == 0 {
+            return Err(RuleError::BankCannotAfford);
+        }
+        *self.bank.get_mut(resource) -= 1;
+        *self.players[player.0].resources.get_mut(resource) += 1;
+        // with an empty bank the second resource is lost
+        self.phase = if left > 1 && self.bank.count() > 0 {
+            Phase::YearOfPlenty(left - 1)
+        } else {
+            Phase::Turn
+        };
+        Ok(vec![Event::YearOfPlenty(player, resource)])
+    }
+
+    fn bank_trade(
+        &mut self,
+        player: PlayerHandle,
+        trade: TradingResources,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        let this = &self.players[player.0];
+        let (giving, taking) = trade.given_and_taken();
+        let taking: i8 = taking.iter().map(|(_, count)| count).sum();
+        let mut giving_total = 0;
+        for (resource, count) in giving {
+            let trade_rate = this.ports.get_trade_rate(resource) as i8;
+            if count % trade_rate != 0 {
+                return Err(RuleError::InvalidTrade);
+            }
+            giving_total += count / trade_rate;
+        }
+        if giving_total != -taking || taking == 0 {
+            return Err(RuleError::InvalidTrade);
+        }
+        let Some(resources) = this.resources.checked_add(trade) else {
+            return Err(RuleError::CannotAfford);
+        };
+        let Some(bank) = self.bank.checked_sub(trade) else {
+            return Err(RuleError::BankCannotAfford);
+        };
+        self.players[player.0].resources = resources;
+        self.bank = bank;
+        Ok(vec![Event::BankTraded(player, trade)])
+    }
+
+    fn trade(
+        &mut self,
+        player: PlayerHandle,
+        other: PlayerHandle,
+        trade: TradingResources,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        let (giving, taking) = trade.given_and_taken();
+        if other == player || giving.is_empty() || taking.is_empty() {
+            return Err(RuleError::InvalidTrade);
+        }
+        let Some(other_player) = self.player(other) else {
+            return Err(RuleError::UnknownPlayer);
+        };
+        let (Some(resources), Some(other_resources)) = (
+            self.players[player.0].resources.checked_add(trade),
+            other_player.resources.checked_sub(trade),
+        ) else {
+            return Err(RuleError::CannotAfford);
+        };
+        self.players[player.0].resources = resources;
+        self.players[other.0].resources = other_resources;
+        Ok(vec![Event::Traded(player, other, trade)])
+    }
+
+    /// the trades the player can answer (or accept if they're the current player)
+    pub fn trade_offers(&self, player: PlayerHandle) -> impl Iterator<Item = &TradeOffer> {
+        self.trades
+            .iter()
+            .filter(move |offer| offer.for_player(player))
+    }
+
+    // whether both sides of the trade have what they would give, for offers to everyone just the
+    // current player
+    fn can_trade(&self, offer: &TradeOffer) -> bool {
+        let (current, other) = offer.parties(self.current);
+        self.players[current.0]
+            .resources
+            .checked_add(offer.trade)
+            .is_some()
+            && other.is_none_or(|other| {
+                self.players[other.0]
+                    .resources
+                    .checked_sub(offer.trade)
+                    .is_some()
+            })
+    }
+
+    fn add_trade(&mut self, offer: TradeOffer) -> Result<Events, RuleError> {
+        let (giving, taking) = offer.trade.given_and_taken();
+        if giving.is_empty() || taking.is_empty() {
+            return Err(RuleError::InvalidTrade);
+        }
+        if !self.can_trade(&offer) {
+            return Err(RuleError::CannotAfford);
+        }
+        self.trades.push(offer);
+        self.next_trade.0 += 1;
+        Ok(vec![Event::TradeOffered(offer)])
+    }
+
+    fn offer_trade(
+        &mut self,
+        player: PlayerHandle,
+        to: Option<PlayerHandle>,
+        trade: TradingResources,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        if let Some(to) = to {
+            if to == player {
+                return Err(RuleError::InvalidTrade);
+            }
+            self.player(to).ok_or(RuleError::UnknownPlayer)?;
+        }
+        self.add_trade(TradeOffer {
+            id: self.next_trade,
+            from: player,
+            to,
+            trade,
+        })
+    }
+
+    // the counter offer only goes to who made the offer, and one of them has to be the current
+    // player
+    fn counter_offer(
+        &mut self,
+        player: PlayerHandle,
+        id: TradeId,
+        trade: TradingResources,
+    ) -> Result<Events, RuleError> {
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        let offer = self
+            .trades
+            .iter()
+            .find(|offer| offer.id == id && offer.for_player(player))
+            .ok_or(RuleError::UnknownTrade)?;
+        if player != self.current && offer.from != self.current {
+            return Err(RuleError::InvalidTrade);
+        }
+        self.add_trade(TradeOffer {
+            id: self.next_trade,
+            from: player,
+            to: Some(offer.from),
+            trade,
+        })
+    }
+
+    fn cancel_trade(&mut self, player: PlayerHandle, id: TradeId) -> Result<Events, RuleError> {
+        let index = self
+            .trades
+            .iter()
+            .position(|offer| offer.id == id && offer.from == player)
+            .ok_or(RuleError::UnknownTrade)?;
+        self.trades.remove(index);
+        Ok(vec![Event::TradeCancelled(id)])
+    }
+
+    fn accept_trade(&mut self, player: PlayerHandle, id: TradeId) -> Result<Events, RuleError> {
+        let index = self
+            .trades
+            .iter()
+            .position(|offer| offer.id == id && offer.to == Some(player))
+            .ok_or(RuleError::UnknownTrade)?;
+        let offer = self.trades[index];
+        let events = self.trade(player, offer.from, offer.trade)?;
+        self.trades.remove(index);
+        Ok(events)
+    }
+
+    // after anything that changes resources, offers that can't happen anymore are taken away
+    fn invalidate_trades(&mut self, events: &mut Events) {
+        let (trades, invalid): (Vec<_>, Vec<_>) = self
+            .trades
+            .iter()
+            .copied()
+            .partition(|offer| self.can_trade(offer));
+        events.extend(
+            invalid
+                .iter()
+                .map(|offer| Event::TradeInvalidated(offer.id)),
+        );
+        self.trades = trades;
+    }
+
+    fn end_turn(&mut self, player: PlayerHandle) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        let next = PlayerHandle((player.0 + 1) % self.players.len());
+        if matches!(self.phase, Phase::Turn | Phase::SpecialBuilding(_)) {
+            // the next player can move any of their ships
+            self.new_ships.clear();
+            self.ship_moved = false;
+        }
+        match self.phase {
+            // starting with the next player, everyone gets to build once before the next turn
+            Phase::Turn if self.players.len() > 4 => {
+                self.phase = Phase::SpecialBuilding(next);
+                Ok(vec![Event::SpecialBuilding(next)])
+            }
+            Phase::SpecialBuilding(_) if next != self.current => {
+                self.phase = Phase::SpecialBuilding(next);
+                Ok(vec![Event::SpecialBuilding(next)])
+            }
+            Phase::Turn | Phase::SpecialBuilding(_) => {
+                self.trades.clear();
+                // cards bought this turn (or while special building) can be played from now on
+                for player in &mut self.players {
+                    player.new_development_cards = DevelopmentCards::new_player();
+                    player.played_development_card = false;
+                }
+                self.current = PlayerHandle((self.current.0 + 1) % self.players.len());
+                self.phase = Phase::Roll;
+                Ok(vec![Event::NextPlayer(self.current)])
+            }
+            _ => Err(RuleError::WrongPhase),
+        }
+    }
+
+    // cities & knights
+
+    fn advance_barbarians(&mut self, events: &mut Events) {
+        let Some(cities_and_knights) = &mut self.cities_and_knights else {
+            return;
+        };
+        cities_and_knights.barbarians += 1;
+        events.push(Event::BarbariansMoved(cities_and_knights.barbarians));
+        if cities_and_knights.barbarians >= BARBARIAN_DISTANCE {
+            self.barbarian_attack(events);
+        }
+    }
+
+    // the barbarians are as strong as there are cities, every active knight defends with its level
+    fn barbarian_attack(&mut self, events: &mut Events) {
+        let Some(cities_and_knights) = &mut self.cities_and_knights else {
+            return;
+        };
+        let mut defences = vec![0; self.players.len()];
+        for knight in &mut cities_and_knights.knights {
+            if knight.active {
+                defences[knight.owner.0] += knight.level;
+            }
+            // the knights go home after the attack, whether they fought or not
+            knight.active = false;
+        }
+        cities_and_knights.barbarians = 0;
+        cities_and_knights.attacked = true;
+        let strength = self
+            .buildings
+            .iter()
+            .filter(|(_, _, piece)| *piece == Piece::City)
+            .count() as u8;
+        let defence = defences.iter().sum();
+        events.push(Event::BarbariansAttacked { strength, defence });
+        if defence >= strength {
+            // whoever defended the most gets a victory point, if they tie they all get a
+            // progress card instead
+            let defenders = defences
+                .iter()
+                .enumerate()
+                .filter(|(_, defence)| **defence > 0)
+                .max_set_by_key(|(_, defence)| **defence)
+                .into_iter()
+                .map(|(handle, _)| PlayerHandle(handle))
+                .collect_vec();
+            if let [defender] = defenders[..] {
+                self.players[defender.0].victory_points.actual += 1;
+                events.push(Event::DefenderOfCatan(defender));
+            } else {
+                for defender in defenders {
+                    let improvements = self.players[defender.0].improvements;
+                    let track = Track::ALL
+                        .into_iter()
+                        .max_by_key(|track| improvements.get(*track))
+                        .unwrap_or(Track::Science);
+                    self.draw_progress_card(defender, track, events);
+                }
+            }
+        } else {
+            // whoever defended the least (of the players with a city) loses a city
+            let losers = defences
+                .iter()
+                .enumerate()
+                .filter(|(handle, _)| self.player_cities(PlayerHandle(*handle)) > 0)
+                .min_set_by_key(|(_, defence)| **defence)
+                .into_iter()
+                .map(|(handle, _)| PlayerHandle(handle))
+                .collect_vec();
+            for loser in losers {
+                self.pillage(loser, events);
+            }
+        }
+    }
+
+    fn pillage(&mut self, player: PlayerHandle, events: &mut Events) {
+        // there has to be a town to put back
+        if self.players[player.0].towns_left == 0 {
+            return;
+        }
+        let Some(index) = self
+            .buildings
+            .iter()
+            .position(|(owner, _, piece)| *owner == player && *piece == Piece::City)
+        else {
+            return;
+        };
+        self.buildings[index].2 = Piece::Town;
+        let this = &mut self.players[player.0];
+        this.towns_left -= 1;
+        this.cities_left += 1;
+        this.victory_points.actual -= 1;
+        events.push(Event::Pillaged(player, self.buildings[index].1));
+    }
+
+    // everyone with the improvement gets a progress card if the red die is low enough, starting
+    // with the current player
+    fn open_gate(&mut self, track: Track, red: u8, events: &mut Events) {
+        let count = self.players.len();
+        let current = self.current.0;
+        for handle in (0..count).map(|offset| PlayerHandle((current + offset) % count)) {
+            let level = self.players[handle.0].improvements.get(track);
+            if level > 0 && red <= level + 1 {
+                self.draw_progress_card(handle, track, events);
+            }
+        }
+    }
+
+    fn draw_progress_card(&mut self, player: PlayerHandle, track: Track, events: &mut Events) {
+        let Some(cities_and_knights) = &mut self.cities_and_knights else {
+            return;
+        };
+        let Some(index) = cities_and_knights
+            .progress_cards
+            .iter()
+            .rposition(|card| card.track() == track)
+        else {
+            return;
+        };
+        let card = cities_and_knights.progress_cards[index];
+        let this = &mut self.players[player.0];
+        // instead of discarding down to the limit you just don't get one
+        if !card.victory_point() && this.progress_cards.len() >= PROGRESS_CARD_LIMIT {
+            return;
+        }
+        cities_and_knights.progress_cards.remove(index);
+        if card.victory_point() {
+            this.victory_points.actual += 1;
+        } else {
+            this.progress_cards.push(card);
+        }
+        events.push(Event::ProgressCardDrawn(player, card));
+    }
+
+    // building phases, like everything else that is built
+    fn expect_cities_and_knights_build(&self, player: PlayerHandle) -> Result<(), RuleError> {
+        self.expect_active(player)?;
+        if self.cities_and_knights.is_none()
+            || !matches!(self.phase, Phase::Turn | Phase::SpecialBuilding(_))
+        {
+            return Err(RuleError::WrongPhase);
+        }
+        Ok(())
+    }
+
+    fn improve(&mut self, player: PlayerHandle, track: Track) -> Result<Events, RuleError> {
+        self.expect_cities_and_knights_build(player)?;
+        let level = self.players[player.0].improvements.get(track);
+        if level >= MAX_IMPROVEMENT || self.player_cities(player) == 0 {
+            return Err(RuleError::InvalidImprovement);
+        }
+        let this = &mut self.players[player.0];
+        let commodities = this.commodities.get_mut(track.commodity());
+        // each level costs one more than the last
+        let cost = level + 1;
+        if *commodities < cost {
+            return Err(RuleError::CannotAfford);
+        }
+        *commodities -= cost;
+        *this.improvements.get_mut(track) += 1;
+        Ok(vec![Event::Improved(player, track, level + 1)])
+    }
+
+    fn place_knight(
+        &mut self,
+        player: PlayerHandle,
+        position: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_cities_and_knights_build(player)?;
+        if !self.knights_left(player, 1) {
+            return Err(RuleError::NoPiecesLeft);
+        }
+        if !self.knight_placements(player).contains(&position) {
+            return Err(RuleError::IllegalPlacement);
+        }
+        self.pay(player, KNIGHT_RESOURCES)?;
+        if let Some(cities_and_knights) = &mut self.cities_and_knights {
+            cities_and_knights.knights.push(Knight {
+                owner: player,
+                position,
+                level: 1,
+                active: false,
+            });
+        }
+        Ok(vec![Event::KnightPlaced(player, position)])
+    }
+
+    fn promote_knight(
+        &mut self,
+        player: PlayerHandle,
+        position: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_cities_and_knights_build(player)?;
+        if !self.knight_promotions(player).contains(&position) {
+            return Err(RuleError::InvalidKnight);
+        }
+        self.pay(player, KNIGHT_RESOURCES)?;
+        self.update_knight(player, position, |knight| knight.level += 1);
+        Ok(vec![Event::KnightPromoted(player, position)])
+    }
+
+    fn activate_knight(
+        &mut self,
+        player: PlayerHandle,
+        position: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_cities_and_knights_build(player)?;
+        if self
+            .player_knight(player, position)
+            .is_none_or(|knight| knight.active)
+        {
+            return Err(RuleError::InvalidKnight);
+        }
+        self.pay(player, ACTIVATE_KNIGHT_RESOURCES)?;
+        self.update_knight(player, position, |knight| knight.active = true);
+        Ok(vec![Event::KnightActivated(player, position)])
+    }
+
+    fn chase_robber(
+        &mut self,
+        player: PlayerHandle,
+        position: BuildingPosition,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        if !self.robber_chases(player).contains(&position) {
+            return Err(RuleError::InvalidKnight);
+        }
+        self.update_knight(player, position, |knight| knight.active = false);
+        self.phase = Phase::PlaceRobber { then_roll: false };
+        Ok(vec![Event::RobberChased(player, position)])
+    }
+
+    fn update_knight(
+        &mut self,
+        player: PlayerHandle,
+        position: BuildingPosition,
+        update: impl FnOnce(&mut Knight),
+    ) {
+        if let Some(knight) = self
+            .cities_and_knights
+            .as_mut()
+            .and_then(|cities_and_knights| {
+                cities_and_knights
+                    .knights
+                    .iter_mut()
+                    .find(|knight| knight.owner == player && knight.position == position)
+            })
+        {
+            update(knight);
+        }
+    }
+
+    /// one development card a turn, not the turn it was bought, knights can be played before
+    /// rolling
+    pub fn can_play_development_card(&self, player: PlayerHandle, card: DevelopmentCard) -> bool {
+        let Some(this) = self.player(player) else {
+            return false;
+        };
+        self.active_player() == Some(player)
+            && match (&self.phase, card) {
+                (_, DevelopmentCard::VictoryPoint) => false,
+                (_, DevelopmentCard::YearOfPlenty) if self.bank.count() == 0 => false,
+                (Phase::Roll, DevelopmentCard::Knight) | (Phase::Turn, _) => true,
+                _ => false,
+            }
+            && !this.played_development_card
+            && this.development_cards.get(card) > this.new_development_cards.get(card)
+    }
+
+    /// cards that would do nothing can't be played
+    pub fn can_play_progress_card(&self, card: ProgressCard) -> bool {
+        match card {
+            ProgressCard::Bishop => self.robber_active(),
+            ProgressCard::RoadBuilding => self.road_building_pieces(self.current) > 0,
+            ProgressCard::Printer | ProgressCard::Constitution => false,
+            _ => true,
+        }
+    }
+
+    fn play_progress_card(
+        &mut self,
+        player: PlayerHandle,
+        card: ProgressCard,
+    ) -> Result<Events, RuleError> {
+        self.expect_active(player)?;
+        if self.phase != Phase::Turn {
+            return Err(RuleError::WrongPhase);
+        }
+        let Some(index) = self.players[player.0]
+            .progress_cards
+            .iter()
+            .position(|held| *held == card)
+        else {
+            return Err(RuleError::DoesNotHaveCard);
+        };
+        if !self.can_play_progress_card(card) {
+            return Err(RuleError::WrongPhase);
+        }
+        self.players[player.0].progress_cards.remove(index);
+        let mut events = vec![Event::ProgressCardPlayed(player, card)];
+        match card {
+            ProgressCard::Irrigation => self.harvest(player, Resource::Wheat, &mut events),
+            ProgressCard::Mining => self.harvest(player, Resource::Ore, &mut events),
+            ProgressCard::RoadBuilding => {
+                self.phase = Phase::RoadBuilding(self.road_building_pieces(player).min(2));
+            }
+            ProgressCard::ResourceMonopoly => self.phase = Phase::Monopoly,
+            ProgressCard::TradeMonopoly => self.phase = Phase::CommodityMonopoly,
+            ProgressCard::Bishop => self.phase = Phase::PlaceRobber { then_roll: false },
+            ProgressCard::Warlord => {
+                if let Some(cities_and_knights) = &mut self.cities_and_knights {
+                    for knight in &mut cities_and_knights.knights {
+                        if knight.owner == player {
+                            knight.active = true;
+                        }
+                    }
+                }
+            }
+            ProgressCard::Printer | ProgressCard::Constitution => {
+                unreachable!("victory points are never in a hand")
+            }
+        }
+        Ok(events)
+    }
+
+    // two of the resource for every hex of it next to one of the players buildings, as much as
+    // the bank has
+    fn harvest(&mut self, player: PlayerHandle, resource: Resource, events: &mut Events) {
+        let buildings = self.player_buildings(player);
+        let hexes = self
+            .board
+            .hexes
+            .iter()
+            .filter(|(position, hex, _)| {
+                hex.to_resources()
+                    .is_some_and(|produces| produces.get(resource) > 0)
+                    && buildings.iter().any(|building| building.contains(position))
+            })
+            .count() as u8;
+        let amount = (hexes * 2).min(self.bank.get(resource));
+        if amount == 0 {
+            return;
+        }
+        let mut gained = Resources::empty();
+        *gained.get_mut(resource) = amount;
+        *self.bank.get_mut(resource) -= amount;
+        self.players[player.0].resources += gained;
+        events.push(Event::Produced(player, gained));
+    }
+
+    fn pay(&mut self, player: PlayerHandle, cost: Resources) -> Result<(), RuleError> {
+        let resources = self.players[player.0]
+            .resources
+            .checked_sub(cost)
+            .ok_or(RuleError::CannotAfford)?;
+        self.players[player.0].resources = resources;
+        self.bank += cost;
+        Ok(())
+    }
+
+    fn check_for_winner(&mut self, events: &mut Events) {
+        if matches!(self.phase, Phase::Setup { .. } | Phase::Finished(_)) {
+            return;
+        }
+        let winner = &self.players[self.current.0];
+        if winner.total_victory_points() >= self.victory_points_to_win() {
+            // hidden victory point cards count as soon as they are enough to win
+            let cards = winner.victory_points.from_development_cards;
+            if cards > 0 {
+                events.push(Event::VictoryPointCardsRevealed(self.current, cards));
+            }
+            self.phase = Phase::Finished(self.current);
+            events.push(Event::Won(self.current));
+        }
+    }
+
+    fn update_largest_army(&mut self, player: PlayerHandle, events: &mut Events) {
+        let knights = self.players[player.0].knights;
+        if knights > self.largest_army.1 {
+            if self.largest_army.0 != Some(player) {
+                if let Some(holder) = self.largest_army.0 {
+                    self.players[holder.0].victory_points.actual -= 2;
+                }
+                self.players[player.0].victory_points.actual += 2;
+                events.push(Event::LargestArmy(player));
+            }
+            self.largest_army = (Some(player), knights);
+        }
+    }
+
+    fn recompute_longest_road(&mut self, player: PlayerHandle) {
+        let roads = self.player_roads(player);
+        let ships = self.player_ships(player);
+        if self.players[player.0].road_graph.pieces()
+            != (roads.len() + ships.len(), self.buildings.len())
+        {
+            self.players[player.0].road_graph = RoadGraph::new(
+                &roads,
+                &ships,
+                &self.player_buildings(player),
+                &self.other_buildings(player),
+            );
+        }
+        let this = &mut self.players[player.0];
+        this.longest_road = this.road_graph.longest();
+    }
+
+    /// the only place that decides who has longest road, after any road, ship or building change
+    /// the affected players routes are recalculated and then:
+    ///
+    /// If the player who up to this point had the Longest Road still meets the requirements for
+    /// the Longest Road (either alone or together with another player), he keeps the card.
+    ///
+    /// If another player now meets the requirements for the Longest Road, he receives the card.
+    ///
+    /// If none of the players - or more than one player - meets the requirements for the
+    /// Longest Road, none of the players receives the card.
+    fn update_longest_road(
+        &mut self,
+        affected: impl IntoIterator<Item = PlayerHandle>,
+        events: &mut Events,
+    ) {
+        for player in affected {
+            self.recompute_longest_road(player);
+        }
+        let leaders = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| {
+                player.longest_road.len() >= usize::from(self.game_rules.longest_road)
+            })
+            .max_set_by_key(|(_, player)| player.longest_road.len())
+            .into_iter()
+            .map(|(handle, player)| (PlayerHandle(handle), player.longest_road.len() as u8))
+            .collect_vec();
+        let holder = self.longest_road.0;
+        let longest_road = match (holder, &leaders[..]) {
+            (Some(holder), _) if leaders.iter().any(|(leader, _)| *leader == holder) => {
+                (Some(holder), leaders[0].1)
+            }
+            (_, [(leader, len)]) => (Some(*leader), *len),
+            _ => (None, self.game_rules.longest_road - 1),
+        };
+        if longest_road.0 != holder {
+            if let Some(holder) = holder {
+                self.players[holder.0].victory_points.actual -= 2;
+            }
+            if let Some(new_holder) = longest_road.0 {
+                self.players[new_holder.0].victory_points.actual += 2;
+            }
+            events.push(Event::LongestRoad(longest_road.0));
+        }
+        self.longest_road = longest_road;
+    }
+}
+
+/// every way to discard `amount` resources from `resources`
+fn discards(resources: Resources, amount: u8) -> Vec<Resources> {
+    Resource::ALL
+        .into_iter()
+        .fold(vec![Resources::empty()], |discards, resource| {
+            discards
+                .into_iter()
+                .flat_map(|discard| {
+                    (0..=resources.get(resource).min(amount - discard.count())).map(move |n| {
+                        let mut discard = discard;
+                        *discard.get_mut(resource) = n;
+                        discard
+                    })
+                })
+                .collect()
+        })
+        .into_iter()
+        .filter(|discard| discard.count() == amount)
+        .collect()
+}
+
+// roads don't hash the same if their hexes are the other way around, so `unique` can't be used
+fn distinct(roads: impl IntoIterator<Item = RoadPosition>) -> Vec<RoadPosition> {
+    roads.into_iter().fold(vec![], |mut distinct, road| {
+        if !distinct.contains(&road) {
+            distinct.push(road);
+        }
+        distinct
+    })
+}
+
+/// the three roads that meet at an intersection
+pub fn roads_around(size: u8, building: BuildingPosition) -> impl Iterator<Item = RoadPosition> {
+    match building {
+        BuildingPosition::All(position, position1, position2) => [
+            RoadPosition::new(position, position1, Some(size)),
+            RoadPosition::new(position, position2, Some(size)),
+            RoadPosition::new(position1, position2, Some(size)),
+        ]
+        .into_iter()
+        .flatten(),
+    }
+}
+
+/// the intersections at either end of a road
+pub fn buildings_on_road(size: u8, road: RoadPosition) -> impl Iterator<Item = BuildingPosition> {
+    match road {
+        RoadPosition::Both(p1, p2, _) => {
+            let (p3, p4) = road.neighboring_two(Some(size));
+            let make_town_pos = |p, option_p1: Option<_>, p2| {
+                option_p1.and_then(|p1| BuildingPosition::new(p, p1, p2, Some(size)))
+            };
+            [(make_town_pos(p1, p3, p2)), (make_town_pos(p1, p4, p2))]
+                .into_iter()
+                .flatten()
+        }
+    }
+}
+
+/// verifies that there is no buildings with in one road of this building
+pub fn no_touching_buildings(
+    position: &BuildingPosition,
+    buildings: &[BuildingPosition],
+    size: u8,
+) -> bool {
+    !roads_around(size, *position)
+        .flat_map(|road| buildings_on_road(size, road))
+        .any(|building| buildings.contains(&building))
+}
+
+/// not for initial game setup where the are no roads yet
+pub fn possible_town_placements(
+    size: u8,
+    own_roads: &[RoadPosition],
+    buildings: &[BuildingPosition],
+) -> Vec<BuildingPosition> {
+    own_roads
+        .iter()
+        .flat_map(|road| buildings_on_road(size, *road))
+        .filter(|building| no_touching_buildings(building, buildings, size))
+        .unique()
+        .collect()
+}
+
+/// roads that connect to one of the players roads, without going through another players
+/// building
+pub fn possible_road_placements(
+    size: u8,
+    own_roads: &[RoadPosition],
+    roads: &[RoadPosition],
+    other_buildings: &[BuildingPosition],
+) -> Vec<RoadPosition> {
+    // roads are between two hexes (if one coordiante is the same
+    // if q same then its flat (assuming hex is flat)
+    // if r is same then its diagonol like '\'
+    // if s is same then its diagonol like '/'
+    // if there is a new place to put road down
+    // 1) the new hex has to share one coordianate with one hex and another differenet one with the
+    //    other hex (more constraint (i.e cannot be 50 square of in another direction)
+    let possible_roads = own_roads.iter().flat_map(|road| match road {
+        RoadPosition::Both(p1, p2, _) => {
+            let (p3, p4) = road.neighboring_two(Some(size));
+            let make_road_pos = |p, option_p1: Option<_>, p2: &Position| {
+                option_p1.and_then(|p1| RoadPosition::new(p, p1, Some(size)).map(|r| (*p2, r)))
+            };
+            [
+                // the other point (used to check for towns/cities)
+                // the postion of the road
+                make_road_pos(*p2, p3, p1),
+                make_road_pos(*p2, p4, p1),
+                make_road_pos(*p1, p3, p2),
+                make_road_pos(*p1, p4, p2),
+            ]
+            .into_iter()
+            .flatten()
+        }
+    });
+    let mut placements: Vec<RoadPosition> = vec![];
+    for (other_point, road) in possible_roads {
+        // 2) make sure that there is no road already there (whether that color or not)
+        // 3) make sure there is no differeent color town at the three itersection
+        let road_intersection = match road {
+            RoadPosition::Both(position, position1, _) => unsafe {
+                BuildingPosition::new_unchecked(other_point, position, position1)
+            },
+        };
+        if !roads.contains(&road)
+            && !other_buildings.contains(&road_intersection)
+            && !placements.contains(&road)
+        {
+            placements.push(road);
+        }
+    }
+    placements
+}
+
+/// roads for initial placement, any free road that a town could be placed next to
+pub fn setup_road_placements(
+    size: u8,
+    roads: &[RoadPosition],
+    buildings: &[BuildingPosition],
+) -> Vec<RoadPosition> {
+    // generate all road possobilties
+    // generate the ring around it for edge roads
+    positions::generate_postions(size as i8 + 1)
+        .array_combinations::<2>()
+        .filter_map(|[p1, p2]| RoadPosition::new(p1, p2, Some(size)))
+        // filter out ones that are already placed
+        .filter(|road| !roads.contains(road))
+        // only show road if town can placed near it
+        .filter(|road| {
+            buildings_on_road(size, *road)
+                .any(|building| no_touching_buildings(&building, buildings, size))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(players: usize) -> GameState {
+        let board = Board {
+            size: 5,
+            hexes: positions::generate_postions(5)
+                .map(|position| (position, Hexagon::Wood, Number::Number(6)))
+                .collect(),
+            ports: vec![],
+        };
+        let colors = [
+            CatanColor::Red,
+            CatanColor::Blue,
+            CatanColor::White,
+            CatanColor::Green,
+        ];
+        GameState::new(board, colors.into_iter().take(players), vec![])
+    }
+
+    // roads along the border between the hexes in `row` and the row below it, each road continues
+    // the one before it
+    fn zigzag(row: i8, len: usize) -> Vec<RoadPosition> {
+        (0..len as i8)
+            .map(|i| {
+                let j = i.div_euclid(2) - 2;
+                let top = Position {
+                    q: j,
+                    r: row,
+                    s: -j - row,
+                };
+                let bottom = if i % 2 == 0 {
+                    Position {
+                        q: j - 1,
+                        r: row + 1,
+                        s: -j - row,
+                    }
+                } else {
+                    Position {
+                        q: j,
+                        r: row + 1,
+                        s: -j - row - 1,
+                    }
+                };
+                RoadPosition::new(top, bottom, None).unwrap()
+            })
+            .collect()
+    }
+
+    // where the road after `index` starts
+    fn after(roads: &[RoadPosition], index: usize) -> BuildingPosition {
+        let next = roads[index + 1].ends();
+        roads[index]
+            .ends()
+            .into_iter()
+            .find(|end| next.contains(end))
+            .unwrap()
+    }
+
+    // the road from the side that ends where the road after `index` starts, so another player can
+    // build a town there
+    fn spur(roads: &[RoadPosition], index: usize) -> RoadPosition {
+        roads_around(5, after(roads, index))
+            .find(|road| !roads.contains(road))
+            .unwrap()
+    }
+
+    // these tests are about who holds the longest road, not whose turn it is, so the helpers below
+    // put the game in whatever turn and phase lets `player` build instead of playing it there
+    // (the scenarios play whole games)
+
+    // the longest road events from `action`, what it costs is given to `player` first
+    fn place(rules: &mut GameState, player: PlayerHandle, action: Action) -> Events {
+        let cost = match action {
+            Action::PlaceRoad(_) if !matches!(rules.phase, Phase::Setup { .. }) => ROAD_RESOURCES,
+            Action::PlaceTown(_) => TOWN_RESOURCES,
+            _ => Resources::empty(),
+        };
+        rules.bank = rules.bank.checked_sub(cost).unwrap();
+        rules.players[player.0].resources += cost;
+        rules
+            .apply(player, action)
+            .unwrap_or_else(|error| panic!("{player:?} {action:?} was rejected: {error}"))
+            .into_iter()
+            .filter(|event| matches!(event, Event::LongestRoad(_)))
+            .collect()
+    }
+
+    // makes it `player`s turn for every road, a road that doesn't connect to anything of theirs
+    // is placed by pretending it is their setup road
+    fn build_roads(rules: &mut GameState, player: PlayerHandle, roads: &[RoadPosition]) -> Events {
+        let mut events = vec![];
+        for road in roads {
+            rules.current = player;
+            rules.phase = if rules.road_placements(player).contains(road) {
+                Phase::Turn
+            } else {
+                Phase::Setup {
+                    turn: player.0,
+                    road_placed: false,
+                }
+            };
+            events.extend(place(rules, player, Action::PlaceRoad(*road)));
+        }
+        rules.phase = Phase::Turn;
+        events
+    }
+
+    // makes it `player`s turn
+    fn build_town(rules: &mut GameState, player: PlayerHandle, town: BuildingPosition) -> Events {
+        rules.current = player;
+        rules.phase = Phase::Turn;
+        place(rules, player, Action::PlaceTown(town))
+    }
+
+    fn points(rules: &GameState, player: PlayerHandle) -> u8 {
+        rules.players[player.0].victory_points.actual
+    }
+
+    const A: PlayerHandle = PlayerHandle(0);
+    const B: PlayerHandle = PlayerHandle(1);
+    const C: PlayerHandle = PlayerHandle(2);
+
+    #[test]
+    fn zigzag_is_one_road() {
+        let roads = zigzag(0, 9);
+        assert_eq!(RoadGraph::new(&roads, &[], &[], &[]).longest().len(), 9);
+    }
+
+    #[test]
+    fn first_to_five_gets_it() {
+        let mut rules = game(2);
+        let roads = zigzag(0, 5);
+        assert!(build_roads(&mut rules, A, &roads[..4]).is_empty());
+        assert_eq!(rules.longest_road.0, None);
+        let events = build_roads(&mut rules, A, &roads[4..]);
+        assert_eq!(events, vec![Event::LongestRoad(Some(A))]);
+        assert_eq!(rules.longest_road, (Some(A), 5));
+        assert_eq!(points(&rules, A), 2);
+    }
+
+    #[test]
+    fn tie_keeps_the_holder() {
+        let mut rules = game(2);
+        build_roads(&mut rules, A, &zigzag(0, 5));
+        let roads = zigzag(-2, 6);
+        assert!(build_roads(&mut rules, B, &roads[..5]).is_empty());
+        assert_eq!(rules.longest_road, (Some(A), 5));
+        // longer takes it
+        let events = build_roads(&mut rules, B, &roads[5..]);
+        assert_eq!(events, vec![Event::LongestRoad(Some(B))]);
+        assert_eq!(rules.longest_road, (Some(B), 6));
+        assert_eq!((points(&rules, A), points(&rules, B)), (0, 2));
+    }
+
+    // the special building phase (set directly, like the turns) builds roads when it isn't that
+    // players turn
+    #[test]
+    fn roads_of_other_players_count() {
+        let mut rules = game(2);
+        let roads = zigzag(0, 5);
+        build_roads(&mut rules, B, &roads[..4]);
+        rules.current = A;
+        rules.phase = Phase::SpecialBuilding(B);
+        let events = place(&mut rules, B, Action::PlaceRoad(roads[4]));
+        assert_eq!(events, vec![Event::LongestRoad(Some(B))]);
+        assert_eq!(rules.longest_road, (Some(B), 5));
+    }
+
+    #[test]
+    fn broken_road_still_longest_keeps_it() {
+        let mut rules = game(3);
+        let roads = zigzag(0, 9);
+        build_roads(&mut rules, A, &roads);
+        build_roads(&mut rules, B, &zigzag(-2, 5));
+        build_roads(&mut rules, C, &[spur(&roads, 5)]);
+        let events = build_town(&mut rules, C, after(&roads, 5));
+        assert!(events.is_empty());
+        assert_eq!(rules.players[A.0].longest_road.len(), 6);
+        assert_eq!(rules.longest_road, (Some(A), 6));
+    }
+
+    #[test]
+    fn broken_road_goes_to_the_next_longest() {
+        let mut rules = game(3);
+        let roads = zigzag(0, 7);
+        build_roads(&mut rules, A, &roads);
+        build_roads(&mut rules, B, &zigzag(-2, 6));
+        build_roads(&mut rules, C, &[spur(&roads, 2)]);
+        let events = build_town(&mut rules, C, after(&roads, 2));
+        assert_eq!(events, vec![Event::LongestRoad(Some(B))]);
+        assert_eq!(rules.longest_road, (Some(B), 6));
+        assert_eq!((points(&rules, A), points(&rules, B)), (0, 2));
+    }
+
+    #[test]
+    fn broken_road_with_a_tie_goes_to_nobody() {
+        let mut rules = game(4);
+        let roads = zigzag(0, 9);
+        build_roads(&mut rules, B, &zigzag(-2, 6));
+        build_roads(&mut rules, C, &zigzag(2, 6));
+        assert_eq!(rules.longest_road, (Some(B), 6));
+        build_roads(&mut rules, A, &roads);
+        assert_eq!(rules.longest_road, (Some(A), 9));
+        build_roads(&mut rules, PlayerHandle(3), &[spur(&roads, 3)]);
+        let events = build_town(&mut rules, PlayerHandle(3), after(&roads, 3));
+        assert_eq!(events, vec![Event::LongestRoad(None)]);
+        assert_eq!(rules.longest_road, (None, 4));
+        assert_eq!(points(&rules, A) + points(&rules, B) + points(&rules, C), 0);
+        // until someone is longer than everyone else
+        let events = build_roads(&mut rules, C, &zigzag(2, 7)[6..]);
+        assert_eq!(events, vec![Event::LongestRoad(Some(C))]);
+    }
+
+    #[test]
+    fn broken_below_five_goes_to_nobody() {
+        let mut rules = game(2);
+        let roads = zigzag(0, 6);
+        build_roads(&mut rules, A, &roads);
+        build_roads(&mut rules, B, &[spur(&roads, 2)]);
+        let events = build_town(&mut rules, B, after(&roads, 2));
+        assert_eq!(events, vec![Event::LongestRoad(None)]);
+        assert_eq!(rules.longest_road.0, None);
+        assert_eq!(points(&rules, A), 0);
+    }
+
+    #[test]
+    fn own_town_does_not_break_the_road() {
+        let mut rules = game(1);
+        let roads = zigzag(0, 5);
+        build_roads(&mut rules, A, &roads);
+        assert!(build_town(&mut rules, A, after(&roads, 2)).is_empty());
+        assert_eq!(rules.longest_road, (Some(A), 5));
+    }
+}
@@ -0,0 +1,210 @@
+//! saving and loading games
+//! the rules have the whole game (board, pieces, bank, development cards, robber, turn order and
+//! phase) so we just save them along with the rng so the dice keep rolling the same way
+use std::{fmt, path::Path};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    GameState, KatanComponent, LocalPlayer, PlayerEntities, PlayerHandle, Rules, SessionRng,
+    colors::CurrentColor,
+    development_card_actions::{RoadBuildingState, YearOfPlentyState},
+    rules,
+};
+use crate::{
+    AppState,
+    common_ui::{self, ButtonInteraction},
+    utils::stable_hash,
+};
+
+/// bump this whenever the format changes
+// 2: which seats are bots
+// 3: if the game was played on one machine
+pub const SAVE_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SaveFile {
+    pub version: u32,
+    // who saved the game, so when loading they get the same handle back
+    pub player: PlayerHandle,
+    pub rules: rules::GameState,
+    pub rng: Xoshiro256PlusPlus,
+    pub seating: Seating,
+}
+
+/// how the seats of the game are filled, so a loaded game is played the same way
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Seating {
+    // every bot, not only the ones this peer runs
+    pub bots: Vec<PlayerHandle>,
+    // everyone played on this machine (hot seat or a single player with bots), so there is no
+    // one to wait for when loading it
+    pub local: bool,
+}
+impl Seating {
+    /// the seats that aren't bots
+    pub fn players(&self, seats: usize) -> impl Iterator<Item = PlayerHandle> + '_ {
+        (0..seats)
+            .map(PlayerHandle)
+            .filter(|handle| !self.bots.contains(handle))
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+}
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Format(error) => write!(f, "not a valid save: {error}"),
+            Self::Version(version) => write!(
+                f,
+                "save is from version {version}, but only version {SAVE_VERSION} is supported"
+            ),
+        }
+    }
+}
+impl std::error::Error for SaveError {}
+impl From<std::io::Error> for SaveError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+impl From<serde_json::Error> for SaveError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+impl SaveFile {
+    pub fn new(player: PlayerHandle, rules: &Rules, rng: &SessionRng, seating: &Seating) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            player,
+            rules: rules.0.clone(),
+            rng: rng.0.clone(),
+            seating: seating.clone(),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        // check the version first, so we can give a better error then whatever field changed
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let file = std::fs::read_to_string(path)?;
+        let Version { version } = serde_json::from_str(&file)?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version(version));
+        }
+        Ok(serde_json::from_str(&file)?)
+    }
+    /// identifies the game (but not who saved it), so we can check that everyone loaded the same
+    /// game
+    // the rules only keep ordered sets, so every peer serializes the same game the same way
+    pub fn id(&self) -> u64 {
+        stable_hash(&(&self.rules, &self.rng))
+    }
+    /// what we send to the other peers when loading a game, our handle and what game we loaded
+    pub fn claim(&self) -> Box<[u8]> {
+        (self.player.0 as u64)
+            .to_le_bytes()
+            .into_iter()
+            .chain(self.id().to_le_bytes())
+            .collect()
+    }
+}
+pub fn decode_claim(packet: &[u8]) -> Option<(PlayerHandle, u64)> {
+    let (handle, id) = packet.split_at_checked(8)?;
+    Some((
+        PlayerHandle(u64::from_le_bytes(handle.try_into().ok()?) as usize),
+        u64::from_le_bytes(id.try_into().ok()?),
+    ))
+}
+
+/// the save picked in the lobby, the game is set up from this instead of from the seed
+#[derive(Resource, Debug, Clone)]
+pub struct LoadedGame(pub SaveFile);
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Seating>()
+            .add_systems(
+                Update,
+                common_ui::button_system_with_generic::<SaveButton, SaveButtonState<'_>>
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                OnEnter(GameState::Start),
+                resume
+                    .after(super::turn_ui::show_turn_ui)
+                    .run_if(resource_exists::<LoadedGame>),
+            );
+    }
+}
+
+#[derive(Component, Default, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct SaveButton;
+#[derive(SystemParam)]
+pub struct SaveButtonState<'w> {
+    rules: Res<'w, Rules>,
+    rng: Res<'w, SessionRng>,
+    seating: Res<'w, Seating>,
+    local_player: Res<'w, LocalPlayer>,
+}
+impl ButtonInteraction<SaveButton> for SaveButtonState<'_> {
+    fn interact(&mut self, _: &SaveButton) {
+        // every peer has the same game, but the handle is different so each one gets its own file
+        let path = format!("katan-{}.save", self.local_player.0.handle.0);
+        let save = SaveFile::new(
+            self.local_player.0.handle,
+            &self.rules,
+            &self.rng,
+            &self.seating,
+        );
+        match save.save(&path) {
+            Ok(()) => info!("saved game to {path}"),
+            Err(error) => error!("could not save game to {path}: {error}"),
+        }
+    }
+}
+
+// the start of the game always goes to the first setup turn, so for a loaded game we have to jump
+// to where the game was saved
+fn resume(
+    mut commands: Commands<'_, '_>,
+    rules: Res<'_, Rules>,
+    local_player: Res<'_, LocalPlayer>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+    mut road_building_state: ResMut<'_, NextState<RoadBuildingState>>,
+    mut year_of_plenty_state: ResMut<'_, NextState<YearOfPlentyState>>,
+    players: PlayerEntities<'_, '_>,
+    mut color_r: ResMut<'_, CurrentColor>,
+) {
+    // the builder takes over the current color in the special building phase
+    if let rules::Phase::SpecialBuilding(builder) = rules.phase
+        && let Some(builder) = players.get(builder)
+    {
+        color_r.0 = builder;
+    }
+    super::set_state_from_rules(
+        &rules,
+        local_player.0.handle,
+        &mut game_state,
+        &mut road_building_state,
+        &mut year_of_plenty_state,
+    );
+    commands.remove_resource::<LoadedGame>();
+}
@@ -0,0 +1,571 @@
+//! scripted games played against the rules, the inputs go through `Input::apply` the same way
+//! `update_rules` does it, so a script is what the players would actually send
+//! the ui only copies the rules (`sync_rules`), so checking the rules checks the resources, victory
+//! points, longest road and largest army the players see
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{
+    Hexagon, Input, Number, PlayerHandle, Rules, SessionRng, VictoryPoints,
+    colors::CatanColor,
+    development_cards::DevelopmentCard,
+    game_rules::GameRules,
+    positions::{self, BuildingPosition, Position, RoadPosition},
+    resources::{Resource, Resources},
+    resources_management::TradingResources,
+    rules::{Action, Board, Event, Events, GameState, Phase, RuleError, TradeId},
+    save::{SaveFile, Seating},
+};
+use crate::utils::CheckedSub;
+
+const A: PlayerHandle = PlayerHandle(0);
+const B: PlayerHandle = PlayerHandle(1);
+const C: PlayerHandle = PlayerHandle(2);
+const SIZE: u8 = 3;
+
+const fn hex(q: i8, r: i8) -> Position {
+    Position { q, r, s: -q - r }
+}
+
+// the hexes around the middle that produce, the rest of the board is desert
+const HEXES: [(Position, Hexagon, u8); 5] = [
+    (hex(0, 0), Hexagon::Wood, 6),
+    (hex(1, -1), Hexagon::Brick, 8),
+    (hex(0, -1), Hexagon::Wheat, 5),
+    (hex(1, 0), Hexagon::Ore, 9),
+    (hex(-1, 0), Hexagon::Sheep, 4),
+];
+// no hex has a 3, so rolling it only moves the game along
+const NOTHING: (u8, u8) = (1, 2);
+
+fn road(p1: Position, p2: Position) -> RoadPosition {
+    RoadPosition::new(p1, p2, Some(SIZE)).unwrap()
+}
+fn building(p1: Position, p2: Position, p3: Position) -> BuildingPosition {
+    BuildingPosition::new(p1, p2, p3, Some(SIZE)).unwrap()
+}
+
+// wood, brick and wheat
+fn first_a_town() -> BuildingPosition {
+    building(hex(0, 0), hex(1, -1), hex(0, -1))
+}
+// wood and sheep
+fn second_a_town() -> BuildingPosition {
+    building(hex(0, 0), hex(-1, 0), hex(-1, 1))
+}
+// wood and ore
+fn first_b_town() -> BuildingPosition {
+    building(hex(0, 0), hex(1, 0), hex(0, 1))
+}
+// brick and ore
+fn second_b_town() -> BuildingPosition {
+    building(hex(1, 0), hex(2, -1), hex(1, -1))
+}
+
+struct Scenario {
+    rules: GameState,
+    rng: SessionRng,
+}
+impl Scenario {
+    fn new(players: usize, development_cards: Vec<DevelopmentCard>, game_rules: GameRules) -> Self {
+        let hexes = positions::generate_postions(SIZE as i8)
+            .map(|position| {
+                HEXES.iter().find(|(hex, _, _)| *hex == position).map_or(
+                    (position, Hexagon::Desert, Number::None),
+                    |(_, hex, number)| (position, *hex, Number::Number(*number)),
+                )
+            })
+            .collect();
+        let board = Board {
+            size: SIZE,
+            hexes,
+            ports: vec![],
+        };
+        let colors = [CatanColor::Red, CatanColor::Blue, CatanColor::White];
+        let mut rules = GameState::new(board, colors.into_iter().take(players), development_cards);
+        rules.set_game_rules(game_rules);
+        Self {
+            rules,
+            rng: SessionRng(Xoshiro256PlusPlus::seed_from_u64(0)),
+        }
+    }
+
+    /// two players that placed their towns in the middle of the board, and now `A` has to roll
+    fn after_setup(development_cards: Vec<DevelopmentCard>, game_rules: GameRules) -> Self {
+        let mut scenario = Self::new(2, development_cards, game_rules);
+        scenario.play(&[
+            (A, Input::AddRoad(road(hex(0, 0), hex(1, -1)))),
+            (A, Input::AddTown(first_a_town())),
+            (B, Input::AddRoad(road(hex(0, 0), hex(1, 0)))),
+            (B, Input::AddTown(first_b_town())),
+            (B, Input::AddRoad(road(hex(1, 0), hex(1, -1)))),
+            (B, Input::AddTown(second_b_town())),
+            (A, Input::AddRoad(road(hex(0, 0), hex(-1, 0)))),
+            (A, Input::AddTown(second_a_town())),
+        ]);
+        scenario
+    }
+
+    /// `after_setup` with a third player, whose towns are on the desert so they start with nothing
+    fn after_setup_with_three_players(game_rules: GameRules) -> Self {
+        let mut scenario = Self::new(3, vec![], game_rules);
+        scenario.play(&[
+            (A, Input::AddRoad(road(hex(0, 0), hex(1, -1)))),
+            (A, Input::AddTown(first_a_town())),
+            (B, Input::AddRoad(road(hex(0, 0), hex(1, 0)))),
+            (B, Input::AddTown(first_b_town())),
+            (C, Input::AddRoad(road(hex(0, -1), hex(-1, 0)))),
+            (
+                C,
+                Input::AddTown(building(hex(0, -1), hex(-1, 0), hex(-1, -1))),
+            ),
+            (C, Input::AddRoad(road(hex(0, 1), hex(0, 2)))),
+            (
+                C,
+                Input::AddTown(building(hex(0, 1), hex(-1, 2), hex(0, 2))),
+            ),
+            (B, Input::AddRoad(road(hex(1, 0), hex(1, -1)))),
+            (B, Input::AddTown(second_b_town())),
+            (A, Input::AddRoad(road(hex(0, 0), hex(-1, 0)))),
+            (A, Input::AddTown(second_a_town())),
+        ]);
+        scenario
+    }
+
+    fn input(&mut self, player: PlayerHandle, input: Input) -> Result<Events, RuleError> {
+        input.apply(player, &mut self.rules, &mut self.rng)
+    }
+
+    // every input in the script has to be accepted
+    fn play(&mut self, script: &[(PlayerHandle, Input)]) -> Events {
+        script
+            .iter()
+            .flat_map(|(player, input)| {
+                self.input(*player, *input)
+                    .unwrap_or_else(|error| panic!("{player:?} {input:?} was rejected: {error}"))
+            })
+            .collect()
+    }
+
+    // in a game the dice come from the `SessionRng`, scripts pick them so they don't depend on the
+    // seed
+    fn roll(&mut self, player: PlayerHandle, (d1, d2): (u8, u8)) -> Events {
+        self.rules.apply(player, Action::Roll(d1, d2)).unwrap()
+    }
+
+    // the other player rolls nothing and passes the turn back
+    fn skip_turn(&mut self, player: PlayerHandle, other: PlayerHandle) {
+        self.play(&[(player, Input::NextColor)]);
+        self.roll(other, NOTHING);
+        self.play(&[(other, Input::NextColor)]);
+    }
+
+    // takes it from the bank, as if it was produced before the scenario
+    fn give(&mut self, player: PlayerHandle, resources: Resources) {
+        self.rules.bank = self.rules.bank.checked_sub(resources).unwrap();
+        self.rules.players[player.0].resources += resources;
+    }
+
+    fn resources(&self, player: PlayerHandle) -> Resources {
+        self.rules.players[player.0].resources
+    }
+
+    fn victory_points(&self, player: PlayerHandle) -> VictoryPoints {
+        self.rules.players[player.0].victory_points
+    }
+}
+
+// from the current players side, they give the negative part and get the positive part
+fn trade(giving: Resource, taking: Resource) -> TradingResources {
+    let mut trade = TradingResources::default();
+    *trade.get_mut(giving) -= 1;
+    *trade.get_mut(taking) += 1;
+    trade
+}
+
+#[test]
+fn setup_places_in_snake_order_and_pays_out_the_second_town() {
+    let mut scenario = Scenario::new(2, vec![], GameRules::default());
+    let road = road(hex(0, 0), hex(1, -1));
+    assert_eq!(
+        scenario.input(A, Input::AddTown(first_a_town())),
+        Err(RuleError::WrongPhase)
+    );
+    assert_eq!(
+        scenario.input(B, Input::AddRoad(road)),
+        Err(RuleError::NotYourTurn)
+    );
+    let scenario = Scenario::after_setup(vec![], GameRules::default());
+    assert_eq!(scenario.rules.phase, Phase::Roll);
+    assert_eq!(scenario.rules.current, A);
+    for player in [A, B] {
+        assert_eq!(scenario.victory_points(player).actual, 2);
+        assert_eq!(scenario.rules.players[player.0].towns_left, 3);
+    }
+    assert_eq!(scenario.resources(A), Resources::new(1, 0, 1, 0, 0));
+    assert_eq!(scenario.resources(B), Resources::new(0, 1, 0, 0, 1));
+    assert_eq!(scenario.rules.bank, Resources::new(18, 18, 18, 19, 18));
+}
+
+#[test]
+fn setup_towns_keep_their_distance() {
+    let mut scenario = Scenario::new(2, vec![], GameRules::default());
+    scenario.play(&[
+        (A, Input::AddRoad(road(hex(0, 0), hex(1, -1)))),
+        (A, Input::AddTown(first_a_town())),
+        (B, Input::AddRoad(road(hex(0, 0), hex(1, 0)))),
+    ]);
+    // right next to the first town
+    let next_door = building(hex(0, 0), hex(1, -1), hex(1, 0));
+    assert_eq!(
+        scenario.input(B, Input::AddTown(next_door)),
+        Err(RuleError::IllegalPlacement)
+    );
+}
+
+#[test]
+fn production_goes_to_every_town_on_the_number() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    let events = scenario.roll(A, (3, 3));
+    assert!(events.contains(&Event::Produced(A, Resources::new(2, 0, 0, 0, 0))));
+    assert!(events.contains(&Event::Produced(B, Resources::new(1, 0, 0, 0, 0))));
+    assert_eq!(scenario.resources(A), Resources::new(3, 0, 1, 0, 0));
+    assert_eq!(scenario.resources(B), Resources::new(1, 1, 0, 0, 1));
+    assert_eq!(scenario.rules.phase, Phase::Turn);
+    // one roll a turn
+    assert_eq!(scenario.input(A, Input::Roll), Err(RuleError::WrongPhase));
+    scenario.play(&[(A, Input::NextColor)]);
+    scenario.roll(B, (4, 4));
+    assert_eq!(scenario.resources(A), Resources::new(3, 1, 1, 0, 0));
+    assert_eq!(scenario.resources(B), Resources::new(1, 2, 0, 0, 1));
+}
+
+// both players have a town on the wood
+#[test]
+fn short_bank_pays_nobody_when_several_players_want_it() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.give(B, Resources::new(16, 0, 0, 0, 0));
+    let events = scenario.roll(A, (3, 3));
+    assert_eq!(
+        events,
+        vec![Event::Rolled(3, 3), Event::BankShortage(Resource::Wood)]
+    );
+    assert_eq!(scenario.resources(A), Resources::new(1, 0, 1, 0, 0));
+    assert_eq!(scenario.resources(B), Resources::new(16, 1, 0, 0, 1));
+    assert_eq!(scenario.rules.bank.wood, 2);
+}
+
+// only `B` has towns on the ore
+#[test]
+fn short_bank_pays_what_is_left_to_the_only_player() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.give(A, Resources::new(0, 0, 0, 0, 17));
+    let events = scenario.roll(A, (4, 5));
+    assert!(events.contains(&Event::BankShortage(Resource::Ore)));
+    assert!(events.contains(&Event::Produced(B, Resources::new(0, 0, 0, 0, 1))));
+    assert_eq!(scenario.resources(B), Resources::new(0, 1, 0, 0, 2));
+    assert_eq!(scenario.rules.bank.ore, 0);
+}
+
+#[test]
+fn seven_makes_big_hands_discard_and_moves_the_robber() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.give(A, Resources::new(3, 3, 2, 0, 0));
+    assert_eq!(scenario.resources(A).count(), 10);
+    scenario.roll(A, (3, 4));
+    // only `A` has more than 7
+    assert_eq!(scenario.rules.phase, Phase::Discard(vec![(A, 5)]));
+    assert_eq!(
+        scenario.input(A, Input::RobberDiscard(Resources::new(2, 2, 0, 0, 0))),
+        Err(RuleError::InvalidDiscard)
+    );
+    assert_eq!(
+        scenario.input(B, Input::RobberDiscard(Resources::new(0, 1, 0, 0, 0))),
+        Err(RuleError::NotYourTurn)
+    );
+    scenario.play(&[(A, Input::RobberDiscard(Resources::new(2, 2, 1, 0, 0)))]);
+    assert_eq!(scenario.resources(A), Resources::new(2, 1, 2, 0, 0));
+    assert_eq!(
+        scenario.rules.phase,
+        Phase::PlaceRobber { then_roll: false }
+    );
+    // `B` has a town there, so something has to be taken
+    assert_eq!(
+        scenario.input(A, Input::MoveKnight(hex(1, 0))),
+        Err(RuleError::InvalidRobber)
+    );
+    scenario.play(&[(A, Input::Knight(B, hex(1, 0)))]);
+    assert_eq!(scenario.rules.robber, hex(1, 0));
+    assert_eq!(scenario.resources(A).count(), 6);
+    assert_eq!(scenario.resources(B).count(), 1);
+    assert_eq!(scenario.rules.phase, Phase::Turn);
+    // the robber blocks the ore
+    scenario.play(&[(A, Input::NextColor)]);
+    let events = scenario.roll(B, (4, 5));
+    assert_eq!(events, vec![Event::Rolled(4, 5)]);
+}
+
+#[test]
+fn monopoly_takes_everyones_resource() {
+    let mut scenario = Scenario::after_setup(vec![DevelopmentCard::Monopoly], GameRules::default());
+    scenario.give(A, Resources::new(0, 0, 0, 1, 1));
+    scenario.give(B, Resources::new(3, 0, 0, 0, 0));
+    scenario.roll(A, NOTHING);
+    scenario.play(&[(A, Input::TakeDevelopmentCard)]);
+    assert_eq!(
+        scenario.input(A, Input::PlayDevelopmentCard(DevelopmentCard::Monopoly)),
+        Err(RuleError::NewDevelopmentCard)
+    );
+    scenario.skip_turn(A, B);
+    scenario.roll(A, NOTHING);
+    let events = scenario.play(&[
+        (A, Input::PlayDevelopmentCard(DevelopmentCard::Monopoly)),
+        (A, Input::Monopoly(Resource::Wood)),
+    ]);
+    assert!(events.contains(&Event::Monopolized(A, Resource::Wood, 3)));
+    assert_eq!(scenario.resources(A).wood, 4);
+    assert_eq!(scenario.resources(B).wood, 0);
+    assert_eq!(scenario.rules.phase, Phase::Turn);
+    assert_eq!(
+        scenario.rules.players[A.0]
+            .development_cards
+            .get(DevelopmentCard::Monopoly),
+        0
+    );
+}
+
+#[test]
+fn year_of_plenty_only_takes_what_the_bank_has() {
+    let mut scenario =
+        Scenario::after_setup(vec![DevelopmentCard::YearOfPlenty], GameRules::default());
+    scenario.give(A, Resources::new(0, 0, 0, 1, 1));
+    scenario.roll(A, NOTHING);
+    scenario.play(&[(A, Input::TakeDevelopmentCard)]);
+    scenario.skip_turn(A, B);
+    scenario.roll(A, NOTHING);
+    let bank = scenario.rules.bank;
+    scenario.give(B, bank);
+    assert_eq!(
+        scenario.input(A, Input::PlayDevelopmentCard(DevelopmentCard::YearOfPlenty)),
+        Err(RuleError::BankCannotAfford)
+    );
+    // `B` puts a wood back
+    scenario.rules.players[B.0].resources.wood -= 1;
+    scenario.rules.bank.wood += 1;
+    scenario.play(&[(A, Input::PlayDevelopmentCard(DevelopmentCard::YearOfPlenty))]);
+    assert_eq!(scenario.rules.phase, Phase::YearOfPlenty(2));
+    assert_eq!(
+        scenario.input(A, Input::YearOfPlenty(Resource::Sheep)),
+        Err(RuleError::BankCannotAfford)
+    );
+    let events = scenario.play(&[(A, Input::YearOfPlenty(Resource::Wood))]);
+    assert_eq!(events, vec![Event::YearOfPlenty(A, Resource::Wood)]);
+    assert_eq!(scenario.resources(A), Resources::new(2, 0, 0, 0, 0));
+    // there is nothing left for the second one
+    assert_eq!(scenario.rules.bank, Resources::empty());
+    assert_eq!(scenario.rules.phase, Phase::Turn);
+}
+
+#[test]
+fn trade_needs_an_answer_from_the_other_player() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.roll(A, NOTHING);
+    // `A` only has sheep and wood
+    assert_eq!(
+        scenario.input(A, Input::Trade(trade(Resource::Wheat, Resource::Ore), None)),
+        Err(RuleError::CannotAfford)
+    );
+    let wood_for_ore = trade(Resource::Wood, Resource::Ore);
+    scenario.play(&[(A, Input::Trade(wood_for_ore, None))]);
+    // only the other side of the offer can take it up
+    assert_eq!(
+        scenario.input(A, Input::TradeResponce(TradeId(0))),
+        Err(RuleError::UnknownTrade)
+    );
+    let events = scenario.play(&[
+        (B, Input::TradeResponce(TradeId(0))),
+        (A, Input::TradeAccept(TradeId(1))),
+    ]);
+    assert!(events.contains(&Event::Traded(A, B, wood_for_ore)));
+    assert_eq!(scenario.resources(A), Resources::new(0, 0, 1, 0, 1));
+    assert_eq!(scenario.resources(B), Resources::new(1, 1, 0, 0, 0));
+    // the first offer can't be done anymore, `A` has no wood left
+    assert!(events.contains(&Event::TradeInvalidated(TradeId(0))));
+    assert!(scenario.rules.trades.is_empty());
+}
+
+#[test]
+fn spending_what_was_offered_takes_the_offer_away() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.give(A, Resources::new(0, 1, 0, 0, 0));
+    scenario.roll(A, NOTHING);
+    scenario.play(&[(A, Input::Trade(trade(Resource::Wood, Resource::Ore), None))]);
+    // the road takes `A`s only wood
+    let events = scenario.play(&[(A, Input::AddRoad(road(hex(1, -1), hex(0, -1))))]);
+    assert!(events.contains(&Event::TradeInvalidated(TradeId(0))));
+    assert!(scenario.rules.trades.is_empty());
+    assert_eq!(
+        scenario.input(B, Input::TradeResponce(TradeId(0))),
+        Err(RuleError::UnknownTrade)
+    );
+}
+
+#[test]
+fn only_the_addressed_player_answers_an_offer() {
+    let mut scenario = Scenario::after_setup_with_three_players(GameRules::default());
+    scenario.roll(A, NOTHING);
+    let sheep_for_ore = trade(Resource::Sheep, Resource::Ore);
+    scenario.play(&[(A, Input::Trade(sheep_for_ore, Some(B)))]);
+    for input in [
+        Input::TradeResponce(TradeId(0)),
+        Input::CounterOffer(TradeId(0), trade(Resource::Sheep, Resource::Wood)),
+        Input::TradeAccept(TradeId(0)),
+    ] {
+        assert_eq!(scenario.input(C, input), Err(RuleError::UnknownTrade));
+    }
+    scenario.play(&[(B, Input::TradeResponce(TradeId(0)))]);
+    // the answer only goes back to `A`
+    assert_eq!(
+        scenario.input(C, Input::TradeAccept(TradeId(1))),
+        Err(RuleError::UnknownTrade)
+    );
+    let events = scenario.play(&[(A, Input::TradeAccept(TradeId(1)))]);
+    assert!(events.contains(&Event::Traded(A, B, sheep_for_ore)));
+    assert_eq!(scenario.resources(C), Resources::empty());
+}
+
+#[test]
+fn counter_offers_and_cancelled_trades() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.roll(A, NOTHING);
+    scenario.play(&[
+        (
+            A,
+            Input::Trade(trade(Resource::Sheep, Resource::Ore), Some(B)),
+        ),
+        (
+            B,
+            Input::CounterOffer(TradeId(0), trade(Resource::Sheep, Resource::Brick)),
+        ),
+        (A, Input::CancelTrade(TradeId(0))),
+    ]);
+    assert_eq!(
+        scenario.input(B, Input::TradeResponce(TradeId(0))),
+        Err(RuleError::UnknownTrade)
+    );
+    // the counter offer is still there
+    scenario.play(&[(A, Input::TradeAccept(TradeId(1)))]);
+    assert_eq!(scenario.resources(A), Resources::new(1, 1, 0, 0, 0));
+    assert_eq!(scenario.resources(B), Resources::new(0, 0, 1, 0, 1));
+    // offers don't last past the turn
+    scenario.play(&[(
+        A,
+        Input::Trade(trade(Resource::Wood, Resource::Sheep), None),
+    )]);
+    scenario.play(&[(A, Input::NextColor)]);
+    assert!(scenario.rules.trades.is_empty());
+}
+
+#[test]
+fn third_knight_gets_largest_army() {
+    let mut scenario =
+        Scenario::after_setup(vec![DevelopmentCard::Knight; 3], GameRules::default());
+    scenario.give(A, Resources::new(0, 0, 2, 3, 3));
+    scenario.roll(A, NOTHING);
+    scenario.play(&[(A, Input::TakeDevelopmentCard); 3]);
+    // knights are played before rolling, the robber starts on the first desert and goes back and
+    // forth between two of them
+    assert_eq!(scenario.rules.robber, hex(-2, 0));
+    for (turn, desert) in [hex(2, 0), hex(-2, 0), hex(2, 0)].into_iter().enumerate() {
+        scenario.skip_turn(A, B);
+        scenario.play(&[
+            (A, Input::PlayDevelopmentCard(DevelopmentCard::Knight)),
+            (A, Input::MoveKnight(desert)),
+        ]);
+        assert_eq!(scenario.rules.phase, Phase::Roll);
+        assert_eq!(scenario.rules.players[A.0].knights as usize, turn + 1);
+        // one a turn, even with more in hand
+        if turn < 2 {
+            assert_eq!(
+                scenario.input(A, Input::PlayDevelopmentCard(DevelopmentCard::Knight)),
+                Err(RuleError::AlreadyPlayedDevelopmentCard)
+            );
+        }
+        scenario.roll(A, NOTHING);
+    }
+    assert_eq!(scenario.rules.largest_army, (Some(A), 3));
+    assert_eq!(scenario.victory_points(A).actual, 4);
+    assert_eq!(scenario.rules.longest_road.0, None);
+}
+
+#[test]
+fn building_to_the_target_wins() {
+    let game_rules = GameRules {
+        victory_points: Some(4),
+        ..GameRules::default()
+    };
+    let mut scenario = Scenario::after_setup(vec![DevelopmentCard::VictoryPoint], game_rules);
+    scenario.give(A, Resources::new(2, 3, 1, 2, 1));
+    scenario.roll(A, NOTHING);
+    let events = scenario.play(&[
+        (A, Input::AddRoad(road(hex(1, -1), hex(0, -1)))),
+        (A, Input::AddRoad(road(hex(1, -1), hex(1, -2)))),
+        (
+            A,
+            Input::AddTown(building(hex(1, -1), hex(1, -2), hex(2, -2))),
+        ),
+    ]);
+    assert!(!events.contains(&Event::Won(A)));
+    assert_eq!(scenario.victory_points(A).actual, 3);
+    // the victory point card is hidden until it wins the game
+    let events = scenario.play(&[(A, Input::TakeDevelopmentCard)]);
+    assert!(events.contains(&Event::VictoryPointCardsRevealed(A, 1)));
+    assert!(events.contains(&Event::Won(A)));
+    assert_eq!(scenario.rules.phase, Phase::Finished(A));
+    assert_eq!(scenario.resources(A), Resources::empty());
+    assert_eq!(
+        scenario.input(A, Input::NextColor),
+        Err(RuleError::GameOver)
+    );
+    assert_eq!(scenario.input(B, Input::Win), Err(RuleError::WrongPhase));
+    assert_eq!(scenario.input(A, Input::Win), Ok(vec![]));
+}
+
+// every peer loads its own copy of the save and they are compared by id, so the id can't depend on
+// the order a set happens to come out in
+#[test]
+fn loading_a_save_keeps_its_id() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.give(A, Resources::new(1, 2, 0, 0, 0));
+    scenario.roll(A, NOTHING);
+    scenario.play(&[
+        (A, Input::AddRoad(road(hex(1, -1), hex(0, -1)))),
+        (A, Input::AddRoad(road(hex(1, -1), hex(1, -2)))),
+    ]);
+    assert_eq!(scenario.rules.players[A.0].longest_road.len(), 3);
+    let rules = Rules(scenario.rules.clone());
+    let save = SaveFile::new(A, &rules, &scenario.rng, &Seating::default());
+    let file = serde_json::to_string(&save).unwrap();
+    let first = serde_json::from_str::<SaveFile>(&file).unwrap();
+    let second = serde_json::from_str::<SaveFile>(&file).unwrap();
+    assert_eq!(first.id(), save.id());
+    assert_eq!(second.id(), save.id());
+    // the other player saved the same game
+    assert_eq!(
+        SaveFile::new(B, &rules, &scenario.rng, &Seating::default()).id(),
+        save.id()
+    );
+}
+
+// only accepted inputs are in the replay, so a rejected roll or steal can't use up the rng
+#[test]
+fn rejected_inputs_dont_draw_from_the_rng() {
+    let mut scenario = Scenario::after_setup(vec![], GameRules::default());
+    scenario.roll(A, NOTHING);
+    let rng = scenario.rng.0.clone();
+    assert_eq!(scenario.input(A, Input::Roll), Err(RuleError::WrongPhase));
+    assert!(scenario.input(A, Input::Knight(B, hex(1, 0))).is_err());
+    assert_eq!(scenario.rng.0, rng);
+    scenario.play(&[(A, Input::NextColor), (B, Input::Roll)]);
+    assert_ne!(scenario.rng.0, rng);
+}
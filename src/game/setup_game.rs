@@ -11,22 +11,30 @@ use std::{
 use crate::{game::robber::RobberHighlighter, utils::NORMAL_BUTTON};
 
 use super::{
-    Hexagon, KatanComponent, Knights, Left, LocalPlayer, LocalPlayerHandle, Number, PlayerCount,
-    PlayerHandle, Port, Robber, VictoryPoints,
-    cities::City,
+    BoardSize, Hexagon, KatanComponent, Knights, Left, LocalPlayer, LocalPlayerHandle, Number,
+    PlayerCount, PlayerHandle, Port, Robber, Rules, SessionRng, UI, VictoryPoints,
+    balance::{self, BoardConstraints},
+    cities::{City, CityUI},
+    cities_and_knights::{self, CitiesAndKnights},
     colors::{CatanColor, CatanColorRef},
     development_cards::DevelopmentCardsPile,
     development_cards::{DevelopmentCard, DevelopmentCards},
+    game_rules::GameRules,
     longest_road::PlayerLongestRoad,
+    map::MapFile,
     positions::{self, BuildingPosition, FPosition, Position},
     resources::{self, Resources},
-    roads::Road,
-    towns::Town,
+    roads::{Road, RoadUI},
+    rules,
+    save::SaveFile,
+    ships::{self, Ship, ShipUI},
+    towns::{Town, TownUI},
 };
 use bevy::{platform::collections::HashSet, prelude::*};
 use bevy_ggrs::AddRollbackCommandExtension;
 use itertools::Itertools;
 use rand::{Rng, SeedableRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
 pub fn draw_board(
     q: impl Iterator<Item = (Position, Hexagon, Number)>,
     port_q: impl Iterator<Item = (BuildingPosition, Port)>,
@@ -80,8 +88,13 @@ pub fn draw_board(
         }
     }
 }
-fn generate_development_cards(commands: &mut Commands<'_, '_>, rng: &mut Xoshiro256PlusPlus) {
-    let mut development_cards = [
+fn generate_development_cards(
+    commands: &mut Commands<'_, '_>,
+    rng: &mut Xoshiro256PlusPlus,
+    extension: bool,
+    deck: Option<DevelopmentCards>,
+) -> Vec<DevelopmentCard> {
+    let mut development_cards = vec![
         DevelopmentCard::Knight,
         DevelopmentCard::Knight,
         DevelopmentCard::Knight,
@@ -108,93 +121,162 @@ fn generate_development_cards(commands: &mut Commands<'_, '_>, rng: &mut Xoshiro
         DevelopmentCard::YearOfPlenty,
         DevelopmentCard::YearOfPlenty,
     ];
+    if extension {
+        development_cards.extend([
+            DevelopmentCard::Knight,
+            DevelopmentCard::Knight,
+            DevelopmentCard::Knight,
+            DevelopmentCard::Knight,
+            DevelopmentCard::Knight,
+            DevelopmentCard::Knight,
+            DevelopmentCard::RoadBuilding,
+            DevelopmentCard::Monopoly,
+            DevelopmentCard::YearOfPlenty,
+        ]);
+    }
+    // house rules replace the whole pile
+    if let Some(deck) = deck {
+        development_cards = DevelopmentCard::ALL
+            .into_iter()
+            .flat_map(|card| std::iter::repeat_n(card, deck.get(card).into()))
+            .collect();
+    }
     development_cards.shuffle(rng);
 
-    commands.insert_resource(DevelopmentCardsPile(development_cards.to_vec()));
+    commands.insert_resource(DevelopmentCardsPile(development_cards.clone()));
+    development_cards
 }
-fn generate_board(
-    commands: &mut Commands<'_, '_>,
+/// the land with its resources and numbers shuffled onto it, before they are moved around to make
+/// a fair board
+pub fn shuffle_land(
+    land: &[Position],
     rng: &mut Xoshiro256PlusPlus,
-    materials: &mut ResMut<'_, Assets<ColorMaterial>>,
-    meshes: &mut ResMut<'_, Assets<Mesh>>,
 ) -> Vec<(Position, Hexagon, Number)> {
-    let mut numbers = [
-        (Number::Number(2)),
-        (Number::Number(3)),
-        (Number::Number(3)),
-        (Number::Number(4)),
-        (Number::Number(4)),
-        (Number::Number(5)),
-        (Number::Number(5)),
-        (Number::Number(6)),
-        (Number::Number(6)),
-        (Number::Number(8)),
-        (Number::Number(8)),
-        (Number::Number(9)),
-        (Number::Number(9)),
-        (Number::Number(10)),
-        (Number::Number(10)),
-        (Number::Number(11)),
-        (Number::Number(11)),
-        (Number::Number(12)),
-    ];
+    // about 1 desert for every 19 hexes (so the normal board has 1 and the extension board 2), the
+    // other hexes get resources and numbers in the same ratio as the normal board
+    let deserts = ((land.len() + 9) / 19).max(1);
+    let inhabited_count = land.len().saturating_sub(deserts);
+    let mut numbers = scale(
+        &[
+            (Number::Number(2), 1),
+            (Number::Number(3), 2),
+            (Number::Number(4), 2),
+            (Number::Number(5), 2),
+            (Number::Number(6), 2),
+            (Number::Number(8), 2),
+            (Number::Number(9), 2),
+            (Number::Number(10), 2),
+            (Number::Number(11), 2),
+            (Number::Number(12), 1),
+        ],
+        inhabited_count,
+    );
     numbers.shuffle(rng);
-    let inhabited_hexagons = [
-        Hexagon::Wheat,
-        Hexagon::Wheat,
-        Hexagon::Wheat,
-        Hexagon::Wheat,
-        Hexagon::Wood,
-        Hexagon::Wood,
-        Hexagon::Wood,
-        Hexagon::Wood,
-        Hexagon::Sheep,
-        Hexagon::Sheep,
-        Hexagon::Sheep,
-        Hexagon::Sheep,
-        Hexagon::Ore,
-        Hexagon::Ore,
-        Hexagon::Ore,
-        Hexagon::Brick,
-        Hexagon::Brick,
-        Hexagon::Brick,
-    ];
+    let inhabited_hexagons = scale(
+        &[
+            (Hexagon::Wheat, 4),
+            (Hexagon::Wood, 4),
+            (Hexagon::Sheep, 4),
+            (Hexagon::Ore, 3),
+            (Hexagon::Brick, 3),
+        ],
+        inhabited_count,
+    );
     let mut inhabited = inhabited_hexagons
         .into_iter()
         .zip(numbers)
-        .chain([(Hexagon::Desert, Number::None); 1])
+        .chain(iter::repeat_n((Hexagon::Desert, Number::None), deserts))
         .collect_vec();
 
-    // 1 for first layer 6 for second layer 12 for third layer
-
     inhabited.shuffle(rng);
-    let (inhabited, mut desert): (Vec<_>, Vec<_>) = positions::generate_postions(3)
+    land.iter()
+        .copied()
         .zip(inhabited)
         .map(|(position, (hex, number))| (position, hex, number))
-        .partition(|p| p.2 != Number::None);
-    let (reds, normal_number): (Vec<_>, Vec<_>) = inhabited
-        .into_iter()
-        .partition(|(_, _, n)| Number::Number(8) == *n || Number::Number(6) == *n);
-    let mut inhabited = fix_numbers(reds, normal_number, rng);
-    if let Some(desert) = desert.first() {
-        commands.insert_resource(Robber(desert.0));
-        let mesh = meshes.add(Circle::new(30.0));
-        let (x, y) = Into::<FPosition>::into(desert.0).hex_to_pixel();
-        commands.spawn((
-            RobberHighlighter,
-            Mesh2d(mesh),
-            MeshMaterial2d(materials.add(NORMAL_BUTTON.with_alpha(0.9))),
-            Transform::from_xyz(x * 77.0, y * 77., 0.0),
-        ));
+        .collect()
+}
+fn generate_board(
+    commands: &mut Commands<'_, '_>,
+    rng: &mut Xoshiro256PlusPlus,
+    materials: &mut ResMut<'_, Assets<ColorMaterial>>,
+    meshes: &mut ResMut<'_, Assets<Mesh>>,
+    land: &[Position],
+    size: BoardSize,
+    constraints: Option<&BoardConstraints>,
+) -> Vec<(Position, Hexagon, Number)> {
+    let mut inhabited = shuffle_land(land, rng);
+    let mut inhabited = if let Some(constraints) = constraints {
+        balance::balance(&mut inhabited, constraints, rng);
+        inhabited
+    } else {
+        let (inhabited, desert): (Vec<_>, Vec<_>) =
+            inhabited.into_iter().partition(|p| p.2 != Number::None);
+        let (reds, normal_number): (Vec<_>, Vec<_>) = inhabited
+            .into_iter()
+            .partition(|(_, _, n)| Number::Number(8) == *n || Number::Number(6) == *n);
+        let mut inhabited = fix_numbers(reds, normal_number, rng);
+        inhabited.extend(desert);
+        inhabited
+    };
+    info!(
+        "{}",
+        balance::fairness(&inhabited, &constraints.copied().unwrap_or_default())
+    );
+    if let Some(desert) = inhabited
+        .iter()
+        .find(|(_, hex, _)| matches!(hex, Hexagon::Desert))
+    {
+        spawn_robber(commands, desert.0, materials, meshes);
     }
-    inhabited.append(&mut desert);
-    inhabited
-        .extend(positions::generate_postions_ring(3).map(|p| (p, Hexagon::Empty, Number::None)));
+    // everything around the land (and inside the size of the board, for boards that aren't a
+    // hexagon) is water
+    inhabited.extend(
+        positions::generate_postions(size.0 as i8 + 1)
+            .filter(|p| !land.contains(p))
+            .map(|p| (p, Hexagon::Empty, Number::None)),
+    );
     for hex in &inhabited {
         commands.spawn((hex.0, hex.1, hex.2));
     }
     inhabited
 }
+// spreads `total` over the kinds in the same ratio as `counts`, whatever is left after rounding
+// down goes to the kinds with the biggest remainder (the earlier kind on ties), so the normal
+// boards counts are given back exactly and in the same order (which keeps old seeds the same)
+fn scale<T: Copy>(counts: &[(T, usize)], total: usize) -> Vec<T> {
+    let sum = counts.iter().map(|(_, count)| count).sum::<usize>().max(1);
+    let mut scaled = counts
+        .iter()
+        .map(|(kind, count)| (*kind, count * total / sum, count * total % sum))
+        .collect_vec();
+    let left = total - scaled.iter().map(|(_, count, _)| count).sum::<usize>();
+    for i in (0..scaled.len())
+        .sorted_by_key(|i| std::cmp::Reverse(scaled[*i].2))
+        .take(left)
+    {
+        scaled[i].1 += 1;
+    }
+    scaled
+        .into_iter()
+        .flat_map(|(kind, count, _)| iter::repeat_n(kind, count))
+        .collect()
+}
+fn spawn_robber(
+    commands: &mut Commands<'_, '_>,
+    position: Position,
+    materials: &mut ResMut<'_, Assets<ColorMaterial>>,
+    meshes: &mut ResMut<'_, Assets<Mesh>>,
+) {
+    commands.insert_resource(Robber(position));
+    let mesh = meshes.add(Circle::new(30.0));
+    let (x, y) = Into::<FPosition>::into(position).hex_to_pixel();
+    commands.spawn((
+        RobberHighlighter,
+        Mesh2d(mesh),
+        MeshMaterial2d(materials.add(NORMAL_BUTTON.with_alpha(0.9))),
+        Transform::from_xyz(x * 77.0, y * 77., 0.0),
+    ));
+}
 fn fix_numbers(
     mut reds: Vec<(Position, Hexagon, Number)>,
     mut normal: Vec<(Position, Hexagon, Number)>,
@@ -217,6 +299,10 @@ fn fix_numbers(
         (new_used, normal) = normal.into_iter().partition(|p| touches(p.0));
         used.append(&mut new_used);
         reds.iter_mut().filter(|p| touches(p.0)).for_each(|red| {
+            // on small (or crowded) boards we can run out of hexes to swap with
+            if normal.is_empty() {
+                return;
+            }
             let mut new_hexagon =
                 normal.swap_remove((rng.random::<u8>() % normal.len() as u8) as usize);
 
@@ -229,7 +315,7 @@ fn fix_numbers(
     used.append(&mut normal);
     used
 }
-#[derive(Debug, Component, Clone, Copy, Default)]
+#[derive(Debug, Component, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
 #[require(KatanComponent)]
 pub struct Ports {
     three_for_one: bool,
@@ -311,7 +397,7 @@ impl AddAssign<Port> for Ports {
         }
     }
 }
-fn generate_port_positions(n: i8) -> impl Iterator<Item = BuildingPosition> {
+pub fn generate_port_positions(n: i8) -> impl Iterator<Item = BuildingPosition> {
     // very order dependent
     building_postions_on_ring(n)
         .enumerate()
@@ -341,6 +427,50 @@ fn building_postions_on_ring(n: i8) -> impl Iterator<Item = BuildingPosition> {
     }));
     (0..6).flat_map(move |i| row.clone().map(move |town| town.rotate_right_n(i)))
 }
+// for boards that aren't the normal hexagon we can't go by ring, instead we walk along the coast
+// an edge of the coast is a land hex and the direction of the water next to it
+fn coast(land: &[Position]) -> Vec<(Position, usize)> {
+    let is_land = |position: Position| land.contains(&position);
+    let direction = |d: usize| Position::DIRECTION_VECTORS[d % 6];
+    let Some(start) = land.iter().min().and_then(|hex| {
+        (0..6)
+            .find(|d| !is_land(*hex + direction(*d)))
+            .map(|d| (*hex, d))
+    }) else {
+        return vec![];
+    };
+    let mut coast = vec![];
+    let mut edge = start;
+    loop {
+        coast.push(edge);
+        let (hex, d) = edge;
+        // the next edge shares a corner with this one, so its either the next side of this hex or
+        // a side of the hex at that corner
+        let neighbor = hex + direction(d + 1);
+        edge = if is_land(neighbor) {
+            (neighbor, (d + 5) % 6)
+        } else {
+            (hex, (d + 1) % 6)
+        };
+        if edge == start {
+            break;
+        }
+    }
+    coast
+}
+// spreads the ports out evenly along the coast
+fn generate_coast_port_positions(
+    coast: &[(Position, usize)],
+    ports: usize,
+) -> Vec<BuildingPosition> {
+    (0..ports)
+        .flat_map(|i| {
+            let (hex, d) = coast[i * coast.len() / ports];
+            let corners = hex.building_positions_around();
+            [corners[d], corners[(d + 1) % 6]]
+        })
+        .collect()
+}
 fn generate_pieces(
     commands: &mut Commands<'_, '_>,
     player_count: u8,
@@ -353,13 +483,25 @@ fn generate_pieces(
         CatanColor::Red,
         CatanColor::Blue,
     ];
+    if player_count > 4 {
+        catan_colors.extend([CatanColor::Orange, CatanColor::Brown]);
+    }
     catan_colors.shuffle(rng);
 
-    let colors = catan_colors.into_iter();
-
+    spawn_players(
+        commands,
+        catan_colors.into_iter().take(player_count as usize),
+        local_player,
+    )
+}
+// the colors are in turn order
+fn spawn_players(
+    commands: &mut Commands<'_, '_>,
+    colors: impl Iterator<Item = CatanColor>,
+    local_player: LocalPlayerHandle,
+) -> Vec<CatanColorRef> {
     colors
         .enumerate()
-        .take(player_count as usize)
         .map(|(handle, color)| {
             println!("{handle} {:?}", local_player.0);
             let catan_color_ref = CatanColorRef {
@@ -371,6 +513,7 @@ fn generate_pieces(
                         Left::<Town>(5, PhantomData),
                         Left::<City>(4, PhantomData),
                         Left::<Road>(15, PhantomData),
+                        Left::<Ship>(rules::SHIPS, PhantomData),
                         PlayerHandle(handle),
                         Resources::new_player(),
                         // maybe initialize this as part of longest road plugin just have system that run
@@ -395,29 +538,46 @@ fn generate_pieces(
         })
         .collect_vec()
 }
+/// where the ports go, two intersections next to each other on the coast for each port
+pub fn port_positions(
+    land: &[Position],
+    size: BoardSize,
+    stretched: bool,
+) -> Vec<BuildingPosition> {
+    // very hacky and order dependent
+    if size == BoardSize::default() && !stretched {
+        generate_port_positions(3).collect_vec()
+    } else {
+        let coast = coast(land);
+        // the normal board has 9 ports on its 30 coast edges
+        generate_coast_port_positions(&coast, coast.len() * 3 / 10)
+    }
+}
 fn generate_ports(
     commands: &mut Commands<'_, '_>,
     rng: &mut Xoshiro256PlusPlus,
+    land: &[Position],
+    size: BoardSize,
+    stretched: bool,
 ) -> Vec<(BuildingPosition, Port)> {
-    // very hacky and order dependent
-    let positions = generate_port_positions(3);
-    let mut ports = [
-        Port::ThreeForOne,
-        Port::ThreeForOne,
-        Port::ThreeForOne,
-        Port::ThreeForOne,
-        Port::TwoForOne(resources::Resource::Wood),
-        Port::TwoForOne(resources::Resource::Brick),
-        Port::TwoForOne(resources::Resource::Sheep),
-        Port::TwoForOne(resources::Resource::Wheat),
-        Port::TwoForOne(resources::Resource::Ore),
-    ];
-
+    let positions = port_positions(land, size, stretched);
+    let mut ports = scale(
+        &[
+            (Port::ThreeForOne, 4),
+            (Port::TwoForOne(resources::Resource::Wood), 1),
+            (Port::TwoForOne(resources::Resource::Brick), 1),
+            (Port::TwoForOne(resources::Resource::Sheep), 1),
+            (Port::TwoForOne(resources::Resource::Wheat), 1),
+            (Port::TwoForOne(resources::Resource::Ore), 1),
+        ],
+        positions.len() / 2,
+    );
     ports.shuffle(rng);
     positions
         // we duplicate each port type because the postions iterator just returns each port postion
         // seperatly even though a port in the game occupies two intersections, we represent each
         // intersection seperatly but we happen to know that are in order
+        .into_iter()
         .zip(ports.iter().flat_map(|c| [*c, *c]))
         .map(|(pos, port)| {
             commands.spawn((pos, port));
@@ -430,18 +590,178 @@ pub fn setup(
     mut meshes: ResMut<'_, Assets<Mesh>>,
     mut materials: ResMut<'_, Assets<ColorMaterial>>,
     player_count: PlayerCount,
+    size: BoardSize,
+    constraints: Option<&BoardConstraints>,
+    cities_and_knights: bool,
+    game_rules: GameRules,
+    map: Option<&MapFile>,
     seed: u64,
     local_player: LocalPlayerHandle,
 ) -> vec::IntoIter<CatanColorRef> {
     let mut rng = Xoshiro256PlusPlus::seed_from_u64(seed);
+    // 5-6 players play on a stretched board with the extension development cards
+    let extension = player_count.0 > 4;
+    let board = if let Some(map) = map {
+        // a custom map doesn't need the rng, the rest of the game is still from the seed
+        let board = map.board();
+        for hex in &board.hexes {
+            commands.spawn(*hex);
+        }
+        for port in &board.ports {
+            commands.spawn(*port);
+        }
+        spawn_robber(commands, map.robber, &mut materials, &mut meshes);
+        commands.insert_resource(BoardSize(map.size));
+        board
+    } else {
+        let land = if extension {
+            positions::generate_stretched_postions(size.0 as i8).collect_vec()
+        } else {
+            positions::generate_postions(size.0 as i8).collect_vec()
+        };
+        rules::Board {
+            size: size.0,
+            hexes: generate_board(
+                commands,
+                &mut rng,
+                &mut materials,
+                &mut meshes,
+                &land,
+                size,
+                constraints,
+            ),
+            ports: generate_ports(commands, &mut rng, &land, size, extension),
+        }
+    };
+    draw_board(
+        board.hexes.iter().copied(),
+        board.ports.iter().copied(),
+        &mut materials,
+        &mut meshes,
+        commands,
+        3.,
+    );
+    let development_cards =
+        generate_development_cards(commands, &mut rng, extension, game_rules.development_cards);
+    let colors = generate_pieces(commands, player_count.0, &mut rng, local_player);
+    let mut rules = rules::GameState::new(
+        board,
+        colors.iter().map(|color| color.color),
+        development_cards,
+    );
+    if let Some(map) = map {
+        rules.robber = map.robber;
+    }
+    rules.set_game_rules(game_rules);
+    if cities_and_knights {
+        let mut progress_cards = cities_and_knights::progress_cards();
+        progress_cards.shuffle(&mut rng);
+        rules.cities_and_knights = Some(CitiesAndKnights::new(progress_cards));
+    }
+    if let Some(pirate) = rules.pirate {
+        ships::spawn_pirate(commands, pirate, &mut materials, &mut meshes);
+    }
+    commands.insert_resource(Rules(rules));
+    commands.insert_resource(SessionRng(rng));
+    colors.into_iter()
+}
+/// rebuilds the board and pieces of a saved game, all the numbers (resources, points ...) are
+/// filled in by `sync_rules`
+pub fn load(
+    commands: &mut Commands<'_, '_>,
+    mut meshes: ResMut<'_, Assets<Mesh>>,
+    mut materials: ResMut<'_, Assets<ColorMaterial>>,
+    save: &SaveFile,
+    local_player: LocalPlayerHandle,
+) -> vec::IntoIter<CatanColorRef> {
+    let rules = &save.rules;
+    commands.insert_resource(BoardSize(rules.board.size));
+    for hex in &rules.board.hexes {
+        commands.spawn(*hex);
+    }
+    for port in &rules.board.ports {
+        commands.spawn(*port);
+    }
+    spawn_robber(commands, rules.robber, &mut materials, &mut meshes);
+    if let Some(pirate) = rules.pirate {
+        ships::spawn_pirate(commands, pirate, &mut materials, &mut meshes);
+    }
     draw_board(
-        generate_board(commands, &mut rng, &mut materials, &mut meshes).into_iter(),
-        generate_ports(commands, &mut rng).into_iter(),
+        rules.board.hexes.iter().copied(),
+        rules.board.ports.iter().copied(),
         &mut materials,
         &mut meshes,
         commands,
         3.,
     );
-    generate_development_cards(commands, &mut rng);
-    generate_pieces(commands, player_count.0, &mut rng, local_player).into_iter()
+    commands.insert_resource(DevelopmentCardsPile(rules.development_cards.clone()));
+    let colors = spawn_players(
+        commands,
+        rules.players.iter().map(|player| player.color),
+        local_player,
+    );
+
+    let scale = 3.;
+    for (owner, road) in &rules.roads {
+        let color = colors[owner.0];
+        let road_entity = commands
+            .spawn((Road, *road, color.color))
+            .add_rollback()
+            .id();
+        commands.entity(color.entity).add_child(road_entity);
+        commands.spawn(RoadUI::bundle(
+            *road,
+            &mut meshes,
+            &mut materials,
+            color.color,
+            scale,
+        ));
+    }
+    for (owner, ship) in &rules.ships {
+        let color = colors[owner.0];
+        let ship_entity = commands
+            .spawn((Ship, *ship, color.color))
+            .add_rollback()
+            .id();
+        commands.entity(color.entity).add_child(ship_entity);
+        commands.spawn(ShipUI::bundle(
+            *ship,
+            &mut meshes,
+            &mut materials,
+            color.color,
+            scale,
+        ));
+    }
+    for (owner, building, piece) in &rules.buildings {
+        let color = colors[owner.0];
+        let building_entity = match piece {
+            rules::Piece::Town => {
+                commands.spawn(TownUI::bundle(
+                    *building,
+                    &mut meshes,
+                    &mut materials,
+                    color.color,
+                    scale,
+                ));
+                commands.spawn((Town, *building, color.color))
+            }
+            rules::Piece::City => {
+                commands.spawn(CityUI::bundle(
+                    *building,
+                    &mut meshes,
+                    &mut materials,
+                    color.color,
+                    scale,
+                ));
+                commands.spawn((City, *building, color.color))
+            }
+        }
+        .add_rollback()
+        .id();
+        commands.entity(color.entity).add_child(building_entity);
+    }
+
+    commands.insert_resource(Rules(rules.clone()));
+    commands.insert_resource(SessionRng(save.rng.clone()));
+    colors.into_iter()
 }
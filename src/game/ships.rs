@@ -0,0 +1,331 @@
+//! seafarers ships, the pirate and gold fields
+//! ships are placed like roads (but on the sea) and an open ship can be moved once a turn, all of
+//! it is only shown on boards with sea
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ui_anchor::{AnchorPoint, AnchorUiConfig, AnchoredUiNodes};
+
+use crate::utils::{HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+
+use super::{
+    GameState, Input, KatanComponent, Left, LocalPlayer, PlaceButton, Rules, UI,
+    colors::{CatanColor, CurrentColor},
+    common_ui::ButtonInteraction,
+    development_card_actions::{RoadBuildingState, YearOfPlentyState},
+    positions::{Coordinate, FPosition, Position, RoadPosition},
+    resources::{self, Resources, SHIP_RESOURCES},
+    rules,
+};
+
+#[derive(Debug, Component, Clone, Copy, Hash, Default)]
+#[require(KatanComponent)]
+pub struct Ship;
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent, PlaceButton)]
+pub struct ShipPlaceButton(RoadPosition);
+/// first one of these for every ship that can be moved, once one is picked one for everywhere it
+/// can go
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent, PlaceButton)]
+pub struct ShipMoveButton {
+    from: RoadPosition,
+    to: Option<RoadPosition>,
+}
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct GoldButton(resources::Resource);
+#[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+// marker component to mark the 2d mesh that represent the pirate
+pub struct PirateHighlighter;
+
+fn spawn_edge_button(commands: &mut Commands<'_, '_>, position: RoadPosition, button: impl Bundle) {
+    let multiplier = 3.0;
+    let (x, y) = position.positon_to_pixel_coordinates();
+    commands.spawn((
+        Transform::from_xyz(x * multiplier * 25.6, y * multiplier * 25.6, 0.0),
+        AnchoredUiNodes::spawn_one((
+            AnchorUiConfig {
+                anchorpoint: AnchorPoint::middle(),
+                offset: None,
+                ..Default::default()
+            },
+            Button,
+            Node {
+                width: Val::VMin(2.0),
+                height: Val::VMin(2.0),
+                ..default()
+            },
+            button,
+            BorderRadius::MAX,
+            BackgroundColor(NORMAL_BUTTON),
+        )),
+    ));
+}
+
+/// if `RESOURCE_MULTIPLIER` is zero then its free (from the road building card)
+pub fn place_normal_ship<const RESOURCE_MULTIPLIER: u8>(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    ship_free_q: Query<'_, '_, &Left<Ship>, With<CatanColor>>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    if !ship_free_q
+        .get(color_r.0.entity)
+        .is_ok_and(|ships| ships.0 > 0)
+    {
+        return;
+    }
+    let possible_ships = rules.ship_placements(color_r.0.handle);
+    let count = possible_ships.len();
+    for ship in possible_ships {
+        spawn_edge_button(&mut commands, ship, ShipPlaceButton(ship));
+    }
+    // with the road building card the roads might still be placeable
+    if count == 0
+        && (RESOURCE_MULTIPLIER != 0 || rules.road_placements(color_r.0.handle).is_empty())
+    {
+        game_state.set(GameState::Turn);
+    }
+}
+pub struct ShipUI;
+impl UI for ShipUI {
+    type Pos = RoadPosition;
+
+    fn bundle(
+        pos: Self::Pos,
+        meshes: &mut ResMut<'_, Assets<Mesh>>,
+        materials: &mut ResMut<'_, Assets<ColorMaterial>>,
+        color: CatanColor,
+        scale: f32,
+    ) -> impl Bundle {
+        let (x, y) = pos.positon_to_pixel_coordinates();
+        // rounder and shorter than a road so they can be told apart
+        let mesh = meshes.add(Capsule2d::new(scale * 2.5, scale * 14.));
+        (
+            KatanComponent,
+            DrawnShip(pos),
+            Mesh2d(mesh),
+            MeshMaterial2d(materials.add(color.to_bevy_color())),
+            Transform::from_xyz(x * scale * 25.6, y * scale * 25.6, 0.).with_rotation(
+                Quat::from_rotation_z(
+                    match pos.shared_coordinate() {
+                        Coordinate::R => 0f32,
+                        Coordinate::Q => -60f32,
+                        Coordinate::S => 60f32,
+                    }
+                    .to_radians(),
+                ),
+            ),
+        )
+    }
+
+    fn resources() -> Resources {
+        SHIP_RESOURCES
+    }
+}
+/// the mesh of a ship, so it can be found again when the ship moves
+#[derive(Component, Clone, Copy, Debug)]
+#[require(KatanComponent)]
+pub struct DrawnShip(pub RoadPosition);
+
+#[derive(SystemParam)]
+pub struct PlaceShipButtonState<'w> {
+    game_state: Res<'w, State<GameState>>,
+    game_state_mut: ResMut<'w, NextState<GameState>>,
+
+    input: ResMut<'w, Input>,
+    substate_mut: Option<ResMut<'w, NextState<RoadBuildingState>>>,
+    substate: Option<Res<'w, State<RoadBuildingState>>>,
+}
+impl ButtonInteraction<ShipPlaceButton> for PlaceShipButtonState<'_> {
+    fn interact(&mut self, ShipPlaceButton(position): &ShipPlaceButton) {
+        let PlaceShipButtonState {
+            game_state,
+            game_state_mut,
+            substate_mut,
+            substate,
+            input,
+        } = self;
+
+        **input = Input::AddShip(*position);
+        if *game_state.get() == GameState::RoadBuilding {
+            if let Some((substate_mut, substate)) =
+                substate_mut.as_deref_mut().zip(substate.as_ref())
+            {
+                if *substate.get() == RoadBuildingState::Road1 {
+                    substate_mut.set(RoadBuildingState::Road2);
+                } else {
+                    game_state_mut.set(GameState::Turn);
+                }
+            }
+        } else {
+            game_state_mut.set(GameState::Turn);
+        }
+    }
+}
+
+pub fn show_ship_moves(
+    mut commands: Commands<'_, '_>,
+    color_r: Res<'_, CurrentColor>,
+    rules: Res<'_, Rules>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+) {
+    let froms = rules
+        .ship_moves(color_r.0.handle)
+        .into_iter()
+        .map(|(from, _)| from)
+        .fold(vec![], |mut froms, from| {
+            if !froms.contains(&from) {
+                froms.push(from);
+            }
+            froms
+        });
+    if froms.is_empty() {
+        game_state.set(GameState::Turn);
+    }
+    for from in froms {
+        spawn_edge_button(&mut commands, from, ShipMoveButton { from, to: None });
+    }
+}
+#[derive(SystemParam)]
+pub struct MoveShipButtonState<'w, 's> {
+    commands: Commands<'w, 's>,
+    buttons: Query<'w, 's, Entity, With<ShipMoveButton>>,
+    color_r: Res<'w, CurrentColor>,
+    rules: Res<'w, Rules>,
+    game_state_mut: ResMut<'w, NextState<GameState>>,
+    input: ResMut<'w, Input>,
+}
+impl ButtonInteraction<ShipMoveButton> for MoveShipButtonState<'_, '_> {
+    fn interact(&mut self, ShipMoveButton { from, to }: &ShipMoveButton) {
+        if let Some(to) = to {
+            *self.input = Input::MoveShip(*from, *to);
+            self.game_state_mut.set(GameState::Turn);
+            return;
+        }
+        // the ship was picked, now where it goes
+        for button in &self.buttons {
+            self.commands.entity(button).despawn();
+        }
+        for (_, to) in self
+            .rules
+            .ship_moves(self.color_r.0.handle)
+            .into_iter()
+            .filter(|(ship, _)| ship == from)
+        {
+            spawn_edge_button(
+                &mut self.commands,
+                to,
+                ShipMoveButton {
+                    from: *from,
+                    to: Some(to),
+                },
+            );
+        }
+    }
+}
+
+pub fn spawn_pirate(
+    commands: &mut Commands<'_, '_>,
+    position: Position,
+    materials: &mut ResMut<'_, Assets<ColorMaterial>>,
+    meshes: &mut ResMut<'_, Assets<Mesh>>,
+) {
+    let mesh = meshes.add(Circle::new(30.0));
+    let (x, y) = FPosition::from(position).hex_to_pixel();
+    commands.spawn((
+        PirateHighlighter,
+        Mesh2d(mesh),
+        MeshMaterial2d(materials.add(Color::BLACK.with_alpha(0.9))),
+        Transform::from_xyz(x * 77.0, y * 77., 0.0),
+    ));
+}
+
+pub fn gold_setup(mut commands: Commands<'_, '_>) {
+    resources::Resource::ALL
+        .iter()
+        .enumerate()
+        .for_each(|(i, r)| {
+            commands.spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                children![(
+                    Button,
+                    Node {
+                        position_type: PositionType::Relative,
+                        width: Val::Px(15.0),
+                        height: Val::Px(15.0),
+
+                        bottom: Val::Px(35.),
+                        left: Val::Px((i * 30) as f32),
+                        ..default()
+                    },
+                    GoldButton(*r),
+                    BorderRadius::MAX,
+                    BackgroundColor(r.color()),
+                )],
+            ));
+        });
+}
+// one resource a click, the state changes once the rules say there is nothing left to pick
+pub fn gold_interaction(
+    mut interaction_query: Query<
+        '_,
+        '_,
+        (&Interaction, &mut Button, &mut BackgroundColor, &GoldButton),
+        (Changed<Interaction>,),
+    >,
+    mut input: ResMut<'_, Input>,
+) {
+    for (interaction, mut button, mut color, kind) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *input = Input::Gold(kind.0);
+                *color = PRESSED_BUTTON.into();
+                button.set_changed();
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                button.set_changed();
+            }
+            Interaction::None => {
+                *color = kind.0.color().into();
+            }
+        }
+    }
+}
+// anyone next to a gold field can have to pick, not just the player that rolled, so we follow the
+// rules instead of the inputs
+pub fn follow_gold(
+    rules: Res<'_, Rules>,
+    local_player: Res<'_, LocalPlayer>,
+    state: Res<'_, State<GameState>>,
+    mut game_state: ResMut<'_, NextState<GameState>>,
+    mut road_building_state: ResMut<'_, NextState<RoadBuildingState>>,
+    mut year_of_plenty_state: ResMut<'_, NextState<YearOfPlentyState>>,
+    mut was_gold: Local<'_, bool>,
+) {
+    let gold = matches!(rules.phase, rules::Phase::Gold(_));
+    let picking = matches!(&rules.phase, rules::Phase::Gold(pending)
+        if pending.iter().any(|(handle, _)| *handle == local_player.0.handle));
+    if picking {
+        if *state.get() != GameState::PickGold {
+            game_state.set(GameState::PickGold);
+        }
+    } else if *state.get() == GameState::PickGold || (*was_gold && !gold) {
+        super::set_state_from_rules(
+            &rules,
+            local_player.0.handle,
+            &mut game_state,
+            &mut road_building_state,
+            &mut year_of_plenty_state,
+        );
+    }
+    *was_gold = gold;
+}
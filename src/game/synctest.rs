@@ -0,0 +1,132 @@
+//! a game between bots without a window, in a ggrs sync test session so every frame is rolled back
+//! and simulated again, if a frame comes out different the second time (`RollbackMismatch`)
+//! something isn't registered for rollback (or isn't deterministic)
+//! run with `katan --synctest [frames] [seed]`, it exits with an error on the first mismatch
+use std::time::Duration;
+
+use bevy::{prelude::*, state::app::StatesPlugin, time::TimeUpdateStrategy};
+use bevy_ggrs::{
+    GgrsPlugin, RollbackFrameCount, Session,
+    ggrs::{self, PlayerType},
+};
+
+use super::{
+    BoardSize, FPS, GamePlugin, GgrsSessionConfig, LocalPlayerHandle, PlayerCount, PlayerHandle,
+    SessionSeed, bot::Bots, desync::RollbackMismatch,
+};
+use crate::AppState;
+
+const PLAYERS: usize = 4;
+// how many frames each frame gets rolled back, ggrs only keeps 8 frames around
+const CHECK_DISTANCE: usize = 2;
+// a minute of playing, the bots usually don't finish a game in that time
+const FRAMES: i32 = 60 * FPS as i32;
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SyncTest {
+    frames: i32,
+    seed: u64,
+}
+impl SyncTest {
+    /// `None` unless the game was started with `--synctest`
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip_while(|arg| arg != "--synctest");
+        args.next()?;
+        let frames = args
+            .next()
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(FRAMES);
+        let seed = args
+            .next()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+        Some(Self { frames, seed })
+    }
+}
+
+pub fn run(sync_test: SyncTest) -> AppExit {
+    App::new()
+        .add_plugins((MinimalPlugins, StatesPlugin, AssetPlugin::default()))
+        // nothing is drawn, but the game still makes meshes and loads its icons
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .init_asset::<Image>()
+        // a rollback frame every update, as fast as it can go
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1. / FPS as f64,
+        )))
+        .init_state::<AppState>()
+        .add_plugins((GgrsPlugin::<GgrsSessionConfig>::default(), GamePlugin))
+        .insert_resource(sync_test)
+        .add_systems(Startup, start_sync_test)
+        .add_systems(
+            Update,
+            (check_rollbacks, stop_after_frames)
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(OnEnter(AppState::GameOver), game_over)
+        .run()
+}
+
+// like a local game, but every seat is a bot (even the one the ui would be for)
+fn start_sync_test(
+    mut commands: Commands<'_, '_>,
+    sync_test: Res<'_, SyncTest>,
+    mut app_state: ResMut<'_, NextState<AppState>>,
+) {
+    let mut session_builder = ggrs::SessionBuilder::<GgrsSessionConfig>::new()
+        .with_num_players(PLAYERS)
+        .with_check_distance(CHECK_DISTANCE);
+    for handle in 0..PLAYERS {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+    let session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+
+    info!(
+        "sync test for {} frames with seed {}",
+        sync_test.frames, sync_test.seed
+    );
+    commands.insert_resource(SessionSeed(sync_test.seed));
+    commands.insert_resource(LocalPlayerHandle(0));
+    commands.insert_resource(Bots::new((0..PLAYERS).map(PlayerHandle)));
+    commands.insert_resource(PlayerCount(PLAYERS as u8));
+    commands.insert_resource(BoardSize::for_players(PLAYERS));
+    commands.insert_resource(Session::SyncTest(session));
+    app_state.set(AppState::InGame);
+}
+
+fn check_rollbacks(
+    mut mismatches: MessageReader<'_, '_, RollbackMismatch>,
+    sync_test: Res<'_, SyncTest>,
+    mut exit: MessageWriter<'_, AppExit>,
+) {
+    if let Some(RollbackMismatch { frame, diverged }) = mismatches.read().next() {
+        error!(
+            "frame {frame} came out different after a rollback, diverged: {diverged} (seed {})",
+            sync_test.seed
+        );
+        exit.write(AppExit::error());
+    }
+    mismatches.clear();
+}
+
+fn stop_after_frames(
+    frame: Res<'_, RollbackFrameCount>,
+    sync_test: Res<'_, SyncTest>,
+    mut exit: MessageWriter<'_, AppExit>,
+) {
+    if **frame >= sync_test.frames {
+        info!("no mismatches in {} frames", **frame);
+        exit.write(AppExit::Success);
+    }
+}
+
+fn game_over(frame: Res<'_, RollbackFrameCount>, mut exit: MessageWriter<'_, AppExit>) {
+    info!("the game finished on frame {} without a mismatch", **frame);
+    exit.write(AppExit::Success);
+}
@@ -4,28 +4,25 @@ use bevy_ui_anchor::{AnchorPoint, AnchorUiConfig, AnchoredUiNodes};
 use crate::{game::PlaceButton, utils::NORMAL_BUTTON};
 
 use super::{
-    BoardSize, Building, GameState, Input, KatanComponent, Left, UI,
+    Building, GameState, Input, KatanComponent, Left, Rules, UI,
     colors::{CatanColor, CurrentColor, CurrentSetupColor},
     common_ui::ButtonInteraction,
-    positions::{BuildingPosition, RoadPosition},
+    positions::BuildingPosition,
     resources::{Resources, TOWN_RESOURCES},
-    roads::{RoadQuery, RoadQueryItem},
 };
 
 #[derive(Component, Clone, Copy, Debug)]
 #[require(KatanComponent, PlaceButton)]
-pub struct TownPlaceButton(Resources, BuildingPosition);
-#[derive(Debug, Component, Clone, Copy, Default)]
+pub struct TownPlaceButton(BuildingPosition);
+#[derive(Debug, Component, Clone, Copy, Hash, Default)]
 #[require(KatanComponent)]
 #[require(Building)]
 pub struct Town;
 pub fn place_normal_town(
     mut commands: Commands<'_, '_>,
     color_r: Res<'_, CurrentColor>,
-    size_r: Res<'_, BoardSize>,
     town_free_q: Query<'_, '_, &Left<Town>, With<CatanColor>>,
-    road_q: Query<'_, '_, RoadQuery>,
-    building_q: Query<'_, '_, (&'_ Building, &'_ CatanColor, &'_ BuildingPosition)>,
+    rules: Res<'_, Rules>,
     mut game_state: ResMut<'_, NextState<GameState>>,
 ) {
     let unplaced_towns_correct_color = town_free_q.get(color_r.0.entity);
@@ -36,9 +33,9 @@ pub fn place_normal_town(
     };
 
     let multiplier = 3.0;
-    let possible_towns =
-        get_possible_town_placements(color_r.0.color, BoardSize(size_r.0), road_q, building_q);
+    let possible_towns = rules.town_placements(color_r.0.handle);
     let count = possible_towns
+        .into_iter()
         .filter_map(|p| {
             let (x, y) = p.positon_to_pixel_coordinates();
             (x != 0. || y != 0.).then_some((x, y, p))
@@ -58,7 +55,7 @@ pub fn place_normal_town(
                         height: Val::VMin(2.0),
                         ..default()
                     },
-                    TownPlaceButton(TOWN_RESOURCES, p),
+                    TownPlaceButton(p),
                     BorderRadius::MAX,
                     BackgroundColor(NORMAL_BUTTON),
                 )),
@@ -75,14 +72,12 @@ pub fn place_normal_town(
 pub fn place_setup_town(
     mut commands: Commands<'_, '_>,
     color_r: Res<'_, CurrentSetupColor>,
-    size_r: Res<'_, BoardSize>,
-    road_q: Query<'_, '_, RoadQuery>,
-    building_q: Query<'_, '_, (&'_ Building, &'_ CatanColor, &'_ BuildingPosition)>,
+    rules: Res<'_, Rules>,
 ) {
     let multiplier = 3.0;
-    let possible_towns =
-        get_possible_town_placements(color_r.0.color, BoardSize(size_r.0), road_q, building_q);
+    let possible_towns = rules.town_placements(color_r.0.handle);
     possible_towns
+        .into_iter()
         .filter_map(|p| {
             let (x, y) = p.positon_to_pixel_coordinates();
             (x != 0. || y != 0.).then_some((x, y, p))
@@ -102,7 +97,7 @@ pub fn place_setup_town(
                         height: Val::VMin(2.0),
                         ..default()
                     },
-                    TownPlaceButton(Resources::empty(), p),
+                    TownPlaceButton(p),
                     BorderRadius::MAX,
                     BackgroundColor(NORMAL_BUTTON),
                 )),
@@ -112,67 +107,6 @@ pub fn place_setup_town(
             commands.spawn(b);
         });
 }
-fn get_possible_town_placements(
-    color_r: CatanColor,
-    size_r: BoardSize,
-    road_q: Query<'_, '_, RoadQuery>,
-    building_q: Query<'_, '_, (&Building, &CatanColor, &BuildingPosition)>,
-) -> impl Iterator<Item = BuildingPosition> {
-    let (current_color_roads, _): (Vec<_>, Vec<_>) =
-        road_q.into_iter().partition(|r| *r.1 == color_r);
-
-    let possibles_towns = buildings_on_roads(
-        current_color_roads
-            .into_iter()
-            .map(|RoadQueryItem(_, _, road)| *road),
-        BoardSize(size_r.0),
-    );
-
-    possibles_towns.filter(move |r| check_no_touching_buildings(r, building_q, size_r.0))
-}
-/// verifies that there is no buildings with in one road of this building
-pub fn check_no_touching_buildings(
-    position: &BuildingPosition,
-    building_q: Query<'_, '_, (&Building, &CatanColor, &BuildingPosition)>,
-    size_r_0: u8,
-) -> bool {
-    match position {
-        BuildingPosition::All(position, position1, position2) => !buildings_on_roads(
-            [
-                RoadPosition::new(*position, *position1, Some(size_r_0)),
-                RoadPosition::new(*position, *position2, Some(size_r_0)),
-                RoadPosition::new(*position1, *position2, Some(size_r_0)),
-            ]
-            .into_iter()
-            .flatten(),
-            BoardSize(size_r_0),
-        )
-        .any(|p| building_q.iter().any(|(_, _, place_b)| &p == place_b)),
-    }
-}
-fn buildings_on_roads(
-    current_color_roads: impl Iterator<Item = RoadPosition>,
-    size_r: BoardSize,
-) -> impl Iterator<Item = BuildingPosition> {
-    current_color_roads.flat_map(move |road| buildings_on_road(size_r, road))
-}
-
-pub fn buildings_on_road(
-    size_r: BoardSize,
-    road: RoadPosition,
-) -> impl Iterator<Item = BuildingPosition> {
-    match road {
-        RoadPosition::Both(p1, p2, _) => {
-            let (p3, p4) = road.neighboring_two(Some(size_r.0));
-            let make_town_pos = |p, option_p1: Option<_>, p2| {
-                option_p1.and_then(|p1| BuildingPosition::new(p, p1, p2, Some(size_r.0)))
-            };
-            [(make_town_pos(p1, p3, p2)), (make_town_pos(p1, p4, p2))]
-                .into_iter()
-                .flatten()
-        }
-    }
-}
 pub struct TownUI;
 impl UI for TownUI {
     type Pos = BuildingPosition;
@@ -205,14 +139,14 @@ pub struct PlaceTownButtonState<'w> {
     input: ResMut<'w, Input>,
 }
 impl ButtonInteraction<TownPlaceButton> for PlaceTownButtonState<'_> {
-    fn interact(&mut self, TownPlaceButton(cost, position): &TownPlaceButton) {
+    fn interact(&mut self, TownPlaceButton(position): &TownPlaceButton) {
         let PlaceTownButtonState {
             game_state,
             game_state_mut,
             input,
         } = self;
 
-        **input = Input::AddTown(*position, *cost, *game_state.get() == GameState::SetupTown);
+        **input = Input::AddTown(*position);
         if *game_state.get() == GameState::PlaceTown {
             game_state_mut.set(GameState::Turn);
         }
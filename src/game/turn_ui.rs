@@ -5,14 +5,13 @@ use crate::game::LocalPlayer;
 
 use super::{
     CatanColor, CurrentColor, GameState, Input, KatanComponent, Knights, Layout, Left,
-    PlayerHandle, Resources, VictoryPoints,
+    PlayerHandle, Resources, Rules, VictoryPoints,
     cities::City,
     colors::CatanColorRef,
     development_cards::DevelopmentCards,
-    dice,
     larget_army::LargetArmyRef,
     longest_road::{LongestRoadRef, PlayerLongestRoad},
-    resources::{CITY_RESOURCES, ROAD_RESOURCES, TOWN_RESOURCES},
+    resources::{CITY_RESOURCES, ROAD_RESOURCES, SHIP_RESOURCES, TOWN_RESOURCES},
     roads::Road,
     towns::Town,
 };
@@ -33,6 +32,14 @@ pub struct TownButton;
 #[require(KatanComponent)]
 // button in game to start city placement ui
 pub struct CityButton;
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+// button in game to start ship placement ui (seafarers only)
+pub struct ShipButton;
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+#[require(KatanComponent)]
+// button in game to start moving a ship (seafarers only)
+pub struct MoveShipButton;
 #[derive(Component, PartialEq, Eq, Default, Clone, Copy)]
 #[require(KatanComponent)]
 pub struct DieButton;
@@ -43,22 +50,22 @@ pub struct NextButton;
 // for roll there are two dice so it cannot be a single (its probably possible to have on dice
 // thing which looks like two dice)
 pub fn turn_ui_roll_interaction(
-    game_state: ResMut<'_, NextState<GameState>>,
     mut interaction_query: Query<
         '_,
         '_,
         (&DieButton, &Interaction, &mut Button),
         Changed<Interaction>,
     >,
-    player_resources: Query<'_, '_, &mut Resources, With<CatanColor>>,
-    input: ResMut<'_, Input>,
+    mut input: ResMut<'_, Input>,
 ) {
     for (_, interaction, mut button) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
                 button.set_changed();
 
-                dice::full_roll_dice(player_resources, game_state, input);
+                // the dice are rolled once every peer has the input, the state is changed
+                // then too
+                *input = Input::Roll;
 
                 button.set_changed();
                 break;
@@ -193,11 +200,79 @@ pub fn turn_ui_road_interaction(
         }
     }
 }
+pub fn turn_ui_ship_interaction(
+    mut game_state: ResMut<'_, NextState<GameState>>,
+    interaction_query: Single<
+        '_,
+        '_,
+        (&ShipButton, &Interaction, &mut Button),
+        Changed<Interaction>,
+    >,
+    player_resources: Query<'_, '_, &Resources, With<CatanColor>>,
+    color_r: Res<'_, CurrentColor>,
+) {
+    let player_resources = player_resources.get(color_r.0.entity).ok();
+    if let Some(resources) = player_resources {
+        let (_, interaction, mut button) = interaction_query.into_inner();
+        if resources.contains(SHIP_RESOURCES) {
+            match *interaction {
+                Interaction::Pressed => {
+                    game_state.set(GameState::PlaceShip);
+                    button.set_changed();
+                }
+                Interaction::Hovered => {
+                    button.set_changed();
+                }
+                Interaction::None => {}
+            }
+        } else {
+            // TODO: grey out
+        }
+    }
+}
+// moving a ship is free, whether there is anything to move is figured out when showing the moves
+pub fn turn_ui_move_ship_interaction(
+    mut game_state: ResMut<'_, NextState<GameState>>,
+    interaction_query: Single<
+        '_,
+        '_,
+        (&MoveShipButton, &Interaction, &mut Button),
+        Changed<Interaction>,
+    >,
+) {
+    let (_, interaction, mut button) = interaction_query.into_inner();
+    match *interaction {
+        Interaction::Pressed => {
+            game_state.set(GameState::MoveShip);
+            button.set_changed();
+        }
+        Interaction::Hovered => {
+            button.set_changed();
+        }
+        Interaction::None => {}
+    }
+}
+fn ship_text_button(text: &str, button: impl Bundle) -> impl Bundle {
+    (
+        Node {
+            height: Val::Px(25.0),
+            padding: UiRect::horizontal(Val::Px(3.)),
+            ..default()
+        },
+        Button,
+        Text::new(text),
+        TextColor(Color::BLACK),
+        BackgroundColor(Color::WHITE),
+        button,
+    )
+}
 pub fn show_turn_ui(
     mut commands: Commands<'_, '_>,
     asset_server: Res<'_, AssetServer>,
     layout: Res<'_, Layout>,
+    rules: Res<'_, Rules>,
 ) {
+    let seafarers = rules.board.seafarers();
     let road_icon = asset_server.load("road.png");
     let town_icon = asset_server.load("house.png");
     let city_icon = asset_server.load("city.png");
@@ -205,13 +280,7 @@ pub fn show_turn_ui(
     let next_turn_icon = asset_server.load("x.png");
     commands.entity(layout.ui).insert((
         Node {
-            grid_template_columns: vec![
-                GridTrack::min_content(),
-                GridTrack::min_content(),
-                GridTrack::min_content(),
-                GridTrack::min_content(),
-                GridTrack::min_content(),
-            ],
+            grid_template_columns: vec![GridTrack::min_content(); if seafarers { 7 } else { 5 }],
             column_gap: Val::Px(5.),
             align_items: AlignItems::End,
             justify_content: JustifyContent::Center,
@@ -279,6 +348,16 @@ pub fn show_turn_ui(
             )
         ],
     ));
+    if seafarers {
+        // before the next turn button
+        let ship = commands.spawn(ship_text_button("ship", ShipButton)).id();
+        let move_ship = commands
+            .spawn(ship_text_button("move ship", MoveShipButton))
+            .id();
+        commands
+            .entity(layout.ui)
+            .insert_children(4, &[ship, move_ship]);
+    }
     // TODO: better way to do ui layouting
     commands.entity(layout.board).with_child((
         Node {
@@ -1,4 +1,15 @@
-use crate::game::{GgrsSessionConfig, LocalPlayerHandle, PlayerCount, SessionSeed};
+use crate::game::{
+    BoardSize, GgrsSessionConfig, LocalPlayerHandle, PlayerCount, PlayerHandle, SessionSeed,
+    balance::BoardConstraints,
+    bot::Bots,
+    cities_and_knights::PlayCitiesAndKnights,
+    editor::EditorMap,
+    game_rules::GameRules,
+    hot_seat::HotSeat,
+    map::{LoadedMap, MapFile},
+    replay::{Replay, ReplayFile},
+    save::{self, LoadedGame, SaveFile, Seating},
+};
 use crate::{
     AppState,
     common_ui::{self, ButtonInteraction},
@@ -10,6 +21,7 @@ use bevy::camera::visibility::RenderLayers;
 use bevy::{
     ecs::system::SystemParam,
     input_focus::{InputDispatchPlugin, InputFocus},
+    platform::collections::HashMap,
     prelude::*,
 };
 use bevy_ggrs::{ggrs, ggrs::DesyncDetection, prelude::*};
@@ -18,7 +30,11 @@ use bevy_simple_text_input::{
     TextInput, TextInputInactive, TextInputPlugin, TextInputSystem, TextInputTextColor,
     TextInputTextFont, TextInputValue,
 };
+use itertools::Itertools;
 
+/// the matchbox channel for everything that is not an input (channel 0 is taken by ggrs), like
+/// claiming handles when loading a game or comparing checksums when the game desyncs
+pub const RELIABLE_CHANNEL: usize = 1;
 pub struct LobbyPlugin;
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, SubStates)]
 #[source(AppState = AppState::Menu)]
@@ -32,6 +48,27 @@ pub enum MenuState {
 pub struct Server;
 #[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
 pub struct Room;
+// path of a save to continue, empty for a new game
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Load;
+// how many seats are filled with bots (on top of the players)
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct BotCount;
+// how many rings of land the board has, empty for the usual board
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Board;
+// path of a custom map, empty for a shuffled board
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Map;
+// "no" for a board that is only shuffled, without the fairness constraints
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Balanced;
+// "yes" to play with the cities & knights rules
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CitiesAndKnights;
+// like `vp=12, friendly robber`, empty for the usual rules
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct HouseRules;
 
 #[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
 pub struct JoinButton;
@@ -40,22 +77,395 @@ pub struct JoinButton;
 pub struct JoinButtonState<'w, 's> {
     room_query: Single<'w, 's, &'static TextInputValue, With<Room>>,
     server_query: Single<'w, 's, &'static TextInputValue, With<Server>>,
+    load_query: Single<'w, 's, &'static TextInputValue, With<Load>>,
+    bot_query: Single<'w, 's, &'static TextInputValue, With<BotCount>>,
+    board_query: Single<'w, 's, &'static TextInputValue, With<Board>>,
+    map_query: Single<'w, 's, &'static TextInputValue, With<Map>>,
+    balanced_query: Single<'w, 's, &'static TextInputValue, With<Balanced>>,
+    cities_and_knights_query: Single<'w, 's, &'static TextInputValue, With<CitiesAndKnights>>,
+    house_rules_query: Single<'w, 's, &'static TextInputValue, With<HouseRules>>,
     commands: Commands<'w, 's>,
     state: ResMut<'w, NextState<MenuState>>,
+    app_state: ResMut<'w, NextState<AppState>>,
 }
 impl ButtonInteraction<JoinButton> for JoinButtonState<'_, '_> {
     fn interact(&mut self, _: &JoinButton) {
-        self.commands
-            .insert_resource(MatchboxSocket::new_unreliable(format!(
-                "{}/katan?next={}",
-                self.server_query.0, self.room_query.0
-            )));
+        let path = self.load_query.0.trim();
+        let (players, bots) = if path.is_empty() {
+            let Ok(players) = self.room_query.0.trim().parse() else {
+                error!("the number of players should be a number");
+                return;
+            };
+            let Some(bots) = bot_count(&self.bot_query) else {
+                return;
+            };
+            let Some(seats) = seats(players, bots) else {
+                return;
+            };
+            self.commands.remove_resource::<LoadedGame>();
+            if !load_map(&mut self.commands, &self.map_query)
+                || !house_rules(&mut self.commands, &self.house_rules_query)
+            {
+                return;
+            }
+            board_constraints(&mut self.commands, &self.balanced_query);
+            cities_and_knights(&mut self.commands, &self.cities_and_knights_query);
+            let bots = (players..players + bots).map(PlayerHandle).collect_vec();
+            if players == 1 && !bots.is_empty() {
+                let board_size = board_size(&self.board_query, seats.0.into());
+                start_local(
+                    &mut self.commands,
+                    &mut self.app_state,
+                    seats,
+                    bots,
+                    board_size,
+                );
+                return;
+            }
+            (players, bots)
+        } else {
+            match SaveFile::load(path) {
+                // the save knows how many seats there are and which of them are bots
+                Ok(save) => {
+                    let Some(seats) = seats(
+                        save.seating.players(save.rules.players.len()).count(),
+                        save.seating.bots.len(),
+                    ) else {
+                        return;
+                    };
+                    let Seating { bots, local } = save.seating.clone();
+                    // the save already has its board
+                    self.commands.remove_resource::<LoadedMap>();
+                    self.commands.insert_resource(LoadedGame(save));
+                    if local {
+                        let board_size = board_size(&self.board_query, seats.0.into());
+                        start_local(
+                            &mut self.commands,
+                            &mut self.app_state,
+                            seats,
+                            bots,
+                            board_size,
+                        );
+                        return;
+                    }
+                    (usize::from(seats.0) - bots.len(), bots)
+                }
+                Err(error) => {
+                    error!("could not load {path}: {error}");
+                    return;
+                }
+            }
+        };
+        // only players that want the same number of bots (and the same board) end up in the same
+        // room, if they have the same map is checked once everyone is there
+        let mut room = if bots.is_empty() {
+            "katan".to_string()
+        } else {
+            format!("katan-{}-bots", bots.len())
+        };
+        let size = self.board_query.0.trim();
+        if path.is_empty() && !size.is_empty() {
+            room += &format!("-size-{size}");
+        }
+        if path.is_empty() && !self.map_query.0.trim().is_empty() {
+            room += "-map";
+        }
+        if path.is_empty() && self.balanced_query.0.trim() == "no" {
+            room += "-unbalanced";
+        }
+        if path.is_empty() && self.cities_and_knights_query.0.trim() == "yes" {
+            room += "-cities-and-knights";
+        }
+        if path.is_empty()
+            && let Ok(game_rules) = self.house_rules_query.0.parse::<GameRules>()
+            && game_rules != GameRules::default()
+        {
+            room += &format!("-rules-{:x}", game_rules.id());
+        }
+        self.commands.insert_resource(MatchboxSocket::from(
+            WebRtcSocketBuilder::new(format!("{}/{room}?next={players}", self.server_query.0))
+                // for ggrs
+                .add_unreliable_channel()
+                // RELIABLE_CHANNEL
+                .add_reliable_channel(),
+        ));
+        self.commands.insert_resource(RoomSeats { players, bots });
         self.state.set(MenuState::Room);
     }
 
     // TODO: url verification and room verification
 }
 
+// who the room waits for, the bots don't join but every peer has to know which seats they have
+#[derive(Resource, Debug, Clone)]
+struct RoomSeats {
+    players: usize,
+    bots: Vec<PlayerHandle>,
+}
+
+// empty for no bots
+fn bot_count(bot_query: &TextInputValue) -> Option<usize> {
+    let bots = bot_query.0.trim();
+    if bots.is_empty() {
+        return Some(0);
+    }
+    let bots = bots.parse().ok();
+    if bots.is_none() {
+        error!("the number of bots should be a number");
+    }
+    bots
+}
+
+// everyone needs their own color
+fn seats(players: usize, bots: usize) -> Option<PlayerCount> {
+    match players
+        .checked_add(bots)
+        .and_then(|seats| u8::try_from(seats).ok())
+    {
+        Some(seats) if (PlayerCount::MIN..=PlayerCount::MAX).contains(&seats) => {
+            Some(PlayerCount(seats))
+        }
+        _ => {
+            error!(
+                "there should be between {} and {} seats, not {players} players and {bots} bots",
+                PlayerCount::MIN,
+                PlayerCount::MAX
+            );
+            None
+        }
+    }
+}
+
+// typed in the map box instead of a path
+const SEAFARERS_MAP: &str = "seafarers";
+
+// a map that doesn't load is an error (instead of a shuffled board), so nobody ends up playing
+// on a different board then they wanted
+fn load_map(commands: &mut Commands<'_, '_>, map_query: &TextInputValue) -> bool {
+    let path = map_query.0.trim();
+    if path.is_empty() {
+        commands.remove_resource::<LoadedMap>();
+        return true;
+    }
+    // the built in map isn't a file
+    if path == SEAFARERS_MAP {
+        commands.insert_resource(LoadedMap(MapFile::seafarers()));
+        return true;
+    }
+    match MapFile::load(path) {
+        Ok(map) => {
+            commands.insert_resource(LoadedMap(map));
+            true
+        }
+        Err(error) => {
+            error!("could not load map {path}: {error}");
+            false
+        }
+    }
+}
+
+// empty (or not a valid size) for the usual board for that many players
+fn board_size(board_query: &TextInputValue, players: usize) -> BoardSize {
+    let size = board_query.0.trim();
+    if size.is_empty() {
+        return BoardSize::for_players(players);
+    }
+    match size.parse() {
+        Ok(size) if (BoardSize::MIN..=BoardSize::MAX).contains(&size) => BoardSize(size),
+        _ => {
+            warn!(
+                "board size should be between {} and {}, using the usual board",
+                BoardSize::MIN,
+                BoardSize::MAX
+            );
+            BoardSize::for_players(players)
+        }
+    }
+}
+
+// balanced unless asked not to be, the constraints themselves aren't picked in the lobby
+fn board_constraints(commands: &mut Commands<'_, '_>, balanced_query: &TextInputValue) {
+    match balanced_query.0.trim() {
+        "no" => commands.remove_resource::<BoardConstraints>(),
+        balanced => {
+            if !matches!(balanced, "" | "yes") {
+                warn!("balanced should be yes or no, using a balanced board");
+            }
+            commands.insert_resource(BoardConstraints::default());
+        }
+    }
+}
+
+// the usual rules unless asked for cities & knights
+fn cities_and_knights(commands: &mut Commands<'_, '_>, cities_and_knights_query: &TextInputValue) {
+    match cities_and_knights_query.0.trim() {
+        "yes" => commands.insert_resource(PlayCitiesAndKnights),
+        cities_and_knights => {
+            if !matches!(cities_and_knights, "" | "no") {
+                warn!("cities & knights should be yes or no, playing without it");
+            }
+            commands.remove_resource::<PlayCitiesAndKnights>();
+        }
+    }
+}
+
+// house rules that don't make sense are an error, like a map that doesn't load
+fn house_rules(commands: &mut Commands<'_, '_>, house_rules_query: &TextInputValue) -> bool {
+    match house_rules_query.0.parse::<GameRules>() {
+        Ok(game_rules) => {
+            commands.insert_resource(game_rules);
+            true
+        }
+        Err(error) => {
+            error!("could not use the house rules: {error}");
+            false
+        }
+    }
+}
+
+// a single player with bots (or hot seat) doesn't need anyone else, so there is no socket and
+// everyone is local
+fn start_local(
+    commands: &mut Commands<'_, '_>,
+    app_state: &mut NextState<AppState>,
+    seats: PlayerCount,
+    bots: Vec<PlayerHandle>,
+    board_size: BoardSize,
+) {
+    let num_players = usize::from(seats.0);
+    let mut session_builder = ggrs::SessionBuilder::<GgrsSessionConfig>::new()
+        .with_num_players(num_players)
+        // nothing is ever predicted so there is nothing to check
+        .with_check_distance(0);
+    for handle in 0..num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, handle)
+            .expect("failed to add player");
+    }
+    let session = session_builder
+        .start_synctest_session()
+        .expect("failed to start session");
+
+    let seating = Seating { bots, local: true };
+    let mut players = seating.players(num_players);
+    // the ui starts with the first player, in hot seat it follows whoever has to do something
+    let first = players.next().unwrap_or(PlayerHandle(0));
+    let hot_seat = players.next().is_some();
+    commands.insert_resource(SessionSeed(rand::random()));
+    commands.insert_resource(LocalPlayerHandle(first.0));
+    commands.insert_resource(Bots::new(seating.bots.iter().copied()));
+    commands.insert_resource(seating);
+    commands.insert_resource(seats);
+    commands.insert_resource(board_size);
+    commands.insert_resource(bevy_ggrs::Session::SyncTest(session));
+    if hot_seat {
+        commands.insert_resource(HotSeat);
+    } else {
+        commands.remove_resource::<HotSeat>();
+    }
+    app_state.set(AppState::InGame);
+}
+
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct HotSeatButton;
+
+// everyone in the room box plays on this machine, taking turns
+#[derive(SystemParam)]
+pub struct HotSeatButtonState<'w, 's> {
+    room_query: Single<'w, 's, &'static TextInputValue, With<Room>>,
+    bot_query: Single<'w, 's, &'static TextInputValue, With<BotCount>>,
+    board_query: Single<'w, 's, &'static TextInputValue, With<Board>>,
+    map_query: Single<'w, 's, &'static TextInputValue, With<Map>>,
+    balanced_query: Single<'w, 's, &'static TextInputValue, With<Balanced>>,
+    cities_and_knights_query: Single<'w, 's, &'static TextInputValue, With<CitiesAndKnights>>,
+    house_rules_query: Single<'w, 's, &'static TextInputValue, With<HouseRules>>,
+    commands: Commands<'w, 's>,
+    app_state: ResMut<'w, NextState<AppState>>,
+}
+impl ButtonInteraction<HotSeatButton> for HotSeatButtonState<'_, '_> {
+    fn interact(&mut self, _: &HotSeatButton) {
+        let Ok(players) = self.room_query.0.trim().parse() else {
+            error!("the number of players should be a number");
+            return;
+        };
+        let Some(bots) = bot_count(&self.bot_query) else {
+            return;
+        };
+        let Some(seats) = seats(players, bots) else {
+            return;
+        };
+        let board_size = board_size(&self.board_query, seats.0.into());
+        self.commands.remove_resource::<LoadedGame>();
+        if !load_map(&mut self.commands, &self.map_query)
+            || !house_rules(&mut self.commands, &self.house_rules_query)
+        {
+            return;
+        }
+        board_constraints(&mut self.commands, &self.balanced_query);
+        cities_and_knights(&mut self.commands, &self.cities_and_knights_query);
+        start_local(
+            &mut self.commands,
+            &mut self.app_state,
+            seats,
+            (players..seats.0.into()).map(PlayerHandle).collect(),
+            board_size,
+        );
+    }
+}
+
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct EditorButton;
+
+// edits the map in the map box, or a new one with the size in the board size box
+#[derive(SystemParam)]
+pub struct EditorButtonState<'w, 's> {
+    board_query: Single<'w, 's, &'static TextInputValue, With<Board>>,
+    map_query: Single<'w, 's, &'static TextInputValue, With<Map>>,
+    commands: Commands<'w, 's>,
+    app_state: ResMut<'w, NextState<AppState>>,
+}
+impl ButtonInteraction<EditorButton> for EditorButtonState<'_, '_> {
+    fn interact(&mut self, _: &EditorButton) {
+        let path = self.map_query.0.trim();
+        let (map, path) = if path.is_empty() {
+            // we don't know how many will play on it, so its the usual board
+            let size = board_size(&self.board_query, 0);
+            (MapFile::blank(size.0), "katan.map".to_string())
+        } else if path == SEAFARERS_MAP {
+            (MapFile::seafarers(), "seafarers.map".to_string())
+        } else {
+            match MapFile::read(path) {
+                Ok(map) => (map, path.to_string()),
+                Err(error) => {
+                    error!("could not open map {path}: {error}");
+                    return;
+                }
+            }
+        };
+        self.commands.insert_resource(EditorMap { map, path });
+        self.app_state.set(AppState::Editor);
+    }
+}
+
+#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ReplayButton;
+
+// replays use the load path, the game itself starts in `replay::start_replay`
+#[derive(SystemParam)]
+pub struct ReplayButtonState<'w, 's> {
+    load_query: Single<'w, 's, &'static TextInputValue, With<Load>>,
+    commands: Commands<'w, 's>,
+}
+impl ButtonInteraction<ReplayButton> for ReplayButtonState<'_, '_> {
+    fn interact(&mut self, _: &ReplayButton) {
+        let path = self.load_query.0.trim();
+        match ReplayFile::load(path) {
+            Ok(replay) => self.commands.insert_resource(Replay::new(replay)),
+            Err(error) => error!("could not load replay {path}: {error}"),
+        }
+    }
+}
+
 impl Plugin for LobbyPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(InputDispatchPlugin)
@@ -68,10 +478,27 @@ impl Plugin for LobbyPlugin {
                     .run_if(in_state(MenuState::Lobby))
                     .before(TextInputSystem),
             )
-            .add_systems(Update, wait_for_players.run_if(in_state(MenuState::Room)))
             .add_systems(
                 Update,
-                common_ui::button_system_with_generic::<JoinButton, JoinButtonState<'_, '_>>
+                wait_for_players.run_if(in_state(MenuState::Room).and(resource_exists::<RoomSeats>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    common_ui::button_system_with_generic::<JoinButton, JoinButtonState<'_, '_>>,
+                    common_ui::button_system_with_generic::<
+                        ReplayButton,
+                        ReplayButtonState<'_, '_>,
+                    >,
+                    common_ui::button_system_with_generic::<
+                        HotSeatButton,
+                        HotSeatButtonState<'_, '_>,
+                    >,
+                    common_ui::button_system_with_generic::<
+                        EditorButton,
+                        EditorButtonState<'_, '_>,
+                    >,
+                )
                     .run_if(in_state(MenuState::Lobby)),
             )
             .add_systems(OnEnter(AppState::Menu), setup_lobby);
@@ -113,6 +540,14 @@ pub fn setup_lobby(mut commands: Commands<'_, '_>) {
                     GridTrack::max_content(),
                     GridTrack::max_content(),
                     GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
+                    GridTrack::max_content(),
                 ],
                 ..Default::default()
             },
@@ -203,6 +638,270 @@ pub fn setup_lobby(mut commands: Commands<'_, '_>) {
                         ),
                     ]
                 ),
+                (
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: vec![
+                            GridTrack::max_content(),
+                            GridTrack::minmax(
+                                MinTrackSizingFunction::Px(200.),
+                                MaxTrackSizingFunction::MaxContent
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                    children![
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("bots:")
+                        ),
+                        (
+                            BotCount,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue("0".to_owned()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                    ]
+                ),
+                (
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: vec![
+                            GridTrack::max_content(),
+                            GridTrack::minmax(
+                                MinTrackSizingFunction::Px(200.),
+                                MaxTrackSizingFunction::MaxContent
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                    children![
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("board size:")
+                        ),
+                        (
+                            Board,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue(String::new()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                    ]
+                ),
+                (
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: vec![
+                            GridTrack::max_content(),
+                            GridTrack::minmax(
+                                MinTrackSizingFunction::Px(200.),
+                                MaxTrackSizingFunction::MaxContent
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                    children![
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("map:")
+                        ),
+                        (
+                            Map,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue(String::new()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                    ]
+                ),
+                (
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: vec![
+                            GridTrack::max_content(),
+                            GridTrack::minmax(
+                                MinTrackSizingFunction::Px(200.),
+                                MaxTrackSizingFunction::MaxContent
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                    children![
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("balanced:")
+                        ),
+                        (
+                            Balanced,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue("yes".to_string()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("cities & knights:")
+                        ),
+                        (
+                            CitiesAndKnights,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue("no".to_string()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("house rules:")
+                        ),
+                        (
+                            HouseRules,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue(String::new()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                    ]
+                ),
+                (
+                    Node {
+                        display: Display::Grid,
+                        grid_template_columns: vec![
+                            GridTrack::max_content(),
+                            GridTrack::minmax(
+                                MinTrackSizingFunction::Px(200.),
+                                MaxTrackSizingFunction::MaxContent
+                            ),
+                        ],
+                        ..Default::default()
+                    },
+                    children![
+                        (
+                            TextFont {
+                                font_size: 34.,
+                                ..default()
+                            },
+                            Text::new("load:")
+                        ),
+                        (
+                            Load,
+                            Node {
+                                border: UiRect::all(Val::Px(1.0)),
+                                padding: UiRect::all(Val::Percent(1.0)),
+                                ..default()
+                            },
+                            TextInputInactive(true),
+                            BorderColor::all(BORDER_COLOR_INACTIVE),
+                            BackgroundColor(BACKGROUND_COLOR),
+                            TextInput,
+                            TextInputValue(String::new()),
+                            TextInputTextFont(TextFont {
+                                font_size: 34.,
+                                ..default()
+                            }),
+                            bevy_ui_widgets::observe(text_input_in),
+                            bevy_ui_widgets::observe(text_input_out),
+                            TextInputTextColor(TextColor(TEXT_COLOR)),
+                        ),
+                    ]
+                ),
                 (
                     JoinButton,
                     children![
@@ -225,6 +924,72 @@ pub fn setup_lobby(mut commands: Commands<'_, '_>) {
                     BorderColor::all(BORDER_COLOR_INACTIVE),
                     Button
                 ),
+                (
+                    ReplayButton,
+                    children![
+                        TextFont {
+                            font_size: 34.,
+                            ..default()
+                        },
+                        Text::new("replay"),
+                        TextColor(TEXT_COLOR),
+                    ],
+                    Node {
+                        display: Display::Grid,
+                        padding: UiRect::all(Val::Percent(2.0)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        justify_self: JustifySelf::Center,
+                        justify_content: JustifyContent::End,
+                        ..Default::default()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                    BorderColor::all(BORDER_COLOR_INACTIVE),
+                    Button
+                ),
+                (
+                    HotSeatButton,
+                    children![
+                        TextFont {
+                            font_size: 34.,
+                            ..default()
+                        },
+                        Text::new("hot seat"),
+                        TextColor(TEXT_COLOR),
+                    ],
+                    Node {
+                        display: Display::Grid,
+                        padding: UiRect::all(Val::Percent(2.0)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        justify_self: JustifySelf::Center,
+                        justify_content: JustifyContent::End,
+                        ..Default::default()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                    BorderColor::all(BORDER_COLOR_INACTIVE),
+                    Button
+                ),
+                (
+                    EditorButton,
+                    children![
+                        TextFont {
+                            font_size: 34.,
+                            ..default()
+                        },
+                        Text::new("map editor"),
+                        TextColor(TEXT_COLOR),
+                    ],
+                    Node {
+                        display: Display::Grid,
+                        padding: UiRect::all(Val::Percent(2.0)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        justify_self: JustifySelf::Center,
+                        justify_content: JustifyContent::End,
+                        ..Default::default()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                    BorderColor::all(BORDER_COLOR_INACTIVE),
+                    Button
+                ),
             ]
         )],
     ));
@@ -233,28 +998,121 @@ fn wait_for_players(
     mut commands: Commands<'_, '_>,
     mut socket: ResMut<'_, MatchboxSocket>,
     mut next_state: ResMut<'_, NextState<AppState>>,
-    room_query: Single<'_, '_, &'static TextInputValue, With<Room>>,
+    mut menu_state: ResMut<'_, NextState<MenuState>>,
+    board_query: Single<'_, '_, &'static TextInputValue, With<Board>>,
+    seats: Res<'_, RoomSeats>,
+    loaded: Option<Res<'_, LoadedGame>>,
+    map: Option<Res<'_, LoadedMap>>,
+    mut claims: Local<'_, HashMap<PeerId, (PlayerHandle, u64)>>,
+    mut maps: Local<'_, HashMap<PeerId, u64>>,
 ) {
     if socket.get_channel(0).is_err() {
         return; // we've already started
     }
 
     // Check for new connections
-    socket.update_peers();
+    let new_peers = socket.update_peers();
     let players = socket.players();
 
-    let num_players = room_query
-        .0
-        .parse()
-        .expect("player count should be a number");
+    let num_players = seats.players;
+    let bots = &seats.bots;
+    let total = num_players + bots.len();
+    let seating = Seating {
+        bots: bots.clone(),
+        local: false,
+    };
+
+    // when loading a game everyone gets back the handle they had when the game was saved, so
+    // every peer tells the others which handle it had (and which game it loaded)
+    if let Some(loaded) = &loaded
+        && let Ok(channel) = socket.get_channel_mut(RELIABLE_CHANNEL)
+    {
+        for (peer, state) in new_peers {
+            if state == PeerState::Connected {
+                channel.send(loaded.0.claim(), peer);
+            }
+        }
+        for (peer, packet) in channel.receive() {
+            if let Some(claim) = save::decode_claim(&packet) {
+                claims.insert(peer, claim);
+            }
+        }
+    } else if let Some(map) = &map
+        && let Ok(channel) = socket.get_channel_mut(RELIABLE_CHANNEL)
+    {
+        // same for custom maps (a save already has its board), every peer tells the others which
+        // map it loaded
+        for (peer, state) in new_peers {
+            if state == PeerState::Connected {
+                channel.send(map.0.id().to_le_bytes().into(), peer);
+            }
+        }
+        for (peer, packet) in channel.receive() {
+            if let Ok(id) = packet[..].try_into() {
+                maps.insert(peer, u64::from_le_bytes(id));
+            }
+        }
+    }
+
     if players.len() < num_players {
         return; // wait for more players
     }
 
+    if let Some(map) = &map {
+        for player in &players {
+            if let PlayerType::Remote(peer) = player {
+                let Some(id) = maps.get(peer) else {
+                    return; // wait for everyone to send their map
+                };
+                if *id != map.0.id() {
+                    error!("{peer:?} loaded a different map");
+                    maps.clear();
+                    leave_room(&mut commands, &mut menu_state, &mut claims);
+                    return;
+                }
+            }
+        }
+    }
+    maps.clear();
+
+    let handles = if let Some(loaded) = &loaded {
+        let mut handles = vec![];
+        for player in &players {
+            let handle = match player {
+                PlayerType::Remote(peer) => {
+                    let Some((handle, id)) = claims.get(peer) else {
+                        return; // wait for everyone to claim their handle
+                    };
+                    if *id != loaded.0.id() {
+                        error!(
+                            "{peer:?} loaded a different game (or saved it at a different time)"
+                        );
+                        leave_room(&mut commands, &mut menu_state, &mut claims);
+                        return;
+                    }
+                    *handle
+                }
+                _ => loaded.0.player,
+            };
+            handles.push(handle.0);
+        }
+        let expected = seating.players(total).map(|handle| handle.0);
+        if !handles.iter().copied().sorted().eq(expected) {
+            error!("players did not claim different handles {handles:?}");
+            leave_room(&mut commands, &mut menu_state, &mut claims);
+            return;
+        }
+        handles
+    } else {
+        (0..num_players).collect()
+    };
+    claims.clear();
+
     info!("All peers have joined, going in-game");
 
     // determine the seed
-    let id = socket.id().expect("no peer id assigned").0.as_u64_pair();
+    let own_id = socket.id().expect("no peer id assigned");
+    let id = own_id.0.as_u64_pair();
     let mut seed = id.0 ^ id.1;
     for peer in socket.connected_peers() {
         let peer_id = peer.0.as_u64_pair();
@@ -262,13 +1120,19 @@ fn wait_for_players(
     }
     commands.insert_resource(SessionSeed(seed));
 
+    // bots are run by the peer with the lowest id, for everyone else they are remote players
+    let host = socket
+        .connected_peers()
+        .chain(std::iter::once(own_id))
+        .min_by_key(|peer| peer.0.as_u64_pair())
+        .unwrap_or(own_id);
+
     // create a GGRS P2P session
     let mut session_builder = ggrs::SessionBuilder::<GgrsSessionConfig>::new()
-        .with_num_players(num_players)
-        // .with_desync_detection_mode(DesyncDetection::On { interval: 1 });
-        .with_desync_detection_mode(DesyncDetection::Off);
+        .with_num_players(total)
+        .with_desync_detection_mode(DesyncDetection::On { interval: 1 });
 
-    for (i, player) in players.into_iter().enumerate() {
+    for (player, i) in players.into_iter().zip(handles) {
         if player == PlayerType::Local {
             commands.insert_resource(LocalPlayerHandle(i));
         }
@@ -277,6 +1141,21 @@ fn wait_for_players(
             .add_player(player, i)
             .expect("failed to add player");
     }
+    for handle in bots {
+        let player = if host == own_id {
+            PlayerType::Local
+        } else {
+            PlayerType::Remote(host)
+        };
+        session_builder = session_builder
+            .add_player(player, handle.0)
+            .expect("failed to add bot");
+    }
+    commands.insert_resource(if host == own_id {
+        Bots::new(bots.iter().copied())
+    } else {
+        Bots::default()
+    });
 
     // move the channel out of the socket (required because GGRS takes ownership of it)
     let socket = socket.take_channel(0).unwrap();
@@ -286,11 +1165,28 @@ fn wait_for_players(
         .start_p2p_session(socket)
         .expect("failed to start session");
 
-    commands.insert_resource(PlayerCount(num_players as u8));
+    commands.insert_resource(PlayerCount(total as u8));
+    // loaded games get their board size from the save
+    commands.insert_resource(board_size(&board_query, total));
+    commands.insert_resource(seating);
     commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
+    commands.remove_resource::<HotSeat>();
     next_state.set(AppState::InGame);
 }
 
+fn leave_room(
+    commands: &mut Commands<'_, '_>,
+    menu_state: &mut NextState<MenuState>,
+    claims: &mut HashMap<PeerId, (PlayerHandle, u64)>,
+) {
+    commands.remove_resource::<MatchboxSocket>();
+    commands.remove_resource::<RoomSeats>();
+    commands.remove_resource::<LoadedGame>();
+    commands.remove_resource::<LoadedMap>();
+    claims.clear();
+    menu_state.set(MenuState::Lobby);
+}
+
 fn focus(
     focus: Res<'_, InputFocus>,
     mut text_inputs: Query<'_, '_, (Entity, &mut TextInputInactive, &mut BorderColor)>,
@@ -17,13 +17,20 @@ use bevy::{
 };
 use bevy_ui_anchor::AnchorUiPlugin;
 
-use crate::{game::GamePlugin, lobby::LobbyPlugin};
+use crate::{
+    game::{GamePlugin, synctest::SyncTest},
+    lobby::LobbyPlugin,
+};
 #[derive(Debug, Default, Component)]
 pub struct MainCamera;
 
 pub static WINDOW_HEIGHT: f32 = 1080.;
 pub static WINDOW_WIDTH: f32 = 1920.;
-fn main() {
+fn main() -> AppExit {
+    // `--synctest` plays a game between bots without a window, to check rollbacks
+    if let Some(sync_test) = SyncTest::from_args() {
+        return game::synctest::run(sync_test);
+    }
     App::new()
         .add_plugins(DefaultPlugins)
         .init_state::<AppState>()
@@ -31,7 +38,7 @@ fn main() {
         .add_plugins(AnchorUiPlugin::<MainCamera>::new())
         .add_plugins((LobbyPlugin, GamePlugin))
         .add_systems(Update, resize)
-        .run();
+        .run()
 }
 
 fn setup(
@@ -73,4 +80,5 @@ pub enum AppState {
     Menu,
     InGame,
     GameOver,
+    Editor,
 }
@@ -1,4 +1,8 @@
+use std::hash::Hasher;
+
 use bevy::prelude::*;
+use serde::Serialize;
+
 pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 pub const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
@@ -16,3 +20,71 @@ pub trait CheckedAdd<Rhs = Self> {
     type Output;
     fn checked_add(self, rhs: Rhs) -> Option<Self::Output>;
 }
+
+/// 64 bit FNV-1a, unlike `DefaultHasher` it comes out the same with every rust version and on every
+/// platform (integers are hashed as little endian and `usize` as 64 bits), so hashes can be
+/// compared between peers and saved
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher(u64);
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// hashes things that aren't `Hash` in their serialized form
+pub fn stable_hash(value: &impl Serialize) -> u64 {
+    let mut hasher = StableHasher::default();
+    match serde_json::to_vec(value) {
+        Ok(bytes) => hasher.write(&bytes),
+        Err(error) => error!(
+            "couldn't serialize {}: {error}",
+            std::any::type_name_of_val(value)
+        ),
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the published FNV-1a test vectors, so this doesn't change without noticing
+    #[test]
+    fn stable_hash_is_fnv() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StableHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(stable_hash(&"a"), hash(b"\"a\""));
+    }
+}